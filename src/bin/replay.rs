@@ -0,0 +1,43 @@
+//! Replays a recording made with `Canvas::start_recording` offscreen, writing every frame as a PNG.
+//!
+//! Usage: replay <recording> [output directory]
+
+use std::io::BufReader;
+use std::fs::File;
+use std::path::PathBuf;
+
+use wgpu_canvas::{Renderer, Atlas, Player, Instruction, RgbaImage};
+
+#[path = "../renderer/offscreen.rs"]
+mod offscreen;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let (Some(recording), output) = (args.next(), args.next()) else {
+        eprintln!("usage: replay <recording> [output directory]");
+        std::process::exit(2);
+    };
+    let output = PathBuf::from(output.unwrap_or_else(|| ".".to_string()));
+    if let Err(error) = replay(&recording, &output) {
+        eprintln!("replay: {error}");
+        std::process::exit(1);
+    }
+}
+
+fn replay(recording: &str, output: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    let player = Player::new(BufReader::new(File::open(recording)?))?;
+    std::fs::create_dir_all(output)?;
+
+    let (device, queue) = offscreen::device()?;
+    let mut renderer = offscreen::renderer(&device);
+    let mut atlas = Atlas::default();
+
+    for (index, frame) in player.enumerate() {
+        let frame = frame?;
+        let image = offscreen::render(&device, &queue, &mut renderer, &mut atlas, frame.size, frame.instructions);
+        let path = output.join(format!("frame_{index:05}.png"));
+        image.save(&path)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use wgpu::{RenderPassDepthStencilAttachment, RenderPassColorAttachment, CommandEncoderDescriptor, TextureViewDescriptor, RequestAdapterOptions, SurfaceConfiguration, RenderPassDescriptor, InstanceDescriptor, DepthStencilState, TextureDescriptor, TextureDimension, MultisampleState, DeviceDescriptor, PowerPreference, CompareFunction, WindowHandle, DepthBiasState, TextureUsages, TextureFormat, StencilState, TextureView, Operations, Instance, Features, Extent3d, Surface, StoreOp, LoadOp, Limits, Device, Queue, Trace};
 
-use crate::{Renderer, Atlas, Instruction};
+use std::io::{self, Write};
+
+use crate::{Renderer, PostRenderer, Effect, Atlas, Instruction, Recorder};
 
 const SAMPLE_COUNT: u32 = 4;
 
@@ -14,7 +16,14 @@ pub struct Canvas<'surface> {
     msaa_view: Option<TextureView>,
     depth_view: TextureView,
     renderer: Renderer,
-    old: Vec<Instruction>
+    /// Created with the first effect.
+    post_renderer: Option<PostRenderer>,
+    effects: Vec<Effect>,
+    /// The textures the frame is resolved into and the effects go back and forth between,
+    /// created with the first frame that has effects.
+    post_views: Option<[TextureView; 2]>,
+    old: Vec<Instruction>,
+    recording: Option<io::Result<Recorder<Box<dyn Write + Send>>>>,
 }
 
 impl<'surface> Canvas<'surface> {
@@ -73,7 +82,7 @@ impl<'surface> Canvas<'surface> {
         };
 
         let depth_stencil = DepthStencilState {
-            format: TextureFormat::Depth32Float,
+            format: TextureFormat::Depth24PlusStencil8,
             depth_write_enabled: true,
             depth_compare: CompareFunction::GreaterEqual,
             stencil: StencilState::default(),
@@ -96,7 +105,11 @@ impl<'surface> Canvas<'surface> {
             msaa_view,
             depth_view,
             renderer,
-            old: vec![]
+            post_renderer: None,
+            effects: vec![],
+            post_views: None,
+            old: vec![],
+            recording: None,
         }
     }
 
@@ -113,18 +126,37 @@ impl<'surface> Canvas<'surface> {
                 self.msaa_view = Some(Self::create_msaa_view(&self.device, &self.config));
             }
             self.depth_view = Self::create_depth_view(&self.device, &self.config);
+            self.post_views = None;
             self.old = vec![];
         }
     }
 
+    /// Runs `effects` in order over every frame drawn from now on, before it is presented.
+    ///
+    /// Changing them redraws the next frame even when its items are the same, so effects
+    /// changing over time, like the seed of [`Effect::FilmGrain`], are set before every draw.
+    pub fn set_effects(&mut self, effects: Vec<Effect>) {
+        if !effects.is_empty() && self.post_renderer.is_none() {
+            self.post_renderer = Some(PostRenderer::new(&self.device, &self.queue, &self.config.format));
+        }
+        if effects.is_empty() {self.post_views = None;}
+        self.effects = effects;
+        self.old = vec![];
+    }
+
     /// Draws the given `items` using the provided `atlas`.
     ///
-    /// Handles render pass setup, MSAA, and depth buffer automatically.
+    /// Handles render pass setup, MSAA, and depth buffer automatically. With effects, the frame
+    /// is resolved into a texture of its own and the effects draw it onto the surface.
     pub fn draw(&mut self, items: Vec<Instruction>) {
         self.atlas.trim();
         //TODO: Get a better diff system, one that probably diffs on the vertices bytes too
         if self.old == items {return;}
         self.old = items.clone();
+        if let Some(Ok(recorder)) = &mut self.recording
+            && let Err(error) = recorder.record((self.config.width, self.config.height), &items) {
+            self.recording = Some(Err(error));
+        }
         self.renderer.prepare(
             &self.device,
             &self.queue,
@@ -135,11 +167,18 @@ impl<'surface> Canvas<'surface> {
         let output = self.surface.get_current_texture().unwrap();
         let frame_view = output.texture.create_view(&TextureViewDescriptor::default());
         let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
+        if !self.effects.is_empty() && self.post_views.is_none() {
+            self.post_views = Some([(); 2].map(|_| Self::create_post_view(&self.device, &self.config)));
+        }
+        let resolved = match &self.post_views {
+            Some(views) if !self.effects.is_empty() => &views[0],
+            _ => &frame_view,
+        };
         let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(RenderPassColorAttachment {
-                view: if SAMPLE_COUNT > 1 {self.msaa_view.as_ref().unwrap()} else {&frame_view},
-                resolve_target: if SAMPLE_COUNT > 1 {Some(&frame_view)} else {None},
+                view: if SAMPLE_COUNT > 1 {self.msaa_view.as_ref().unwrap()} else {resolved},
+                resolve_target: if SAMPLE_COUNT > 1 {Some(resolved)} else {None},
                 ops: Operations {
                     load: LoadOp::Clear(wgpu::Color {
                         r: 0.0,
@@ -156,7 +195,10 @@ impl<'surface> Canvas<'surface> {
                     load: LoadOp::Clear(0.0),
                     store: StoreOp::Store,
                 }),
-                stencil_ops: None,
+                stencil_ops: Some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: StoreOp::Discard,
+                }),
             }),
             occlusion_query_set: None,
             timestamp_writes: None,
@@ -166,10 +208,35 @@ impl<'surface> Canvas<'surface> {
 
         drop(rpass);
 
+        if let (Some(post_renderer), Some(views)) = (&mut self.post_renderer, &self.post_views) {
+            post_renderer.render(&self.device, &self.queue, &mut encoder, &self.effects, views, &frame_view);
+        }
+
         self.queue.submit(Some(encoder.finish()));
         output.present();
     }
 
+    /// Records every frame drawn from now on to `writer`, in the format read by [`crate::Player`].
+    ///
+    /// Only the instructions are recorded: the effects of [`Canvas::set_effects`] and changes to
+    /// them are not, so frames play back without them. Replaces any recording in progress
+    /// without finishing it.
+    pub fn start_recording(&mut self, writer: impl Write + Send + 'static) -> io::Result<()> {
+        let recorder = Recorder::new(Box::new(writer) as Box<dyn Write + Send>)?;
+        self.recording = Some(Ok(recorder));
+        //Redraw the current frame so the recording starts with it
+        self.old = vec![];
+        Ok(())
+    }
+
+    /// Finishes the recording in progress, returning the first error hit while writing it.
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recording.take() {
+            Some(recording) => recording?.finish().map(drop),
+            None => Ok(()),
+        }
+    }
+
     fn create_msaa_view(device: &Device, config: &SurfaceConfiguration) -> TextureView {
         device.create_texture(&TextureDescriptor{
             label: Some("Multisampled frame descriptor"),
@@ -188,6 +255,24 @@ impl<'surface> Canvas<'surface> {
         .create_view(&TextureViewDescriptor::default())
     }
 
+    fn create_post_view(device: &Device, config: &SurfaceConfiguration) -> TextureView {
+        device.create_texture(&TextureDescriptor{
+            label: Some("Post processing texture"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&TextureViewDescriptor::default())
+    }
+
     fn create_depth_view(device: &Device, config: &SurfaceConfiguration) -> TextureView {
         device.create_texture(&TextureDescriptor {
             label: Some("Depth Stencil Texture"),
@@ -199,7 +284,7 @@ impl<'surface> Canvas<'surface> {
             mip_level_count: 1,
             sample_count: SAMPLE_COUNT,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
+            format: TextureFormat::Depth24PlusStencil8,
             usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         })
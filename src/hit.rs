@@ -0,0 +1,263 @@
+use crate::{Instruction, Item, Clip, Mask, Image, Shape, Path, Text};
+use crate::transform::{apply, invert, invert3, project};
+
+impl Instruction {
+    /// Whether `point` is on the item, inside the clip of its area. The point is in screen
+    /// space and mapped back through the transform of the area before testing the item.
+    ///
+    /// Clips, masks and layers pushed by earlier instructions are not known here,
+    /// [`Instruction::hit`] and [`Instruction::hits`] follow them.
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        self.test(point, |offset, point| self.1.contains(offset, point))
+    }
+
+    /// Index of the topmost instruction under `point`, the last one drawn there.
+    pub fn hit(instructions: &[Instruction], point: (f32, f32)) -> Option<usize> {
+        let points = Self::points(instructions, point);
+        instructions.iter().enumerate().rposition(|(i, instruction)| points[i].is_some_and(|p| instruction.contains(p)))
+    }
+
+    /// Indices of every instruction under `point`, topmost first.
+    pub fn hits(instructions: &[Instruction], point: (f32, f32)) -> Vec<usize> {
+        let points = Self::points(instructions, point);
+        (0..instructions.len()).rev().filter(|i| points[*i].is_some_and(|p| instructions[*i].contains(p))).collect()
+    }
+
+    /// Where `point` lands in the layers pushed before each instruction and not yet popped,
+    /// mapped back through their transforms. `None` outside any of the clips, masks and
+    /// layers pushed before it.
+    fn points(instructions: &[Instruction], point: (f32, f32)) -> Vec<Option<(f32, f32)>> {
+        let mut clips: Vec<bool> = vec![];
+        let mut masks: Vec<bool> = vec![];
+        let mut layers: Vec<Option<(f32, f32)>> = vec![];
+        instructions.iter().map(|instruction| {
+            let inside = clips.last().copied().unwrap_or(true) && masks.last().copied().unwrap_or(true);
+            let point = layers.last().copied().unwrap_or(Some(point)).filter(|_| inside);
+            match &instruction.1 {
+                Item::PushClip(clip) => clips.push(point.is_some_and(|p| instruction.test(p, |offset, p| clip.contains(offset, p)))),
+                Item::PushMask(mask @ Mask::Image(_)) => masks.push(point.is_some_and(|p| instruction.test(p, |offset, p| mask.contains(offset, p)))),
+                Item::PushMask(mask) => masks.push(point.is_some_and(|p| instruction.bounded(p) && mask.contains(instruction.0.offset, p))),
+                Item::PushLayer(_) => layers.push(point.and_then(|p| instruction.local(p))),
+                Item::PopClip => {clips.pop();},
+                Item::PopMask => {masks.pop();},
+                Item::PopLayer => {layers.pop();},
+                _ => {}
+            }
+            point
+        }).collect()
+    }
+
+    /// Whether `point` is inside the clip of the area.
+    fn bounded(&self, point: (f32, f32)) -> bool {
+        let area = self.0;
+        area.bounds.is_none_or(|bounds| area.clip.unwrap_or_default().distance(bounds, point) <= 0.0)
+    }
+
+    /// Applies `test` to the offset of the area and `point` mapped back through its transform,
+    /// outside the clip of the area being a miss.
+    fn test(&self, point: (f32, f32), test: impl Fn((f32, f32), (f32, f32)) -> bool) -> bool {
+        self.local(point).is_some_and(|p| test(self.0.offset, p))
+    }
+
+    /// `point` mapped back through the transform of the area, `None` outside its clip or
+    /// where the transform does not reach.
+    fn local(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let area = self.0;
+        if !self.bounded(point) {return None;}
+        match area.transform {
+            Some(_) => invert3(area.projection()).map(|m| project(m, point)).filter(|(_, _, w)| *w > 0.0).map(|(x, y, w)| (x / w, y / w)),
+            None => Some(point),
+        }
+    }
+}
+
+impl Item {
+    /// Whether `point` is on the item placed at `offset`. Shapes and images follow their
+    /// outline, rotation, corners and hollow strokes, text its glyph boxes and paths what they
+    /// fill and stroke. Shadows are decoration and clips, masks and layers only change what
+    /// follows them, none of them contains a point.
+    pub fn contains(&self, offset: (f32, f32), point: (f32, f32)) -> bool {match self {
+        Item::Shape(shape) => shape.contains(offset, point),
+        Item::Image(image) => image.shape.contains(offset, point),
+        Item::Text(text) => {
+            let point = text.rotation(offset).and_then(invert).map_or(point, |m| apply(m, point));
+            text.contains((point.0 - offset.0, point.1 - offset.1))
+        },
+        Item::Shadow(_) | Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => false,
+        Item::Path(path) => path.contains((point.0 - offset.0, point.1 - offset.1)),
+    }}
+}
+
+impl Clip {
+    /// Whether `point` is inside the region of the clip placed at `offset`.
+    pub fn contains(&self, offset: (f32, f32), point: (f32, f32)) -> bool {match self {
+        Clip::Shape(shape) => shape.distance(shape.local(offset, point)) <= 0.0,
+        Clip::Path(data, rule) => data.contains((point.0 - offset.0, point.1 - offset.1), *rule),
+    }}
+}
+
+impl Mask {
+    /// Whether the mask placed at `offset` lets anything through at `point`: a pixel of the
+    /// image that is not fully transparent, or any of the instructions.
+    pub fn contains(&self, offset: (f32, f32), point: (f32, f32)) -> bool {match self {
+        Mask::Image(image) => image.shape.contains(offset, point) && image.alpha(offset, point) > 0,
+        Mask::Items(items) => Instruction::hit(items, (point.0 - offset.0, point.1 - offset.1)).is_some(),
+    }}
+}
+
+impl Image {
+    /// Alpha of the pixel of the cropped image shown at `point`, for the image placed at `offset`.
+    fn alpha(&self, offset: (f32, f32), point: (f32, f32)) -> u8 {
+        let [w, h] = self.shape.wh();
+        let (x, y) = self.shape.local(offset, point);
+        let [x0, y0, x1, y1] = Image::crop(&self.image, (w, h));
+        let u = x0 + (x / w.max(f32::EPSILON) + 0.5).clamp(0.0, 1.0) * (x1 - x0);
+        let v = y0 + (y / h.max(f32::EPSILON) + 0.5).clamp(0.0, 1.0) * (y1 - y0);
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {return 0;}
+        let px = ((u * width as f32) as u32).min(width - 1);
+        let py = ((v * height as f32) as u32).min(height - 1);
+        self.image.get_pixel(px, py).0[3]
+    }
+}
+
+impl Shape {
+    /// Whether `point` is on the fill or the border of the shape placed at `offset`.
+    pub fn contains(&self, offset: (f32, f32), point: (f32, f32)) -> bool {
+        let Some(border) = self.border.filter(|b| b.width > 0.0) else {
+            return self.shape.contains(offset, point);
+        };
+        let d = self.shape.distance(self.shape.local(offset, point));
+        let inner = border.outset() - border.width;
+        let stroke = self.shape.stroke();
+        let hollow = stroke > 0.0 && !matches!(self.shape, crate::ShapeType::Arc(..));
+        d <= border.outset() && (d >= inner || !hollow || d >= -stroke)
+    }
+}
+
+impl Path {
+    /// Whether `point`, relative to the offset of the path's area, is on its fill or stroke.
+    /// A fill that is fully transparent with no gradient is not drawn, so it does not count.
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let fill = (self.color.3 > 0 || self.gradient.is_some()) && self.data.contains(point, self.fill_rule);
+        fill || self.stroke.is_some_and(|s| s.width > 0.0 && s.color.3 > 0 && self.data.distance(point) <= s.width / 2.0)
+    }
+}
+
+impl Text {
+    /// Whether `point`, relative to the top left of the text, is inside the box of a glyph.
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        self.lines().iter().flat_map(|line| &line.2).any(|c| {
+            let (x, y, w, h) = c.1;
+            point.0 >= x && point.0 <= x + w && point.1 >= y && point.1 <= y + h
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Area, Border, ClipShape, Color, FillRule, Layer, PathData, ShapeType, StrokeAlign, Stroke, LineCap, LineJoin, Transform};
+    use super::*;
+
+    fn shape(offset: (f32, f32), shape: ShapeType) -> Instruction {
+        Instruction(Area::new(offset, None), Item::Shape(Shape::new(shape, Color::BLACK)))
+    }
+
+    fn square(offset: (f32, f32), size: f32) -> Instruction {
+        shape(offset, ShapeType::Rectangle(0.0, (size, size), 0.0))
+    }
+
+    fn push(item: Item) -> Instruction {Instruction(Area::default(), item)}
+
+    #[test]
+    fn shapes_follow_their_outline() {
+        //A 40 by 10 bar turned upright, centered in its 10 by 40 box
+        let bar = shape((10.0, 10.0), ShapeType::Rectangle(0.0, (40.0, 10.0), 90.0));
+        assert!(bar.contains((15.0, 45.0)));
+        assert!(!bar.contains((45.0, 15.0)));
+        let ring = shape((0.0, 0.0), ShapeType::Ellipse(2.0, (20.0, 20.0), 0.0));
+        assert!(ring.contains((1.0, 10.0)));
+        assert!(!ring.contains((10.0, 10.0)));
+        let rounded = shape((0.0, 0.0), ShapeType::RoundedRectangle(0.0, (20.0, 20.0), 0.0, 10.0));
+        assert!(rounded.contains((10.0, 10.0)));
+        assert!(!rounded.contains((1.0, 1.0)));
+        //A quarter of a ring, clockwise from the top
+        let arc = shape((0.0, 0.0), ShapeType::Arc(4.0, (20.0, 20.0), 0.0, 0.0, 90.0, false));
+        assert!(arc.contains((18.0, 8.0)));
+        assert!(!arc.contains((2.0, 8.0)));
+        assert!(!arc.contains((10.0, 10.0)));
+    }
+
+    #[test]
+    fn borders_and_strokes_count() {
+        let border = |align| Instruction(Area::default(), Item::Shape(Shape{
+            border: Some(Border{color: Color::WHITE, width: 4.0, align}),
+            ..Shape::new(ShapeType::Rectangle(0.0, (20.0, 20.0), 0.0), Color::BLACK)
+        }));
+        assert!(border(StrokeAlign::Outside).contains((-3.0, 10.0)));
+        assert!(!border(StrokeAlign::Inside).contains((-3.0, 10.0)));
+
+        let path = |color: Color| Instruction(Area::new((10.0, 10.0), None), Item::Path(Path{
+            data: PathData::parse("M0 0 H20 V20 H0 Z").unwrap(),
+            color,
+            gradient: None,
+            fill_rule: FillRule::NonZero,
+            stroke: Some(Stroke{color: Color::BLACK, width: 4.0, cap: LineCap::Butt, join: LineJoin::Miter}),
+        }));
+        assert!(path(Color::BLACK).contains((20.0, 20.0)));
+        assert!(path(Color::TRANSPARENT).contains((11.0, 20.0)));
+        assert!(!path(Color::TRANSPARENT).contains((20.0, 20.0)));
+    }
+
+    #[test]
+    fn the_last_instruction_drawn_is_on_top() {
+        let instructions = [square((0.0, 0.0), 20.0), square((10.0, 10.0), 20.0), push(Item::Shadow(crate::Shadow{
+            shape: ShapeType::Rectangle(0.0, (100.0, 100.0), 0.0), color: Color::BLACK, offset: (0.0, 0.0), blur: 0.0, spread: 0.0
+        }))];
+        assert_eq!(Instruction::hit(&instructions, (15.0, 15.0)), Some(1));
+        assert_eq!(Instruction::hits(&instructions, (15.0, 15.0)), vec![1, 0]);
+        assert_eq!(Instruction::hit(&instructions, (5.0, 5.0)), Some(0));
+        assert_eq!(Instruction::hit(&instructions, (50.0, 50.0)), None);
+    }
+
+    #[test]
+    fn area_clips_and_transforms_apply() {
+        let mut clipped = square((0.0, 0.0), 20.0);
+        clipped.0.bounds = Some((0.0, 0.0, 20.0, 20.0));
+        clipped.0.clip = Some(ClipShape{radii: [10.0; 4], angle: 0.0});
+        assert!(clipped.contains((10.0, 10.0)));
+        assert!(!clipped.contains((1.0, 1.0)));
+
+        let mut turned = shape((0.0, 0.0), ShapeType::Rectangle(0.0, (40.0, 10.0), 0.0));
+        turned.0.transform = Some(Transform::around((5.0, 5.0)).rotate(90.0));
+        assert!(turned.contains((5.0, 30.0)));
+        assert!(!turned.contains((30.0, 5.0)));
+
+        //Points the transform folds behind the viewer hit nothing
+        let mut flipped = square((0.0, 0.0), 100.0);
+        flipped.0.transform = Some(Transform::IDENTITY.rotate_y(60.0, 50.0));
+        assert!(flipped.contains((10.0, 10.0)));
+        assert!(!flipped.contains((-200.0, 10.0)));
+    }
+
+    #[test]
+    fn clips_masks_and_layers_apply_until_popped() {
+        let instructions = [
+            push(Item::PushClip(Clip::Shape(ShapeType::Rectangle(0.0, (10.0, 100.0), 0.0)))),
+            square((0.0, 0.0), 50.0),
+            push(Item::PushMask(Mask::Items(vec![square((0.0, 0.0), 5.0)]))),
+            square((0.0, 0.0), 50.0),
+            push(Item::PopMask),
+            push(Item::PopClip),
+            Instruction(Area{transform: Some(Transform::IDENTITY.translate(100.0, 0.0)), ..Area::default()}, Item::PushLayer(Layer::default())),
+            square((0.0, 0.0), 50.0),
+            push(Item::PopLayer),
+            square((0.0, 60.0), 10.0),
+        ];
+        assert_eq!(Instruction::hits(&instructions, (2.0, 2.0)), vec![3, 1]);
+        assert_eq!(Instruction::hits(&instructions, (8.0, 8.0)), vec![1]);
+        assert!(Instruction::hits(&instructions, (20.0, 20.0)).is_empty());
+        assert_eq!(Instruction::hits(&instructions, (120.0, 20.0)), vec![7]);
+        assert_eq!(Instruction::hits(&instructions, (5.0, 65.0)), vec![9]);
+    }
+}
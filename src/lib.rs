@@ -3,7 +3,7 @@ use std::sync::Arc;
 #[cfg(feature = "renderer")]
 mod renderer;
 #[cfg(feature = "renderer")]
-pub use renderer::{Renderer, Atlas};
+pub use renderer::{Renderer, Atlas, Effect, PostRenderer};
 
 #[cfg(feature = "canvas")]
 mod canvas;
@@ -17,9 +17,34 @@ pub use text::{Font, Text, Span, Align, Character};
 mod shape;
 pub use shape::Shape as ShapeType;
 
+mod path;
+pub use path::{PathData, PathCommand, PathError};
+
+mod transform;
+pub use transform::Transform;
+
+#[cfg(feature = "svg")]
+mod svg;
+#[cfg(feature = "svg")]
+pub use svg::{Svg, SvgError};
+
+#[cfg(feature = "pdf")]
+mod pdf;
+#[cfg(feature = "pdf")]
+pub use pdf::Pdf;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+mod recording;
+pub use recording::{Recorder, Player, Frame};
+
+mod hit;
+
 pub use image::RgbaImage;
 
 #[derive(Default, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color(pub u8, pub u8, pub u8, pub u8);
 impl Color {
     pub const WHITE: Self = Color(255, 255, 255, 255);
@@ -34,64 +59,760 @@ impl Color {
 }
 
 #[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Image {
     pub shape: ShapeType,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::image"))]
     pub image: Arc<RgbaImage>,
-    pub color: Option<Color>
+    pub color: Option<Color>,
+    /// Filters the colors of the image, after its tint.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub filter: Option<ColorMatrix>,
 }
 impl Image {
+    /// `image` in `shape`, tinted with `color` if any, without a filter.
+    pub fn new(shape: ShapeType, image: Arc<RgbaImage>, color: Option<Color>) -> Self {
+        Image{shape, image, color, filter: None}
+    }
+
     pub fn scale(&mut self, scale: f32) {self.shape = self.shape.scale(scale);}
     pub fn size(&self) -> (f32, f32) {self.shape.size()}
+
+    /// Texture coordinates `[x0, y0, x1, y1]` of the centered part of `image` that covers
+    /// `size` without stretching.
+    pub fn crop(image: &RgbaImage, size: (f32, f32)) -> [f32; 4] {
+        let wr = size.0 / image.width() as f32;
+        let hr = size.1 / image.height() as f32;
+
+        if hr > wr {
+            let d = (1.0 - (wr / hr)) / 2.0;
+            [d, 0.0, 1.0 - d, 1.0]
+        } else {
+            let d = (1.0 - (hr / wr)) / 2.0;
+            [0.0, d, 1.0, 1.0 - d]
+        }
+    }
+
+    /// The pixels of `image` shown in a shape of `size`, cropped as [`Image::crop`], tinted and
+    /// filtered like the image shader does, for exporters.
+    #[cfg(any(feature = "svg", feature = "pdf"))]
+    pub(crate) fn visible(image: &RgbaImage, size: (f32, f32), tint: Option<Color>, filter: Option<ColorMatrix>) -> RgbaImage {
+        let [x0, y0, x1, y1] = Image::crop(image, size);
+        let (iw, ih) = (image.width() as f32, image.height() as f32);
+        let (x, y) = ((x0 * iw).round() as u32, (y0 * ih).round() as u32);
+        let w = (((x1 - x0) * iw).round() as u32).clamp(1, image.width() - x.min(image.width() - 1));
+        let h = (((y1 - y0) * ih).round() as u32).clamp(1, image.height() - y.min(image.height() - 1));
+        let mut visible = image::imageops::crop_imm(image, x, y, w, h).to_image();
+        if let Some(tint) = tint.filter(|c| c.3 > 0) {
+            visible.pixels_mut().for_each(|p| p.0 = [tint.0, tint.1, tint.2, (tint.3 as u16 * p.0[3] as u16 / 255) as u8]);
+        }
+        if let Some(filter) = filter {
+            visible.pixels_mut().for_each(|p| {
+                let Color(r, g, b, a) = filter.apply(Color(p.0[0], p.0[1], p.0[2], p.0[3]));
+                p.0 = [r, g, b, a];
+            });
+        }
+        visible
+    }
 }
 impl std::fmt::Debug for Image {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Image").field("shape", &self.shape).field("image", &"Arc<RgbaImage>").field("color", &self.color).finish()
+        f.debug_struct("Image").field("shape", &self.shape).field("image", &"Arc<RgbaImage>").field("color", &self.color).field("filter", &self.filter).finish()
     }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Shape {
     pub shape: ShapeType,
-    pub color: Color
+    pub color: Color,
+    pub border: Option<Border>,
+    pub inner_shadow: Option<InnerShadow>,
+    /// Blurs what is drawn under the shape, within it, before its fill is drawn over it, for
+    /// frosted glass.
+    ///
+    /// Every shape with one draws all the items before it again, so a frame of `n` items with
+    /// `m` backdrops draws up to `n * m` of them; keep them few, or early in the frame.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub backdrop: Option<Blur>,
 }
 impl Shape {
-    pub fn scale(&mut self, scale: f32) {self.shape = self.shape.scale(scale);}
+    /// `shape` filled with `color`, without a border, inner shadow or backdrop.
+    pub fn new(shape: ShapeType, color: Color) -> Self {
+        Shape{shape, color, border: None, inner_shadow: None, backdrop: None}
+    }
+
+    pub fn scale(&mut self, scale: f32) {
+        self.shape = self.shape.scale(scale);
+        self.border = self.border.map(|b| b.scale(scale));
+        self.inner_shadow = self.inner_shadow.map(|s| s.scale(scale));
+        self.backdrop = self.backdrop.map(|b| Blur{radius: scale*b.radius, ..b});
+    }
     pub fn size(&self) -> (f32, f32) {self.shape.size()}
 }
 
+/// Where a [`Border`] sits relative to the edge of its shape.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrokeAlign {
+    #[default]
+    Inside,
+    Center,
+    Outside,
+}
+
+/// A separately colored band along the edge of a [`Shape`], drawn in the same pass as its fill.
+#[derive(Clone, Debug, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Border {
+    pub color: Color,
+    pub width: f32,
+    pub align: StrokeAlign,
+}
+impl Border {
+    pub fn scale(self, scale: f32) -> Self {
+        Border{color: self.color, width: scale*self.width, align: self.align}
+    }
+
+    /// How far the border reaches past the edge of the shape.
+    pub fn outset(&self) -> f32 {match self.align {
+        StrokeAlign::Inside => 0.0,
+        StrokeAlign::Center => self.width.max(0.0) / 2.0,
+        StrokeAlign::Outside => self.width.max(0.0),
+    }}
+}
+
+/// A shadow cast inwards from the edge of a [`Shape`], clipped to its interior.
+///
+/// `offset` is in the shape's own (rotated) space. With no `blur` or `offset`, a positive
+/// `spread` draws an inset border of that width.
+#[derive(Clone, Debug, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InnerShadow {
+    pub color: Color,
+    pub offset: (f32, f32),
+    pub blur: f32,
+    pub spread: f32,
+}
+impl InnerShadow {
+    pub fn scale(self, scale: f32) -> Self {InnerShadow{
+        color: self.color,
+        offset: (scale*self.offset.0, scale*self.offset.1),
+        blur: scale*self.blur,
+        spread: scale*self.spread,
+    }}
+}
+
+/// A blurred silhouette of `shape`, drawn as if cast by an item placed at the same `Area`.
+///
+/// `offset` moves the shadow in screen space, `spread` grows (or shrinks when negative) the
+/// silhouette before blurring and `blur` is the blur radius (twice the gaussian sigma).
+#[derive(Clone, Debug, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Shadow {
+    pub shape: ShapeType,
+    pub color: Color,
+    pub offset: (f32, f32),
+    pub blur: f32,
+    pub spread: f32,
+}
+impl Shadow {
+    pub fn scale(&mut self, scale: f32) {
+        self.shape = self.shape.scale(scale);
+        self.offset = (scale*self.offset.0, scale*self.offset.1);
+        self.blur *= scale;
+        self.spread *= scale;
+    }
+    pub fn size(&self) -> (f32, f32) {self.shape.size()}
+}
+
+/// How the interior of a [`Path`] is decided where its outlines overlap.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+/// Shape drawn at the open ends of a stroked [`Path`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+/// Shape drawn where two segments of a stroked [`Path`] meet.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// An outline centered on a [`Path`].
+#[derive(Default, Clone, Debug, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+impl Stroke {
+    pub fn scale(self, scale: f32) -> Self {
+        Stroke{width: scale*self.width, ..self}
+    }
+}
+
+/// The line or circle a [`Gradient`] runs along, in gradient space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientKind {
+    /// `Linear(start, end)`
+    Linear((f32, f32), (f32, f32)),
+    /// `Radial(center, radius)`
+    Radial((f32, f32), f32),
+}
+
+/// How a [`Gradient`] continues past its first and last stop.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientSpread {
+    #[default]
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+/// A fill that blends between color stops, interpolated in sRGB like SVG gradients.
+///
+/// `stops` are `(offset, color)` pairs with offsets from 0.0 to 1.0 in ascending order.
+/// `transform` is an affine matrix `[a, b, c, d, e, f]` (as in SVG) taking gradient space
+/// to the coordinates of the path it fills.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<(f32, Color)>,
+    pub spread: GradientSpread,
+    pub transform: [f32; 6],
+}
+impl Gradient {
+    pub const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+    pub fn linear(start: (f32, f32), end: (f32, f32), stops: Vec<(f32, Color)>) -> Self {
+        Gradient{kind: GradientKind::Linear(start, end), stops, spread: GradientSpread::Pad, transform: Self::IDENTITY}
+    }
+
+    pub fn radial(center: (f32, f32), radius: f32, stops: Vec<(f32, Color)>) -> Self {
+        Gradient{kind: GradientKind::Radial(center, radius), stops, spread: GradientSpread::Pad, transform: Self::IDENTITY}
+    }
+
+    pub fn scale(self, scale: f32) -> Self {
+        Gradient{transform: self.transform.map(|v| scale*v), ..self}
+    }
+
+    /// Color at `t` along the gradient, after applying `spread`.
+    pub fn color_at(&self, t: f32) -> Color {
+        let t = match self.spread {
+            GradientSpread::Pad => t.clamp(0.0, 1.0),
+            GradientSpread::Repeat if !(0.0..=1.0).contains(&t) => t.rem_euclid(1.0),
+            GradientSpread::Repeat => t,
+            GradientSpread::Reflect => 1.0 - ((t.rem_euclid(2.0)) - 1.0).abs(),
+        };
+        let Some(first) = self.stops.first() else {return Color::TRANSPARENT};
+        if t <= first.0 {return first.1;}
+        for pair in self.stops.windows(2) {
+            let ((o1, c1), (o2, c2)) = (pair[0], pair[1]);
+            if t <= o2 {
+                let f = if o2 > o1 {(t - o1) / (o2 - o1)} else {1.0};
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+                return Color(mix(c1.0, c2.0), mix(c1.1, c2.1), mix(c1.2, c2.2), mix(c1.3, c2.3));
+            }
+        }
+        self.stops.last().unwrap().1
+    }
+}
+
+/// Arbitrary vector outlines, filled with `color` and optionally stroked.
+///
+/// A `gradient` replaces `color` for the fill, a fully transparent `color` with no gradient
+/// only draws the stroke.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Path {
+    pub data: PathData,
+    pub color: Color,
+    pub gradient: Option<Gradient>,
+    pub fill_rule: FillRule,
+    pub stroke: Option<Stroke>,
+}
+impl Path {
+    pub fn scale(&mut self, scale: f32) {
+        self.data = std::mem::take(&mut self.data).scale(scale);
+        self.gradient = self.gradient.take().map(|g| g.scale(scale));
+        self.stroke = self.stroke.map(|s| s.scale(scale));
+    }
+    /// Extent of the path from the offset of its `Area`, including half the stroke width.
+    pub fn size(&self) -> (f32, f32) {
+        let (x, y, w, h) = self.data.bounds();
+        let half = self.stroke.map(|s| s.width.max(0.0) / 2.0).unwrap_or_default();
+        ((x+w+half).max(0.0), (y+h+half).max(0.0))
+    }
+}
+
+/// Rounds the corners of the `bounds` of an [`Area`] and rotates them around their center.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClipShape {
+    /// Corner radii clockwise from the top left, capped at half the shorter side.
+    pub radii: [f32; 4],
+    /// Clockwise rotation in degrees.
+    pub angle: f32,
+}
+impl ClipShape {
+    pub fn rounded(radius: f32) -> Self {
+        ClipShape{radii: [radius; 4], angle: 0.0}
+    }
+
+    pub fn scale(self, scale: f32) -> Self {
+        ClipShape{radii: self.radii.map(|r| scale*r), ..self}
+    }
+
+    /// Signed distance from `point` to the clip of `bounds`, negative inside.
+    pub fn distance(&self, (x, y, w, h): (f32, f32, f32, f32), point: (f32, f32)) -> f32 {
+        let (hw, hh) = (w / 2.0, h / 2.0);
+        let (dx, dy) = (point.0 - (x + hw), point.1 - (y + hh));
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let p = (dx*cos + dy*sin, dy*cos - dx*sin);
+        let [tl, tr, br, bl] = self.radii;
+        let r = match (p.0 > 0.0, p.1 > 0.0) {
+            (false, false) => tl,
+            (true, false) => tr,
+            (true, true) => br,
+            (false, true) => bl,
+        };
+        shape::sd_box(p, (hw, hh), r.clamp(0.0, hw.min(hh).max(0.0)))
+    }
+
+    /// The outline of the clip of `bounds`, in screen space.
+    pub fn outline(&self, (x, y, w, h): (f32, f32, f32, f32)) -> PathData {
+        let rotation = Transform::around((w / 2.0, h / 2.0)).rotate(self.angle).screen((x, y));
+        PathData::rounded_rectangle(x, y, w, h, self.radii).transform(rotation)
+    }
+}
+
+/// The region an [`Item::PushClip`] limits everything drawn after it to, until the matching
+/// [`Item::PopClip`]. It is placed by the area of its instruction like any item, bounds and
+/// transform included, and nested clips intersect.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Clip {
+    /// The filled outline of a shape. Strokes do not hollow it out, and corner smoothing and
+    /// the corner radius of polygons are not followed.
+    Shape(ShapeType),
+    /// The fill of a path.
+    Path(PathData, FillRule),
+}
+impl Clip {
+    pub fn scale(&mut self, scale: f32) {match self {
+        Clip::Shape(shape) => *shape = shape.scale(scale),
+        Clip::Path(data, _) => *data = std::mem::take(data).scale(scale),
+    }}
+
+    pub fn size(&self) -> (f32, f32) {match self {
+        Clip::Shape(shape) => shape.size(),
+        Clip::Path(data, _) => {
+            let (x, y, w, h) = data.bounds();
+            ((x+w).max(0.0), (y+h).max(0.0))
+        }
+    }}
+
+    /// The outline of the clip relative to the offset of its area, and the rule filling it.
+    pub fn outline(&self) -> (PathData, FillRule) {match self {
+        Clip::Shape(shape) => {
+            let [w, h] = shape.wh();
+            let placement = Transform::IDENTITY.rotate(shape.angle()).translate(w / 2.0, h / 2.0).matrix;
+            (shape.outline(0.0).transform(placement), FillRule::NonZero)
+        },
+        Clip::Path(data, rule) => (data.clone(), *rule),
+    }}
+}
+
+/// What an [`Item::PushMask`] takes the alpha of, multiplying the alpha of everything drawn
+/// after it until the matching [`Item::PopMask`]. Nothing shows outside of the mask.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mask {
+    /// An image, placed, cropped and clipped to its shape like an [`Item::Image`] in the area
+    /// of the mask.
+    Image(Image),
+    /// The coverage of instructions, placed relative to the offset of the area of the mask.
+    /// The transform of that area does not apply to them, their own areas transform them.
+    Items(Vec<Instruction>),
+}
+impl Mask {
+    pub fn scale(&mut self, scale: f32) {match self {
+        Mask::Image(image) => image.scale(scale),
+        Mask::Items(items) => items.iter_mut().for_each(|i| i.scale(scale)),
+    }}
+
+    pub fn size(&self) -> (f32, f32) {match self {
+        Mask::Image(image) => image.size(),
+        Mask::Items(items) => items.iter().fold((0.0, 0.0), |(w, h), Instruction(area, item)| {
+            let size = item.size();
+            (w.max(area.offset.0 + size.0), h.max(area.offset.1 + size.1))
+        }),
+    }}
+}
+
+/// How the colors of an item or an [`Item::PushLayer`] combine with what is already drawn
+/// under them. The Porter-Duff operators only apply where the item draws, not to the whole
+/// target.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// Draws over what is under, by alpha.
+    #[default]
+    Normal,
+    /// Multiplies the colors, darkening. Only exact over opaque backdrops.
+    Multiply,
+    /// Multiplies the inverse of the colors, lightening.
+    Screen,
+    /// Adds the colors.
+    Add,
+    /// Multiplies dark backdrops and screens light ones, keeping their contrast.
+    Overlay,
+    /// Keeps the darker of the colors.
+    Darken,
+    /// Keeps the lighter of the colors.
+    Lighten,
+    /// Subtracts the darker of the colors from the lighter.
+    Difference,
+    /// Clears what is under.
+    Clear,
+    /// Replaces what is under.
+    Source,
+    /// Draws only where something is under, replacing it.
+    SourceIn,
+    /// Draws only where nothing is under, clearing the rest.
+    SourceOut,
+    /// Draws over what is under, only where something is.
+    SourceAtop,
+    /// Draws under what is already there.
+    DestinationOver,
+    /// Keeps what is under only where this draws.
+    DestinationIn,
+    /// Erases what is under where this draws.
+    DestinationOut,
+    /// Keeps what is under over this, only where this draws.
+    DestinationAtop,
+    /// Draws where nothing is under and erases where something is.
+    Xor,
+}
+impl BlendMode {
+    /// Whether the mode depends on the colors under it in a way blending cannot compute, so
+    /// the renderer reads back what it is drawn over.
+    pub fn reads_back(&self) -> bool {
+        matches!(self, BlendMode::Overlay | BlendMode::Darken | BlendMode::Lighten | BlendMode::Difference)
+    }
+}
+
+/// What a blurred layer shows past the edges of what it draws.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlurEdge {
+    /// Transparency, so the layer fades out past its edges.
+    #[default]
+    Transparent,
+    /// The pixels at its edges, so it stays opaque up to them.
+    Clamp,
+    /// Its pixels mirrored at its edges.
+    Mirror,
+}
+
+/// A gaussian blur of a whole layer, `radius` being twice the gaussian sigma like the blur of
+/// a [`Shadow`].
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Blur {
+    pub radius: f32,
+    pub edge: BlurEdge,
+}
+impl Blur {
+    pub fn new(radius: f32) -> Self {Blur{radius, edge: BlurEdge::Transparent}}
+}
+
+/// A 4x5 matrix filtering colors, row major like SVG's `feColorMatrix`. Every row weighs the
+/// red, green, blue and alpha in, from 0.0 to 1.0 in sRGB without premultiplied alpha, and adds
+/// its fifth column, giving the red, green, blue and alpha out. The presets are the matrices of
+/// the CSS filter functions and chain with [`ColorMatrix::then`].
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorMatrix(pub [f32; 20]);
+impl Default for ColorMatrix {
+    fn default() -> Self {Self::IDENTITY}
+}
+impl ColorMatrix {
+    pub const IDENTITY: Self = ColorMatrix([
+        1.0, 0.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ]);
+
+    /// Mixes the red, green and blue out by the row major 3x3 `m`, keeping alpha.
+    fn rgb(m: [f32; 9]) -> Self {
+        ColorMatrix([
+            m[0], m[1], m[2], 0.0, 0.0,
+            m[3], m[4], m[5], 0.0, 0.0,
+            m[6], m[7], m[8], 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Scales the red, green and blue by `slope` and adds `intercept`, keeping alpha.
+    fn linear(slope: f32, intercept: f32) -> Self {
+        ColorMatrix([
+            slope, 0.0, 0.0, 0.0, intercept,
+            0.0, slope, 0.0, 0.0, intercept,
+            0.0, 0.0, slope, 0.0, intercept,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Toward luminance gray by `amount`, from 0.0 to 1.0.
+    pub fn grayscale(amount: f32) -> Self {
+        let a = 1.0 - amount.clamp(0.0, 1.0);
+        Self::rgb([
+            0.2126 + 0.7874 * a, 0.7152 - 0.7152 * a, 0.0722 - 0.0722 * a,
+            0.2126 - 0.2126 * a, 0.7152 + 0.2848 * a, 0.0722 - 0.0722 * a,
+            0.2126 - 0.2126 * a, 0.7152 - 0.7152 * a, 0.0722 + 0.9278 * a,
+        ])
+    }
+
+    /// Toward sepia by `amount`, from 0.0 to 1.0.
+    pub fn sepia(amount: f32) -> Self {
+        let a = 1.0 - amount.clamp(0.0, 1.0);
+        Self::rgb([
+            0.393 + 0.607 * a, 0.769 - 0.769 * a, 0.189 - 0.189 * a,
+            0.349 - 0.349 * a, 0.686 + 0.314 * a, 0.168 - 0.168 * a,
+            0.272 - 0.272 * a, 0.534 - 0.534 * a, 0.131 + 0.869 * a,
+        ])
+    }
+
+    /// Scales the saturation, 0.0 being gray and 1.0 unchanged.
+    pub fn saturate(amount: f32) -> Self {
+        let s = amount.max(0.0);
+        Self::rgb([
+            0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s,
+            0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s,
+            0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s,
+        ])
+    }
+
+    /// Turns the hue by `degrees`.
+    pub fn hue_rotate(degrees: f32) -> Self {
+        let (s, c) = degrees.to_radians().sin_cos();
+        Self::rgb([
+            0.213 + c * 0.787 - s * 0.213, 0.715 - c * 0.715 - s * 0.715, 0.072 - c * 0.072 + s * 0.928,
+            0.213 - c * 0.213 + s * 0.143, 0.715 + c * 0.285 + s * 0.140, 0.072 - c * 0.072 - s * 0.283,
+            0.213 - c * 0.213 - s * 0.787, 0.715 - c * 0.715 + s * 0.715, 0.072 + c * 0.928 + s * 0.072,
+        ])
+    }
+
+    /// Scales the color, 0.0 being black and 1.0 unchanged.
+    pub fn brightness(amount: f32) -> Self {Self::linear(amount.max(0.0), 0.0)}
+
+    /// Scales the color away from mid gray, 0.0 being gray and 1.0 unchanged.
+    pub fn contrast(amount: f32) -> Self {
+        let c = amount.max(0.0);
+        Self::linear(c, 0.5 - 0.5 * c)
+    }
+
+    /// Toward the inverted color by `amount`, from 0.0 to 1.0.
+    pub fn invert(amount: f32) -> Self {
+        let a = amount.clamp(0.0, 1.0);
+        Self::linear(1.0 - 2.0 * a, a)
+    }
+
+    /// This filter followed by `next`.
+    pub fn then(self, next: ColorMatrix) -> Self {
+        let (a, b) = (self.0, next.0);
+        ColorMatrix(std::array::from_fn(|i| {
+            let (row, col) = (i / 5, i % 5);
+            let product = (0..4).map(|k| b[row * 5 + k] * a[k * 5 + col]).sum::<f32>();
+            if col == 4 {product + b[row * 5 + 4]} else {product}
+        }))
+    }
+
+    /// Filters `color`, clamping every component.
+    pub fn apply(&self, color: Color) -> Color {
+        let m = self.0;
+        let c = [color.0, color.1, color.2, color.3].map(|c| c as f32 / 255.0);
+        let out = |row: usize| {
+            let v = (0..4).map(|k| m[row * 5 + k] * c[k]).sum::<f32>() + m[row * 5 + 4];
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Color(out(0), out(1), out(2), out(3))
+    }
+}
+
+/// What an [`Item::PushLayer`] composites the instructions up to the matching
+/// [`Item::PopLayer`] with, once they are drawn together. Overlapping items inside a layer do
+/// not show through each other when it fades.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer {
+    /// Alpha of the whole layer, from 0.0 to 1.0.
+    pub opacity: f32,
+    pub blend: BlendMode,
+    /// Blurs the layer before it is composited, in its pixels before its transform.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blur: Option<Blur>,
+    /// Filters the colors of the layer, after its blur.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub filter: Option<ColorMatrix>,
+}
+impl Default for Layer {
+    fn default() -> Self {Layer{opacity: 1.0, blend: BlendMode::Normal, blur: None, filter: None}}
+}
+impl Layer {
+    pub fn new(opacity: f32) -> Self {Layer{opacity, ..Layer::default()}}
+
+    pub fn scale(&mut self, scale: f32) {
+        if let Some(blur) = &mut self.blur {blur.radius *= scale;}
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     pub offset: (f32, f32),
+    /// Clip rectangle `(x, y, width, height)` in screen space, applied after `transform`.
     pub bounds: Option<(f32, f32, f32, f32)>,
+    /// Rounds and rotates `bounds`, antialiasing the clipped edge.
+    pub clip: Option<ClipShape>,
+    pub transform: Option<Transform>,
+    /// How the item combines with what is under it. Clips, masks and layers ignore it, layers
+    /// blending by [`Layer::blend`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blend: BlendMode,
 }
 impl Area {
+    /// An area at `offset` clipped to `bounds`, untransformed and blending normally.
+    pub fn new(offset: (f32, f32), bounds: Option<(f32, f32, f32, f32)>) -> Self {
+        Area{offset, bounds, ..Area::default()}
+    }
+
     pub fn scale(self, scale: f32) -> Self {Area {
         offset: (scale*self.offset.0, scale*self.offset.1),
         bounds: self.bounds.map(|b| (scale*b.0, scale*b.1, scale*b.2, scale*b.3)),
+        clip: self.clip.map(|c| c.scale(scale)),
+        transform: self.transform.map(|t| Transform{
+            matrix: [t.matrix[0], t.matrix[1], t.matrix[2], t.matrix[3], scale*t.matrix[4], scale*t.matrix[5]],
+            origin: (scale*t.origin.0, scale*t.origin.1),
+            perspective: (t.perspective.0 / scale, t.perspective.1 / scale),
+        }),
+        blend: self.blend,
+    }}
+
+    /// The affine matrix taking the untransformed pixels of this area to the screen, without
+    /// the perspective of the transform.
+    pub fn matrix(&self) -> [f32; 6] {
+        self.transform.map(|t| t.screen(self.offset)).unwrap_or(transform::IDENTITY)
+    }
+
+    /// The row major 3x3 matrix taking the untransformed pixels of this area to the screen.
+    pub fn projection(&self) -> [f32; 9] {
+        self.transform.map(|t| t.projection(self.offset)).unwrap_or(transform::projective(transform::IDENTITY))
+    }
+
+    /// This area with `matrix`, in its untransformed pixels, applied before its transform.
+    #[cfg(feature = "renderer")]
+    pub(crate) fn within(self, matrix: [f32; 6]) -> Self {
+        let (x, y) = self.offset;
+        let screen = transform::multiply3(self.projection(), transform::projective(matrix));
+        let to = transform::projective([1.0, 0.0, 0.0, 1.0, -x, -y]);
+        let from = transform::projective([1.0, 0.0, 0.0, 1.0, x, y]);
+        Area{transform: Some(Transform::from_projective(transform::multiply3(to, transform::multiply3(screen, from)), (0.0, 0.0))), ..self}
+    }
+
+    /// This area moved `by` pixels, bounds and transform included.
+    #[cfg(feature = "renderer")]
+    pub(crate) fn moved(self, by: (f32, f32)) -> Self {Area {
+        offset: (self.offset.0 + by.0, self.offset.1 + by.1),
+        bounds: self.bounds.map(|b| (b.0 + by.0, b.1 + by.1, b.2, b.3)),
+        ..self
+    }}
+
+    /// The area of something laid out `by` pixels into this one, transformed about the same pivot.
+    #[cfg(feature = "renderer")]
+    pub(crate) fn inner(self, by: (f32, f32)) -> Self {Area {
+        offset: (self.offset.0 + by.0, self.offset.1 + by.1),
+        transform: self.transform.map(|t| Transform{origin: (t.origin.0 - by.0, t.origin.1 - by.1), ..t}),
+        ..self
     }}
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     Shape(Shape),
     Image(Image),
     Text(Text),
+    Shadow(Shadow),
+    Path(Path),
+    /// Clips every following instruction to the region of the clip, inside any clip already
+    /// pushed.
+    PushClip(Clip),
+    /// Removes the clip pushed last. Its area is not used.
+    PopClip,
+    /// Masks every following instruction with the alpha of the mask, inside any mask already
+    /// pushed. Clips pushed inside a mask are popped inside it too.
+    PushMask(Mask),
+    /// Removes the mask pushed last. Its area is not used.
+    PopMask,
+    /// Draws every following instruction into a layer composited as one picture. The
+    /// transform of its area moves the layer about the offset and its bounds clip it. Clips
+    /// and masks pushed inside a layer are popped inside it too.
+    PushLayer(Layer),
+    /// Composites the layer pushed last. Its area is not used.
+    PopLayer,
 }
 impl Item {
     pub fn scale(&mut self, scale: f32) {match self {
         Item::Shape(shape) => shape.scale(scale),
         Item::Image(image) => image.scale(scale),
         Item::Text(text) => text.scale(scale),
+        Item::Shadow(shadow) => shadow.scale(scale),
+        Item::Path(path) => path.scale(scale),
+        Item::PushClip(clip) => clip.scale(scale),
+        Item::PushMask(mask) => mask.scale(scale),
+        Item::PushLayer(layer) => layer.scale(scale),
+        Item::PopClip | Item::PopMask | Item::PopLayer => {},
     }}
 
     pub fn size(&self) -> (f32, f32) {match self {
         Item::Shape(shape) => shape.size(),
         Item::Image(image) => image.size(),
         Item::Text(text) => text.size(),
+        Item::Shadow(shadow) => shadow.size(),
+        Item::Path(path) => path.size(),
+        Item::PushClip(clip) => clip.size(),
+        Item::PushMask(mask) => mask.size(),
+        Item::PopClip | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => (0.0, 0.0),
     }}
 }
 
 #[derive(Debug, Clone,  PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction(pub Area, pub Item);
 impl Instruction {
     pub fn scale(&mut self, scale: f32) {
@@ -99,3 +820,52 @@ impl Instruction {
         self.1.scale(scale);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: ColorMatrix, b: ColorMatrix) -> bool {
+        a.0.iter().zip(b.0).all(|(a, b)| (a - b).abs() < 1e-3)
+    }
+
+    #[test]
+    fn presets_are_the_identity_at_their_neutral_amount() {
+        for neutral in [
+            ColorMatrix::grayscale(0.0), ColorMatrix::sepia(0.0), ColorMatrix::saturate(1.0), ColorMatrix::hue_rotate(0.0),
+            ColorMatrix::hue_rotate(360.0), ColorMatrix::brightness(1.0), ColorMatrix::contrast(1.0), ColorMatrix::invert(0.0),
+        ] {
+            assert!(close(neutral, ColorMatrix::IDENTITY), "{neutral:?}");
+        }
+        assert_eq!(ColorMatrix::default(), ColorMatrix::IDENTITY);
+    }
+
+    #[test]
+    fn presets_match_the_css_filters() {
+        let orange = Color(255, 128, 0, 200);
+        let gray = ColorMatrix::grayscale(1.0).apply(orange);
+        assert!(gray.0 == gray.1 && gray.1 == gray.2);
+        assert_eq!(gray, Color(146, 146, 146, 200));
+        assert_eq!(ColorMatrix::grayscale(2.0), ColorMatrix::grayscale(1.0));
+        assert_eq!(ColorMatrix::sepia(1.0).apply(Color::WHITE), Color(255, 255, 239, 255));
+        assert_eq!(ColorMatrix::invert(1.0).apply(orange), Color(0, 127, 255, 200));
+        assert_eq!(ColorMatrix::invert(0.5).apply(orange), Color(128, 128, 128, 200));
+        assert_eq!(ColorMatrix::brightness(0.0).apply(orange), Color(0, 0, 0, 200));
+        assert_eq!(ColorMatrix::contrast(0.0).apply(orange), Color(128, 128, 128, 200));
+        assert_eq!(ColorMatrix::contrast(2.0).apply(Color(192, 32, 255, 255)), Color(255, 0, 255, 255));
+        assert!(close(ColorMatrix::saturate(0.0), ColorMatrix::rgb([0.213, 0.715, 0.072, 0.213, 0.715, 0.072, 0.213, 0.715, 0.072])));
+        //Gray has no hue to turn
+        let gray = Color(100, 100, 100, 255);
+        assert_eq!(ColorMatrix::hue_rotate(123.0).apply(gray), gray);
+    }
+
+    #[test]
+    fn chained_filters_apply_in_order() {
+        let color = Color(200, 50, 20, 255);
+        let (first, second) = (ColorMatrix::brightness(0.5), ColorMatrix::invert(1.0));
+        assert_eq!(first.then(second).apply(color), second.apply(first.apply(color)));
+        assert_ne!(first.then(second).apply(color), second.then(first).apply(color));
+        assert!(close(ColorMatrix::brightness(0.5).then(ColorMatrix::brightness(0.5)), ColorMatrix::brightness(0.25)));
+        assert_eq!(ColorMatrix::sepia(0.3).then(ColorMatrix::IDENTITY), ColorMatrix::sepia(0.3));
+    }
+}
@@ -0,0 +1,702 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+use crate::FillRule;
+
+/// Control point distance for a quarter circle of radius 1 drawn as a cubic bézier.
+const KAPPA: f32 = 0.552_284_8;
+
+/// A single segment of a [`PathData`], in absolute coordinates.
+///
+/// SVG arcs, smooth curves and relative commands are resolved while parsing, so a path only
+/// ever holds these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathCommand {
+    MoveTo((f32, f32)),
+    LineTo((f32, f32)),
+    /// `QuadTo(control, to)`
+    QuadTo((f32, f32), (f32, f32)),
+    /// `CubicTo(control1, control2, to)`
+    CubicTo((f32, f32), (f32, f32), (f32, f32)),
+    Close,
+}
+
+impl PathCommand {
+    fn map(self, f: impl Fn((f32, f32)) -> (f32, f32)) -> Self {match self {
+        PathCommand::MoveTo(p) => PathCommand::MoveTo(f(p)),
+        PathCommand::LineTo(p) => PathCommand::LineTo(f(p)),
+        PathCommand::QuadTo(c, p) => PathCommand::QuadTo(f(c), f(p)),
+        PathCommand::CubicTo(c1, c2, p) => PathCommand::CubicTo(f(c1), f(c2), f(p)),
+        PathCommand::Close => PathCommand::Close,
+    }}
+}
+
+/// Why a path data string could not be parsed. Every variant carries the byte offset into the
+/// string where the problem was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    /// A character that does not start a command or a number.
+    UnexpectedChar(usize, char),
+    /// A command is missing some of its numbers.
+    ExpectedNumber(usize),
+    /// An arc flag was something other than `0` or `1`.
+    ExpectedFlag(usize),
+    /// The path has to start with a moveto (`M` or `m`).
+    MissingMoveTo(usize),
+}
+
+impl PathError {
+    pub fn position(&self) -> usize {match self {
+        PathError::UnexpectedChar(p, _) => *p,
+        PathError::ExpectedNumber(p) => *p,
+        PathError::ExpectedFlag(p) => *p,
+        PathError::MissingMoveTo(p) => *p,
+    }}
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {match self {
+        PathError::UnexpectedChar(p, c) => write!(f, "unexpected character {c:?} at {p}"),
+        PathError::ExpectedNumber(p) => write!(f, "expected a number at {p}"),
+        PathError::ExpectedFlag(p) => write!(f, "expected an arc flag (0 or 1) at {p}"),
+        PathError::MissingMoveTo(p) => write!(f, "path data must start with a moveto, found a command at {p}"),
+    }}
+}
+
+impl std::error::Error for PathError {}
+
+/// Vector outlines parsed from SVG path data (the `d` attribute).
+///
+/// Coordinates are relative to the offset of the [`Area`](crate::Area) the path is drawn at.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathData(pub Vec<PathCommand>);
+
+impl PathData {
+    /// Parses SVG path data: absolute and relative `M L H V C S Q T A Z` commands.
+    pub fn parse(d: &str) -> Result<Self, PathError> {
+        Parser{src: d.as_bytes(), pos: 0}.parse()
+    }
+
+    /// A rectangle with elliptical corners of radii `rx` and `ry`, as SVG's `<rect>` draws it.
+    pub fn rectangle(x: f32, y: f32, w: f32, h: f32, rx: f32, ry: f32) -> Self {
+        use PathCommand::*;
+        if rx <= 0.0 || ry <= 0.0 {
+            return PathData(vec![MoveTo((x, y)), LineTo((x+w, y)), LineTo((x+w, y+h)), LineTo((x, y+h)), Close]);
+        }
+        let (kx, ky) = (rx*KAPPA, ry*KAPPA);
+        PathData(vec![
+            MoveTo((x+rx, y)),
+            LineTo((x+w-rx, y)),
+            CubicTo((x+w-rx+kx, y), (x+w, y+ry-ky), (x+w, y+ry)),
+            LineTo((x+w, y+h-ry)),
+            CubicTo((x+w, y+h-ry+ky), (x+w-rx+kx, y+h), (x+w-rx, y+h)),
+            LineTo((x+rx, y+h)),
+            CubicTo((x+rx-kx, y+h), (x, y+h-ry+ky), (x, y+h-ry)),
+            LineTo((x, y+ry)),
+            CubicTo((x, y+ry-ky), (x+rx-kx, y), (x+rx, y)),
+            Close,
+        ])
+    }
+
+    /// A rectangle with a circular radius of its own at each corner, clockwise from the top left.
+    pub fn rounded_rectangle(x: f32, y: f32, w: f32, h: f32, radii: [f32; 4]) -> Self {
+        use PathCommand::*;
+        let [tl, tr, br, bl] = radii.map(|r| r.clamp(0.0, w.min(h) / 2.0));
+        //Quarter circle from `from` to `to` around the corner point `c`
+        let corner = |from: (f32, f32), to: (f32, f32), c: (f32, f32)| CubicTo(
+            (from.0 + (c.0 - from.0)*KAPPA, from.1 + (c.1 - from.1)*KAPPA),
+            (to.0 + (c.0 - to.0)*KAPPA, to.1 + (c.1 - to.1)*KAPPA),
+            to
+        );
+        let commands = [
+            Some(MoveTo((x+tl, y))),
+            Some(LineTo((x+w-tr, y))),
+            (tr > 0.0).then(|| corner((x+w-tr, y), (x+w, y+tr), (x+w, y))),
+            Some(LineTo((x+w, y+h-br))),
+            (br > 0.0).then(|| corner((x+w, y+h-br), (x+w-br, y+h), (x+w, y+h))),
+            Some(LineTo((x+bl, y+h))),
+            (bl > 0.0).then(|| corner((x+bl, y+h), (x, y+h-bl), (x, y+h))),
+            Some(LineTo((x, y+tl))),
+            (tl > 0.0).then(|| corner((x, y+tl), (x+tl, y), (x, y))),
+            Some(Close),
+        ];
+        PathData(commands.into_iter().flatten().collect())
+    }
+
+    pub fn ellipse(cx: f32, cy: f32, rx: f32, ry: f32) -> Self {
+        use PathCommand::*;
+        let (kx, ky) = (rx*KAPPA, ry*KAPPA);
+        PathData(vec![
+            MoveTo((cx+rx, cy)),
+            CubicTo((cx+rx, cy+ky), (cx+kx, cy+ry), (cx, cy+ry)),
+            CubicTo((cx-kx, cy+ry), (cx-rx, cy+ky), (cx-rx, cy)),
+            CubicTo((cx-rx, cy-ky), (cx-kx, cy-ry), (cx, cy-ry)),
+            CubicTo((cx+kx, cy-ry), (cx+rx, cy-ky), (cx+rx, cy)),
+            Close,
+        ])
+    }
+
+    pub fn scale(self, scale: f32) -> Self {
+        PathData(self.0.into_iter().map(|c| c.map(|(x, y)| (scale*x, scale*y))).collect())
+    }
+
+    /// Applies an affine matrix `[a, b, c, d, e, f]` (as in SVG) to every point.
+    pub fn transform(self, m: [f32; 6]) -> Self {
+        PathData(self.0.into_iter().map(|c| c.map(|(x, y)| (m[0]*x + m[2]*y + m[4], m[1]*x + m[3]*y + m[5]))).collect())
+    }
+
+    /// Bounding box of the path as `(x, y, width, height)`, curves included.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let mut min = (f32::MAX, f32::MAX);
+        let mut max = (f32::MIN, f32::MIN);
+        let mut add = |(x, y): (f32, f32)| {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        };
+        let mut current = (0.0, 0.0);
+        for command in &self.0 {
+            match *command {
+                PathCommand::MoveTo(p) | PathCommand::LineTo(p) => {add(p); current = p;},
+                PathCommand::QuadTo(c, p) => {
+                    quad_extrema(current, c, p).into_iter().for_each(&mut add);
+                    add(p);
+                    current = p;
+                },
+                PathCommand::CubicTo(c1, c2, p) => {
+                    cubic_extrema(current, c1, c2, p).into_iter().for_each(&mut add);
+                    add(p);
+                    current = p;
+                },
+                PathCommand::Close => {}
+            }
+        }
+        if min.0 > max.0 {return (0.0, 0.0, 0.0, 0.0);}
+        (min.0, min.1, max.0-min.0, max.1-min.1)
+    }
+
+    /// The subpaths as polylines with curves split into short segments, and whether each was
+    /// closed.
+    pub fn flatten(&self) -> Vec<(Vec<(f32, f32)>, bool)> {
+        let mut subpaths: Vec<(Vec<(f32, f32)>, bool)> = vec![];
+        let mut current = (0.0, 0.0);
+        let mut start = (0.0, 0.0);
+        let segments = |points: &[(f32, f32)]| {
+            let length: f32 = points.windows(2).map(|w| ((w[1].0-w[0].0).powi(2) + (w[1].1-w[0].1).powi(2)).sqrt()).sum();
+            (length / 4.0).ceil().clamp(1.0, 64.0) as usize
+        };
+        for command in &self.0 {
+            if !matches!(command, PathCommand::MoveTo(_)) && subpaths.last().is_none_or(|s| s.1) {
+                subpaths.push((vec![start], false));
+                current = start;
+            }
+            match *command {
+                PathCommand::MoveTo(p) => {
+                    subpaths.push((vec![p], false));
+                    (current, start) = (p, p);
+                },
+                PathCommand::LineTo(p) => {
+                    subpaths.last_mut().unwrap().0.push(p);
+                    current = p;
+                },
+                PathCommand::QuadTo(c, p) => {
+                    let n = segments(&[current, c, p]);
+                    let p0 = current;
+                    subpaths.last_mut().unwrap().0.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let mt = 1.0 - t;
+                        (mt*mt*p0.0 + 2.0*mt*t*c.0 + t*t*p.0, mt*mt*p0.1 + 2.0*mt*t*c.1 + t*t*p.1)
+                    }));
+                    current = p;
+                },
+                PathCommand::CubicTo(c1, c2, p) => {
+                    let n = segments(&[current, c1, c2, p]);
+                    let p0 = current;
+                    subpaths.last_mut().unwrap().0.extend((1..=n).map(|i| {
+                        let t = i as f32 / n as f32;
+                        let mt = 1.0 - t;
+                        let (a, b, c, d) = (mt*mt*mt, 3.0*mt*mt*t, 3.0*mt*t*t, t*t*t);
+                        (a*p0.0 + b*c1.0 + c*c2.0 + d*p.0, a*p0.1 + b*c1.1 + c*c2.1 + d*p.1)
+                    }));
+                    current = p;
+                },
+                PathCommand::Close => {
+                    subpaths.last_mut().unwrap().1 = true;
+                    current = start;
+                }
+            }
+        }
+        subpaths
+    }
+
+    /// Whether `point` is inside the filled path, every subpath closed as the fill closes them.
+    pub fn contains(&self, point: (f32, f32), fill_rule: FillRule) -> bool {
+        let mut winding = 0;
+        for (points, _) in self.flatten() {
+            let Some(last) = points.last() else {continue};
+            for (a, b) in std::iter::once((*last, points[0])).chain(points.windows(2).map(|w| (w[0], w[1]))) {
+                let cross = (b.0 - a.0) * (point.1 - a.1) - (point.0 - a.0) * (b.1 - a.1);
+                if a.1 <= point.1 && b.1 > point.1 && cross > 0.0 {winding += 1;}
+                if a.1 > point.1 && b.1 <= point.1 && cross < 0.0 {winding -= 1;}
+            }
+        }
+        match fill_rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    /// Distance from `point` to the nearest segment of the path.
+    pub fn distance(&self, point: (f32, f32)) -> f32 {
+        self.flatten().iter().flat_map(|(points, closed)| {
+            let close = closed.then(|| (*points.last().unwrap(), points[0]));
+            points.windows(2).map(|w| (w[0], w[1])).chain(close).chain((points.len() == 1).then(|| (points[0], points[0])))
+        }).map(|(a, b)| {
+            let (ab, ap) = ((b.0 - a.0, b.1 - a.1), (point.0 - a.0, point.1 - a.1));
+            let len = ab.0*ab.0 + ab.1*ab.1;
+            let t = if len > 0.0 {((ap.0*ab.0 + ap.1*ab.1) / len).clamp(0.0, 1.0)} else {0.0};
+            ((ap.0 - ab.0*t).powi(2) + (ap.1 - ab.1*t).powi(2)).sqrt()
+        }).fold(f32::MAX, f32::min)
+    }
+}
+
+impl FromStr for PathData {
+    type Err = PathError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {PathData::parse(s)}
+}
+
+/// Writes the commands back out as SVG path data, which [`PathData::parse`] reads unchanged.
+impl std::fmt::Display for PathData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, command) in self.0.iter().enumerate() {
+            if i > 0 {f.write_str(" ")?;}
+            match command {
+                PathCommand::MoveTo((x, y)) => write!(f, "M{x} {y}"),
+                PathCommand::LineTo((x, y)) => write!(f, "L{x} {y}"),
+                PathCommand::QuadTo((cx, cy), (x, y)) => write!(f, "Q{cx} {cy} {x} {y}"),
+                PathCommand::CubicTo((c1x, c1y), (c2x, c2y), (x, y)) => write!(f, "C{c1x} {c1y} {c2x} {c2y} {x} {y}"),
+                PathCommand::Close => f.write_str("Z"),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+fn quad_extrema(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) -> Vec<(f32, f32)> {
+    let at = |t: f32, a: f32, b: f32, c: f32| (1.0-t)*(1.0-t)*a + 2.0*(1.0-t)*t*b + t*t*c;
+    let mut points = vec![];
+    for axis in 0..2 {
+        let (a, b, c) = if axis == 0 {(p0.0, p1.0, p2.0)} else {(p0.1, p1.1, p2.1)};
+        let denominator = a - 2.0*b + c;
+        if denominator != 0.0 {
+            let t = (a - b) / denominator;
+            if t > 0.0 && t < 1.0 {points.push((at(t, p0.0, p1.0, p2.0), at(t, p0.1, p1.1, p2.1)));}
+        }
+    }
+    points
+}
+
+fn cubic_extrema(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) -> Vec<(f32, f32)> {
+    let at = |t: f32, a: f32, b: f32, c: f32, d: f32| {
+        let u = 1.0-t;
+        u*u*u*a + 3.0*u*u*t*b + 3.0*u*t*t*c + t*t*t*d
+    };
+    let mut points = vec![];
+    for axis in 0..2 {
+        let (a, b, c, d) = if axis == 0 {(p0.0, p1.0, p2.0, p3.0)} else {(p0.1, p1.1, p2.1, p3.1)};
+        //Roots of the derivative: qa*t^2 + qb*t + qc = 0
+        let qa = -a + 3.0*b - 3.0*c + d;
+        let qb = 2.0*(a - 2.0*b + c);
+        let qc = b - a;
+        let roots = if qa.abs() < 1e-6 {
+            if qb != 0.0 {vec![-qc / qb]} else {vec![]}
+        } else {
+            let discriminant = qb*qb - 4.0*qa*qc;
+            if discriminant < 0.0 {vec![]} else {
+                let s = discriminant.sqrt();
+                vec![(-qb + s) / (2.0*qa), (-qb - s) / (2.0*qa)]
+            }
+        };
+        points.extend(roots.into_iter().filter(|t| *t > 0.0 && *t < 1.0).map(|t|
+            (at(t, p0.0, p1.0, p2.0, p3.0), at(t, p0.1, p1.1, p2.1, p3.1))
+        ));
+    }
+    points
+}
+
+/// The point `degrees` clockwise from the top of the ellipse around `center`.
+pub(crate) fn arc_point(center: (f32, f32), (rx, ry): (f32, f32), degrees: f32) -> (f32, f32) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (center.0 + rx*sin, center.1 - ry*cos)
+}
+
+/// Cubic béziers of at most a quarter turn each, following the ellipse around `center` from
+/// `start` through `sweep` degrees (clockwise from the top, as in [`Shape::Arc`](crate::ShapeType::Arc)).
+/// They continue from the point at `start`.
+pub(crate) fn elliptic_arc(center: (f32, f32), (rx, ry): (f32, f32), start: f32, sweep: f32) -> Vec<PathCommand> {
+    let sweep = sweep.clamp(-360.0, 360.0);
+    let segments = (sweep.abs() / 90.0).ceil().max(1.0) as usize;
+    let step = (sweep / segments as f32).to_radians();
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let derivative = |t: f32| {
+        let (s, c) = t.sin_cos();
+        (rx*c, ry*s)
+    };
+    (0..segments).map(|i| {
+        let t1 = start.to_radians() + step * i as f32;
+        let t2 = t1 + step;
+        let (p1, d1) = (arc_point(center, (rx, ry), t1.to_degrees()), derivative(t1));
+        let (p2, d2) = (arc_point(center, (rx, ry), t2.to_degrees()), derivative(t2));
+        PathCommand::CubicTo((p1.0 + k*d1.0, p1.1 + k*d1.1), (p2.0 - k*d2.0, p2.1 - k*d2.1), p2)
+    }).collect()
+}
+
+/// Converts an SVG endpoint arc into cubic béziers of at most a quarter turn each.
+fn arc_to_cubics(
+    from: (f32, f32), radii: (f32, f32), rotation: f32, large_arc: bool, sweep: bool, to: (f32, f32)
+) -> Vec<PathCommand> {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if from == to {return vec![];}
+    if rx == 0.0 || ry == 0.0 {return vec![PathCommand::LineTo(to)];}
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    //Endpoint to center parameterization, see the SVG implementation notes (F.6.5)
+    let dx = (from.0 - to.0) / 2.0;
+    let dy = (from.1 - to.1) / 2.0;
+    let x1 = cos*dx + sin*dy;
+    let y1 = -sin*dx + cos*dy;
+
+    let lambda = (x1*x1) / (rx*rx) + (y1*y1) / (ry*ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = (rx*rx*ry*ry - rx*rx*y1*y1 - ry*ry*x1*x1).max(0.0);
+    let denominator = rx*rx*y1*y1 + ry*ry*x1*x1;
+    let mut coefficient = if denominator == 0.0 {0.0} else {(numerator / denominator).sqrt()};
+    if large_arc == sweep {coefficient = -coefficient;}
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = coefficient * -ry * x1 / rx;
+    let cx = cos*cx1 - sin*cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin*cx1 + cos*cy1 + (from.1 + to.1) / 2.0;
+
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| {
+        let a = (ux*vx + uy*vy) / ((ux*ux + uy*uy).sqrt() * (vx*vx + vy*vy).sqrt());
+        let a = a.clamp(-1.0, 1.0).acos();
+        if ux*vy - uy*vx < 0.0 {-a} else {a}
+    };
+    let start = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle((x1 - cx1) / rx, (y1 - cy1) / ry, (-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    if !sweep && delta > 0.0 {delta -= 2.0*PI;}
+    if sweep && delta < 0.0 {delta += 2.0*PI;}
+
+    let segments = (delta.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+    let step = delta / segments as f32;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |t: f32| {
+        let (s, c) = t.sin_cos();
+        (cx + rx*c*cos - ry*s*sin, cy + rx*c*sin + ry*s*cos)
+    };
+    let derivative = |t: f32| {
+        let (s, c) = t.sin_cos();
+        (-rx*s*cos - ry*c*sin, -rx*s*sin + ry*c*cos)
+    };
+
+    (0..segments).map(|i| {
+        let t1 = start + step * i as f32;
+        let t2 = t1 + step;
+        let (p1, d1) = (point(t1), derivative(t1));
+        let (p2, d2) = (point(t2), derivative(t2));
+        let p2 = if i == segments - 1 {to} else {p2};
+        PathCommand::CubicTo((p1.0 + k*d1.0, p1.1 + k*d1.1), (p2.0 - k*d2.0, p2.1 - k*d2.1), p2)
+    }).collect()
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.src.len() && self.src[self.pos].is_ascii_whitespace() {self.pos += 1;}
+    }
+
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.src.get(self.pos) == Some(&b',') {
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    /// Whether another number follows, for commands repeated without their letter.
+    fn at_number(&mut self) -> bool {
+        self.skip_separator();
+        matches!(self.src.get(self.pos), Some(b'0'..=b'9' | b'-' | b'+' | b'.'))
+    }
+
+    fn number(&mut self) -> Result<f32, PathError> {
+        self.skip_separator();
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let s = p.pos;
+            while p.pos < p.src.len() && p.src[p.pos].is_ascii_digit() {p.pos += 1;}
+            p.pos > s
+        };
+        if matches!(self.src.get(self.pos), Some(b'-' | b'+')) {self.pos += 1;}
+        let mut valid = digits(self);
+        if self.src.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            valid |= digits(self);
+        }
+        if !valid {
+            self.pos = start;
+            return Err(PathError::ExpectedNumber(start));
+        }
+        if matches!(self.src.get(self.pos), Some(b'e' | b'E')) {
+            let mantissa = self.pos;
+            self.pos += 1;
+            if matches!(self.src.get(self.pos), Some(b'-' | b'+')) {self.pos += 1;}
+            if !digits(self) {self.pos = mantissa;}
+        }
+        std::str::from_utf8(&self.src[start..self.pos]).ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .ok_or(PathError::ExpectedNumber(start))
+    }
+
+    fn point(&mut self) -> Result<(f32, f32), PathError> {
+        Ok((self.number()?, self.number()?))
+    }
+
+    fn flag(&mut self) -> Result<bool, PathError> {
+        self.skip_separator();
+        match self.src.get(self.pos) {
+            Some(b'0') => {self.pos += 1; Ok(false)},
+            Some(b'1') => {self.pos += 1; Ok(true)},
+            _ => Err(PathError::ExpectedFlag(self.pos))
+        }
+    }
+
+    fn parse(mut self) -> Result<PathData, PathError> {
+        let mut commands = vec![];
+        let mut current = (0.0, 0.0);
+        let mut start = (0.0, 0.0);
+        //Control point to reflect for the smooth curve commands
+        let mut last_cubic: Option<(f32, f32)> = None;
+        let mut last_quad: Option<(f32, f32)> = None;
+
+        self.skip_whitespace();
+        if self.pos < self.src.len() && !matches!(self.src[self.pos], b'M' | b'm') {
+            return Err(match self.src[self.pos] {
+                c if c.is_ascii_alphabetic() => PathError::MissingMoveTo(self.pos),
+                _ => self.unexpected()
+            });
+        }
+
+        while self.pos < self.src.len() {
+            let letter = self.src[self.pos];
+            if !letter.is_ascii_alphabetic() {return Err(self.unexpected());}
+            self.pos += 1;
+            let relative = letter.is_ascii_lowercase();
+            let base = |current: (f32, f32)| if relative {current} else {(0.0, 0.0)};
+            let offset = |p: (f32, f32), b: (f32, f32)| (p.0 + b.0, p.1 + b.1);
+
+            let mut first = true;
+            while first || (!letter.eq_ignore_ascii_case(&b'Z') && self.at_number()) {
+                let b = base(current);
+                let (cubic, quad) = match letter.to_ascii_uppercase() {
+                    b'M' => {
+                        let p = offset(self.point()?, b);
+                        //Pairs after the first are implicit linetos
+                        commands.push(if first {PathCommand::MoveTo(p)} else {PathCommand::LineTo(p)});
+                        if first {start = p;}
+                        current = p;
+                        (None, None)
+                    },
+                    b'L' => {
+                        current = offset(self.point()?, b);
+                        commands.push(PathCommand::LineTo(current));
+                        (None, None)
+                    },
+                    b'H' => {
+                        current = (self.number()? + b.0, current.1);
+                        commands.push(PathCommand::LineTo(current));
+                        (None, None)
+                    },
+                    b'V' => {
+                        current = (current.0, self.number()? + b.1);
+                        commands.push(PathCommand::LineTo(current));
+                        (None, None)
+                    },
+                    b'C' => {
+                        let c1 = offset(self.point()?, b);
+                        let c2 = offset(self.point()?, b);
+                        current = offset(self.point()?, b);
+                        commands.push(PathCommand::CubicTo(c1, c2, current));
+                        (Some(c2), None)
+                    },
+                    b'S' => {
+                        let c1 = last_cubic.map(|c| (2.0*current.0 - c.0, 2.0*current.1 - c.1)).unwrap_or(current);
+                        let c2 = offset(self.point()?, b);
+                        current = offset(self.point()?, b);
+                        commands.push(PathCommand::CubicTo(c1, c2, current));
+                        (Some(c2), None)
+                    },
+                    b'Q' => {
+                        let c = offset(self.point()?, b);
+                        current = offset(self.point()?, b);
+                        commands.push(PathCommand::QuadTo(c, current));
+                        (None, Some(c))
+                    },
+                    b'T' => {
+                        let c = last_quad.map(|c| (2.0*current.0 - c.0, 2.0*current.1 - c.1)).unwrap_or(current);
+                        current = offset(self.point()?, b);
+                        commands.push(PathCommand::QuadTo(c, current));
+                        (None, Some(c))
+                    },
+                    b'A' => {
+                        let radii = self.point()?;
+                        let rotation = self.number()?;
+                        let large_arc = self.flag()?;
+                        let sweep = self.flag()?;
+                        let to = offset(self.point()?, b);
+                        commands.extend(arc_to_cubics(current, radii, rotation, large_arc, sweep, to));
+                        current = to;
+                        (None, None)
+                    },
+                    b'Z' => {
+                        commands.push(PathCommand::Close);
+                        current = start;
+                        (None, None)
+                    },
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.unexpected());
+                    }
+                };
+                last_cubic = cubic;
+                last_quad = quad;
+                first = false;
+            }
+            self.skip_separator();
+        }
+        Ok(PathData(commands))
+    }
+
+    fn unexpected(&self) -> PathError {
+        let c = std::str::from_utf8(&self.src[self.pos..]).ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(self.src[self.pos] as char);
+        PathError::UnexpectedChar(self.pos, c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PathCommand::*;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    fn end(command: &PathCommand) -> (f32, f32) {match *command {
+        MoveTo(p) | LineTo(p) | QuadTo(_, p) | CubicTo(_, _, p) => p,
+        Close => panic!("close has no end point"),
+    }}
+
+    #[test]
+    fn relative_and_implicit_commands_resolve_to_absolute_points() {
+        let path = PathData::parse("m10 10 5 0 h5 v5 l-5,5 z M0 0 1e1-2.5.5.5").unwrap();
+        assert_eq!(path.0, vec![
+            MoveTo((10.0, 10.0)), LineTo((15.0, 10.0)), LineTo((20.0, 10.0)), LineTo((20.0, 15.0)),
+            LineTo((15.0, 20.0)), Close,
+            MoveTo((0.0, 0.0)), LineTo((10.0, -2.5)), LineTo((0.5, 0.5)),
+        ]);
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        let path = PathData::parse("M0 0 C0 10 10 10 10 0 S20 -10 20 0 Q25 5 30 0 T40 0").unwrap();
+        assert_eq!(path.0[2], CubicTo((10.0, -10.0), (20.0, -10.0), (20.0, 0.0)));
+        assert_eq!(path.0[4], QuadTo((35.0, -5.0), (40.0, 0.0)));
+        //Without a curve before them the control point is the current point
+        let path = PathData::parse("M5 5 S10 0 20 5 T30 5").unwrap();
+        assert_eq!(path.0[1], CubicTo((5.0, 5.0), (10.0, 0.0), (20.0, 5.0)));
+        assert_eq!(path.0[2], QuadTo((20.0, 5.0), (30.0, 5.0)));
+    }
+
+    #[test]
+    fn malformed_path_data_reports_where_it_went_wrong() {
+        assert_eq!(PathData::parse("L10 10"), Err(PathError::MissingMoveTo(0)));
+        assert_eq!(PathData::parse("  #"), Err(PathError::UnexpectedChar(2, '#')));
+        assert_eq!(PathData::parse("M10"), Err(PathError::ExpectedNumber(3)));
+        assert_eq!(PathData::parse("M10 10 L5 x"), Err(PathError::ExpectedNumber(10)));
+        assert_eq!(PathData::parse("M0 0 L- 5"), Err(PathError::ExpectedNumber(6)));
+        assert_eq!(PathData::parse("M0 0 A5 5 0 2 1 10 0"), Err(PathError::ExpectedFlag(12)));
+        assert_eq!(PathData::parse("M0 0 X1 1"), Err(PathError::UnexpectedChar(5, 'X')));
+        assert_eq!(PathData::parse("M0 0 L1 1 ü"), Err(PathError::UnexpectedChar(10, 'ü')));
+        assert_eq!(PathData::parse("M0 0 L1 1 ü").unwrap_err().position(), 10);
+        assert_eq!(PathData::parse(""), Ok(PathData(vec![])));
+    }
+
+    #[test]
+    fn display_round_trips_through_parse() {
+        let path = PathData::parse("M1.5 2 L3 4 Q5 6 7 8 C9 10 11 12 13 14 Z").unwrap();
+        assert_eq!(PathData::parse(&path.to_string()), Ok(path));
+    }
+
+    #[test]
+    fn arcs_end_on_their_endpoint_in_quarter_turns() {
+        //Half circle of radius 10 from (0, 0) to (20, 0)
+        let cubics = arc_to_cubics((0.0, 0.0), (10.0, 10.0), 0.0, false, true, (20.0, 0.0));
+        assert_eq!(cubics.len(), 2);
+        assert_eq!(end(&cubics[1]), (20.0, 0.0));
+        //Sweeping clockwise in y down coordinates passes over the top
+        assert!(close(end(&cubics[0]), (10.0, -10.0)));
+        let cubics = arc_to_cubics((0.0, 0.0), (10.0, 10.0), 0.0, false, false, (20.0, 0.0));
+        assert!(close(end(&cubics[0]), (10.0, 10.0)));
+        //The large arc of a circle through two points a quarter turn apart takes three quarters
+        let cubics = arc_to_cubics((10.0, 0.0), (10.0, 10.0), 0.0, true, true, (0.0, 10.0));
+        assert_eq!(cubics.len(), 3);
+        assert_eq!(end(&cubics[2]), (0.0, 10.0));
+    }
+
+    #[test]
+    fn degenerate_arcs_follow_the_svg_rules() {
+        //Same endpoints draw nothing, a zero radius draws a line
+        assert_eq!(arc_to_cubics((5.0, 5.0), (10.0, 10.0), 0.0, false, true, (5.0, 5.0)), vec![]);
+        assert_eq!(arc_to_cubics((0.0, 0.0), (0.0, 10.0), 0.0, false, true, (20.0, 0.0)), vec![LineTo((20.0, 0.0))]);
+        //Radii too small to reach are scaled up to a half ellipse
+        let cubics = arc_to_cubics((0.0, 0.0), (1.0, 1.0), 0.0, false, true, (20.0, 0.0));
+        assert_eq!(cubics.len(), 2);
+        assert!(close(end(&cubics[0]), (10.0, -10.0)));
+        //Negative radii are used as their absolute value
+        assert_eq!(
+            arc_to_cubics((0.0, 0.0), (-10.0, -10.0), 0.0, false, true, (20.0, 0.0)),
+            arc_to_cubics((0.0, 0.0), (10.0, 10.0), 0.0, false, true, (20.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn bounds_include_curve_extrema() {
+        let path = PathData::parse("M0 0 A10 10 0 0 1 20 0").unwrap();
+        let (x, y, w, h) = path.bounds();
+        assert!(close((x, y), (0.0, -10.0)));
+        assert!(close((w, h), (20.0, 10.0)));
+        assert_eq!(PathData::default().bounds(), (0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn fill_rules_decide_holes() {
+        let path = PathData::parse("M0 0 H30 V30 H0 Z M10 10 H20 V20 H10 Z").unwrap();
+        assert!(path.contains((15.0, 15.0), FillRule::NonZero));
+        assert!(!path.contains((15.0, 15.0), FillRule::EvenOdd));
+        assert!(path.contains((5.0, 5.0), FillRule::EvenOdd));
+        assert!(!path.contains((35.0, 5.0), FillRule::NonZero));
+        assert!((path.distance((35.0, 5.0)) - 5.0).abs() < 1e-4);
+    }
+}
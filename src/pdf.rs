@@ -0,0 +1,817 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::{
+    Instruction, Item, Area, Clip, Mask, Layer, BlendMode, ClipShape, Color, Shape, Shadow, Image, Text, Path, Font, ShapeType, StrokeAlign, FillRule, LineCap,
+    LineJoin, Gradient, GradientKind, PathData, PathCommand, RgbaImage, ColorMatrix
+};
+use crate::path::{arc_point, elliptic_arc};
+
+/// Number of translucent outlines stacked to approximate the blur of a [`Shadow`].
+const SHADOW_LAYERS: usize = 8;
+
+type PlacedImage = (Arc<RgbaImage>, (f32, f32), Option<Color>, Option<ColorMatrix>);
+
+/// A vector PDF document with a page per instruction list.
+///
+/// Pages are painted in instruction order at one point per pixel. Shapes, paths and gradients
+/// stay vectors, images are embedded as the renderer shows them (cropped, tinted and filtered)
+/// and text is set in its fonts, embedded and subsetted to the glyphs used across the document.
+/// `Area.bounds` become clipping paths and `Area.transform` a change of coordinates, without its
+/// perspective. Pushed clips and masks stay in the graphics state up to their pop, masks as
+/// alpha soft masks, and pushed layers become transparency groups painted with their opacity,
+/// blend mode and transform. Instructions blend by the mode of their area, the Porter-Duff
+/// operators PDF has no blend mode for drawing over. Blurred shadows are approximated by stacked
+/// translucent outlines, inner shadows, the blur and color matrix of layers and backdrops are left
+/// out.
+#[derive(Default, Clone)]
+pub struct Pdf {
+    pages: Vec<((f32, f32), Vec<u8>)>,
+    fonts: Vec<(Font, BTreeMap<u16, char>)>,
+    /// Images with the size of the shape they fill, their tint and their color matrix.
+    images: Vec<PlacedImage>,
+    opacities: Vec<(u8, u8)>,
+    /// Blend modes of single instructions.
+    blends: Vec<BlendMode>,
+    /// Gradients with the range of `t` their shading has to cover.
+    gradients: Vec<(Gradient, (f32, f32))>,
+    /// Compressed content of the soft mask groups.
+    masks: Vec<Vec<u8>>,
+    /// Compressed content of the layer groups with their opacity and blend mode.
+    layers: Vec<(Vec<u8>, f32, BlendMode)>,
+}
+
+impl Pdf {
+    pub fn new() -> Self {Pdf::default()}
+
+    /// A single page document of `size` showing `instructions`.
+    pub fn export(instructions: &[Instruction], size: (f32, f32)) -> Vec<u8> {
+        let mut pdf = Pdf::new();
+        pdf.page(instructions, size);
+        pdf.finish()
+    }
+
+    /// Adds a page of `size` showing `instructions`.
+    pub fn page(&mut self, instructions: &[Instruction], size: (f32, f32)) {
+        let mut content = Content::new(self);
+        //Flip to the renderer's y down coordinates
+        content.op(&format!("1 0 0 -1 0 {} cm", n(size.1)));
+        instructions.iter().for_each(|instruction| content.instruction(instruction));
+        let ops = compress(content.finish().as_bytes());
+        self.pages.push((size, ops));
+    }
+
+    /// Writes out the document with every page added so far.
+    pub fn finish(&self) -> Vec<u8> {
+        let mut writer = Writer{bytes: b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec(), offsets: vec![]};
+        let catalog = writer.reserve();
+        let tree = writer.reserve();
+        let resources = writer.reserve();
+
+        let fonts = self.fonts.iter().enumerate()
+            .map(|(i, (font, glyphs))| format!("/F{i} {} 0 R", write_font(&mut writer, i, font, glyphs)))
+            .collect::<String>();
+        let images = self.images.iter().enumerate()
+            .map(|(i, (image, size, tint, filter))| format!("/I{i} {} 0 R", write_image(&mut writer, image, *size, *tint, *filter)))
+            .collect::<String>();
+        let mut states = self.opacities.iter().enumerate().map(|(i, (fill, stroke))| {
+            let id = writer.reserve();
+            writer.object(id, &format!("<< /Type /ExtGState /ca {} /CA {} >>", n(*fill as f32 / 255.0), n(*stroke as f32 / 255.0)));
+            format!("/A{i} {id} 0 R")
+        }).collect::<String>();
+        let shadings = self.gradients.iter().enumerate().map(|(i, (gradient, range))| {
+            let shading = write_shading(&mut writer, gradient, *range, false);
+            if gradient.stops.iter().any(|s| s.1.3 < 255) {
+                let mask = write_shading(&mut writer, gradient, *range, true);
+                let form = writer.reserve();
+                writer.stream(form, &format!(
+                    "/Type /XObject /Subtype /Form /BBox [-100000 -100000 100000 100000] /Group << /S /Transparency /CS /DeviceGray >> /Resources << /Shading << /S0 {mask} 0 R >> >>"
+                ), b"/S0 sh");
+                let state = writer.reserve();
+                writer.object(state, &format!("<< /Type /ExtGState /SMask << /S /Luminosity /G {form} 0 R >> >>"));
+                states.push_str(&format!("/M{i} {state} 0 R"));
+            }
+            format!("/S{i} {shading} 0 R")
+        }).collect::<String>();
+        for (i, ops) in self.masks.iter().enumerate() {
+            let form = writer.reserve();
+            writer.stream(form, &format!(
+                "/Type /XObject /Subtype /Form /BBox [-100000 -100000 100000 100000] /Group << /S /Transparency >> /Resources {resources} 0 R /Filter /FlateDecode"
+            ), ops);
+            let state = writer.reserve();
+            writer.object(state, &format!("<< /Type /ExtGState /SMask << /S /Alpha /G {form} 0 R >> >>"));
+            states.push_str(&format!("/K{i} {state} 0 R"));
+        }
+        let mut forms = String::new();
+        for (i, (ops, opacity, blend)) in self.layers.iter().enumerate() {
+            let form = writer.reserve();
+            writer.stream(form, &format!(
+                "/Type /XObject /Subtype /Form /BBox [-100000 -100000 100000 100000] /Group << /S /Transparency >> /Resources {resources} 0 R /Filter /FlateDecode"
+            ), ops);
+            forms.push_str(&format!("/L{i} {form} 0 R"));
+            let state = writer.reserve();
+            let opacity = n(opacity.clamp(0.0, 1.0));
+            writer.object(state, &format!("<< /Type /ExtGState /ca {opacity} /CA {opacity} /BM /{} >>", blend_name(*blend)));
+            states.push_str(&format!("/G{i} {state} 0 R"));
+        }
+        for (i, blend) in self.blends.iter().enumerate() {
+            let state = writer.reserve();
+            writer.object(state, &format!("<< /Type /ExtGState /BM /{} >>", blend_name(*blend)));
+            states.push_str(&format!("/B{i} {state} 0 R"));
+        }
+        writer.object(resources, &format!(
+            "<< /Font << {fonts} >> /XObject << {images}{forms} >> /ExtGState << {states} >> /Shading << {shadings} >> >>"
+        ));
+
+        let kids = self.pages.iter().map(|((w, h), ops)| {
+            let content = writer.reserve();
+            writer.stream(content, "/Filter /FlateDecode", ops);
+            let page = writer.reserve();
+            writer.object(page, &format!(
+                "<< /Type /Page /Parent {tree} 0 R /MediaBox [0 0 {} {}] /Resources {resources} 0 R /Contents {content} 0 R >>",
+                n(*w), n(*h)
+            ));
+            format!("{page} 0 R ")
+        }).collect::<String>();
+        writer.object(tree, &format!("<< /Type /Pages /Kids [{kids}] /Count {} >>", self.pages.len()));
+        writer.object(catalog, &format!("<< /Type /Catalog /Pages {tree} 0 R >>"));
+        writer.finish(catalog)
+    }
+
+    fn font(&mut self, font: &Font) -> usize {
+        self.fonts.iter().position(|(f, _)| f == font).unwrap_or_else(|| {
+            self.fonts.push((font.clone(), BTreeMap::new()));
+            self.fonts.len() - 1
+        })
+    }
+
+    fn image(&mut self, image: &Arc<RgbaImage>, size: (f32, f32), tint: Option<Color>, filter: Option<ColorMatrix>) -> usize {
+        self.images.iter().position(|(i, s, t, f)| Arc::ptr_eq(i, image) && *s == size && *t == tint && *f == filter).unwrap_or_else(|| {
+            self.images.push((image.clone(), size, tint, filter));
+            self.images.len() - 1
+        })
+    }
+
+    fn opacity(&mut self, fill: u8, stroke: u8) -> usize {
+        self.opacities.iter().position(|o| *o == (fill, stroke)).unwrap_or_else(|| {
+            self.opacities.push((fill, stroke));
+            self.opacities.len() - 1
+        })
+    }
+
+    fn blend(&mut self, blend: BlendMode) -> usize {
+        self.blends.iter().position(|b| *b == blend).unwrap_or_else(|| {
+            self.blends.push(blend);
+            self.blends.len() - 1
+        })
+    }
+
+    /// Registers a shading for `gradient` that covers all of `data`, in the path's coordinates.
+    fn gradient(&mut self, gradient: &Gradient, data: &PathData) -> Option<usize> {
+        let [a, b, c, d, e, f] = gradient.transform;
+        let determinant = a*d - b*c;
+        if determinant.abs() < 1e-12 {return None;}
+        let inverse = |(x, y): (f32, f32)| {
+            let (x, y) = (x - e, y - f);
+            ((d*x - c*y) / determinant, (a*y - b*x) / determinant)
+        };
+        let (x, y, w, h) = data.bounds();
+        let corners = [(x, y), (x+w, y), (x, y+h), (x+w, y+h)].map(inverse);
+        let range = match gradient.kind {
+            GradientKind::Linear(start, end) => {
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                let length = dx*dx + dy*dy;
+                if length == 0.0 {return None;}
+                corners.iter().map(|p| ((p.0 - start.0)*dx + (p.1 - start.1)*dy) / length)
+                    .fold((0.0f32, 1.0f32), |(min, max), t| (min.min(t), max.max(t)))
+            },
+            GradientKind::Radial(center, radius) => {
+                if radius <= 0.0 {return None;}
+                let far = corners.iter().map(|p| ((p.0 - center.0).powi(2) + (p.1 - center.1).powi(2)).sqrt()).fold(0.0, f32::max);
+                (0.0, (far / radius).max(1.0))
+            }
+        };
+        self.gradients.push((gradient.clone(), range));
+        Some(self.gradients.len() - 1)
+    }
+}
+
+/// The operators of a page being drawn, registering the resources they use with the document.
+struct Content<'a> {
+    pdf: &'a mut Pdf,
+    ops: String,
+    /// Graphics states saved by clips and masks that have not been popped yet.
+    pushed: usize,
+    /// The operators and pushed states outside every layer that has not been popped yet, with
+    /// the area and layer of its push.
+    layers: Vec<(String, usize, Area, Layer)>,
+}
+
+impl<'a> Content<'a> {
+    fn new(pdf: &'a mut Pdf) -> Self {Content{pdf, ops: String::new(), pushed: 0, layers: vec![]}}
+
+    /// The operators with every layer, clip and mask still pushed closed.
+    fn finish(mut self) -> String {
+        while !self.layers.is_empty() {self.pop_layer();}
+        for _ in 0..self.pushed {self.op("Q");}
+        self.ops
+    }
+
+    fn op(&mut self, op: &str) {
+        self.ops.push_str(op);
+        self.ops.push('\n');
+    }
+
+    fn instruction(&mut self, Instruction(area, item): &Instruction) {
+        match item {
+            Item::PushClip(clip) => return self.push(area, clip),
+            Item::PushMask(mask) => return self.mask(area, mask),
+            Item::PushLayer(layer) => {
+                let outside = (std::mem::take(&mut self.ops), std::mem::take(&mut self.pushed));
+                return self.layers.push((outside.0, outside.1, *area, *layer));
+            },
+            Item::PopClip | Item::PopMask => return self.pop(),
+            Item::PopLayer => return self.pop_layer(),
+            _ => {}
+        }
+        self.op("q");
+        if area.blend != BlendMode::Normal {
+            let blend = self.pdf.blend(area.blend);
+            self.op(&format!("/B{blend} gs"));
+        }
+        self.bounds(area);
+        if area.transform.is_some() {
+            let [a, b, c, d, e, f] = area.matrix().map(n);
+            self.op(&format!("{a} {b} {c} {d} {e} {f} cm"));
+        }
+        match item {
+            Item::Shape(shape) => self.shape(area.offset, shape),
+            Item::Image(image) => self.image(area.offset, image),
+            Item::Text(text) => self.text(area.offset, text),
+            Item::Shadow(shadow) => self.shadow(area.offset, shadow),
+            Item::Path(path) => self.path_item(area.offset, path),
+            Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => unreachable!(),
+        }
+        self.op("Q");
+    }
+
+    /// Clips to the bounds of `area` and their shape.
+    fn bounds(&mut self, area: &Area) {
+        match (area.bounds, area.clip) {
+            (Some(bounds), Some(clip)) if clip != ClipShape::default() => {
+                self.path(&clip.outline((bounds.0, bounds.1, bounds.2.max(0.0), bounds.3.max(0.0))));
+                self.op("W n");
+            },
+            (Some((x, y, w, h)), _) => self.op(&format!("{} {} {} {} re W n", n(x), n(y), n(w.max(0.0)), n(h.max(0.0)))),
+            (None, _) => {}
+        }
+    }
+
+    /// Saves the graphics state and clips to `clip`, transformed to screen space so the
+    /// coordinates of the instructions up to its pop stay untouched.
+    fn push(&mut self, area: &Area, clip: &Clip) {
+        self.op("q");
+        self.bounds(area);
+        let (data, rule) = clip.outline();
+        let (x, y) = area.offset;
+        self.path(&data.transform(crate::transform::multiply(area.matrix(), [1.0, 0.0, 0.0, 1.0, x, y])));
+        self.op(match rule {
+            FillRule::NonZero => "W n",
+            FillRule::EvenOdd => "W* n",
+        });
+        self.pushed += 1;
+    }
+
+    /// Saves the graphics state and sets a soft mask drawing `mask` like the instructions of
+    /// the page, taking its alpha.
+    fn mask(&mut self, area: &Area, mask: &Mask) {
+        let mut content = Content::new(&mut *self.pdf);
+        let ops = match mask {
+            Mask::Image(image) => {
+                content.instruction(&Instruction(*area, Item::Image(image.clone())));
+                content.finish()
+            },
+            Mask::Items(items) => {
+                content.op("q");
+                content.bounds(area);
+                content.op(&format!("1 0 0 1 {} {} cm", n(area.offset.0), n(area.offset.1)));
+                items.iter().for_each(|instruction| content.instruction(instruction));
+                content.finish() + "Q\n"
+            }
+        };
+        let ops = compress(ops.as_bytes());
+        self.pdf.masks.push(ops);
+        self.op("q");
+        self.op(&format!("/K{} gs", self.pdf.masks.len() - 1));
+        self.pushed += 1;
+    }
+
+    /// Paints the operators since the layer pushed last, if any, as a transparency group with
+    /// its opacity and blend mode, inside the clip of its area and transformed by it.
+    fn pop_layer(&mut self) {
+        let Some((ops, pushed, area, layer)) = self.layers.pop() else {return};
+        for _ in 0..self.pushed {self.op("Q");}
+        let group = std::mem::replace(&mut self.ops, ops);
+        self.pushed = pushed;
+        self.pdf.layers.push((compress(group.as_bytes()), layer.opacity, layer.blend));
+        self.op("q");
+        self.bounds(&area);
+        if area.transform.is_some() {
+            let [a, b, c, d, e, f] = area.matrix().map(n);
+            self.op(&format!("{a} {b} {c} {d} {e} {f} cm"));
+        }
+        self.op(&format!("/G{0} gs /L{0} Do", self.pdf.layers.len() - 1));
+        self.op("Q");
+    }
+
+    /// Restores the graphics state saved by the clip or mask pushed last, if any.
+    fn pop(&mut self) {
+        if self.pushed > 0 {
+            self.pushed -= 1;
+            self.op("Q");
+        }
+    }
+
+    fn fill(&mut self, color: Color) {
+        let state = self.pdf.opacity(color.3, 255);
+        self.op(&format!("/A{state} gs {} rg", rgb(color)));
+    }
+
+    fn stroke(&mut self, color: Color, width: f32, cap: u8, join: u8) {
+        let state = self.pdf.opacity(255, color.3);
+        self.op(&format!("/A{state} gs {} RG {} w {cap} J {join} j 4 M", rgb(color), n(width)));
+    }
+
+    /// Moves the origin to the center of `shape` placed at `offset`, rotated with it.
+    fn place(&mut self, shape: &ShapeType, offset: (f32, f32)) {
+        let (x, y) = shape.center(offset);
+        let (sin, cos) = shape.angle().to_radians().sin_cos();
+        self.op(&format!("{} {} {} {} {} {} cm", n(cos), n(sin), n(-sin), n(cos), n(x), n(y)));
+    }
+
+    fn path(&mut self, data: &PathData) {
+        let mut current = (0.0, 0.0);
+        for command in &data.0 {
+            let op = match *command {
+                PathCommand::MoveTo(p) => {current = p; format!("{} {} m", n(p.0), n(p.1))},
+                PathCommand::LineTo(p) => {current = p; format!("{} {} l", n(p.0), n(p.1))},
+                PathCommand::QuadTo(c, p) => {
+                    let c1 = (current.0 + 2.0/3.0*(c.0 - current.0), current.1 + 2.0/3.0*(c.1 - current.1));
+                    let c2 = (p.0 + 2.0/3.0*(c.0 - p.0), p.1 + 2.0/3.0*(c.1 - p.1));
+                    current = p;
+                    format!("{} {} {} {} {} {} c", n(c1.0), n(c1.1), n(c2.0), n(c2.1), n(p.0), n(p.1))
+                },
+                PathCommand::CubicTo(c1, c2, p) => {
+                    current = p;
+                    format!("{} {} {} {} {} {} c", n(c1.0), n(c1.1), n(c2.0), n(c2.1), n(p.0), n(p.1))
+                },
+                PathCommand::Close => "h".to_string(),
+            };
+            self.op(&op);
+        }
+    }
+
+    fn shape(&mut self, offset: (f32, f32), shape: &Shape) {
+        let [w, h] = shape.shape.wh();
+        let stroke = shape.shape.stroke();
+        self.op("q");
+        self.place(&shape.shape, offset);
+        match shape.shape {
+            //Ring segments are drawn as a thick arc along their middle
+            ShapeType::Arc(_, _, _, _, _, rounded) if stroke > 0.0 && stroke < w.min(h) / 2.0 => {
+                let (start, sweep) = shape.shape.sweep().unwrap();
+                let radii = (w/2.0 - stroke/2.0, h/2.0 - stroke/2.0);
+                let mut commands = vec![PathCommand::MoveTo(arc_point((0.0, 0.0), radii, start))];
+                commands.extend(elliptic_arc((0.0, 0.0), radii, start, sweep));
+                self.stroke(shape.color, stroke, if rounded {1} else {0}, 0);
+                self.path(&PathData(commands));
+                self.op("S");
+            },
+            _ if stroke > 0.0 => {
+                self.stroke(shape.color, stroke, 0, 0);
+                self.path(&shape.shape.outline(-stroke / 2.0));
+                self.op("S");
+            },
+            _ => {
+                self.fill(shape.color);
+                self.path(&shape.shape.outline(0.0));
+                self.op("f");
+            }
+        }
+        if let Some(border) = shape.border.filter(|b| b.width > 0.0) {
+            let grow = match border.align {
+                StrokeAlign::Inside => -border.width / 2.0,
+                StrokeAlign::Center => 0.0,
+                StrokeAlign::Outside => border.width / 2.0,
+            };
+            self.stroke(border.color, border.width, 0, 0);
+            self.path(&shape.shape.outline(grow));
+            self.op("S");
+        }
+        self.op("Q");
+    }
+
+    /// Blurred shadows are stacked outlines from `blur / 2` outside to `blur / 2` inside the
+    /// spread edge, each adding an even share of the shadow's opacity.
+    fn shadow(&mut self, offset: (f32, f32), shadow: &Shadow) {
+        self.op("q");
+        self.place(&shadow.shape, (offset.0 + shadow.offset.0, offset.1 + shadow.offset.1));
+        if shadow.blur <= 0.0 {
+            self.fill(shadow.color);
+            self.path(&shadow.shape.outline(shadow.spread));
+            self.op("f");
+        } else {
+            let alpha = shadow.color.3 as f32 / 255.0;
+            let mut covered = 0.0;
+            for i in 0..SHADOW_LAYERS {
+                let target = alpha * (i + 1) as f32 / SHADOW_LAYERS as f32;
+                let layer = if covered < 1.0 {(target - covered) / (1.0 - covered)} else {0.0};
+                covered = target;
+                let grow = shadow.spread + shadow.blur / 2.0 - shadow.blur * (i as f32 + 0.5) / SHADOW_LAYERS as f32;
+                let Color(r, g, b, _) = shadow.color;
+                self.fill(Color(r, g, b, (layer * 255.0).round() as u8));
+                self.path(&shadow.shape.outline(grow));
+                self.op("f");
+            }
+        }
+        self.op("Q");
+    }
+
+    fn image(&mut self, offset: (f32, f32), image: &Image) {
+        let [w, h] = image.shape.wh();
+        if w <= 0.0 || h <= 0.0 || image.image.width() == 0 || image.image.height() == 0 {return;}
+        let index = self.pdf.image(&image.image, (w, h), image.color, image.filter);
+        self.op("q");
+        self.place(&image.shape, offset);
+        if !matches!(image.shape, ShapeType::Rectangle(..)) {
+            self.path(&image.shape.outline(0.0));
+            self.op("W n");
+        }
+        //Sample rows run bottom to top of the unit square, which is the top in y down space
+        self.op(&format!("{} 0 0 {} {} {} cm /I{index} Do", n(w), n(-h), n(-w / 2.0), n(h / 2.0)));
+        self.op("Q");
+    }
+
+    /// Every glyph is positioned on its own, at the pen position it was laid out at.
+    fn text(&mut self, offset: (f32, f32), text: &Text) {
+        let mut current = None;
+        if let Some([a, b, c, d, e, f]) = text.rotation(offset).map(|m| m.map(n)) {
+            self.op(&format!("{a} {b} {c} {d} {e} {f} cm"));
+        }
+        self.op("BT");
+        for line in text.lines() {
+            for (character, size) in line.sized(text) {
+                if character.0.chars().all(char::is_whitespace) {continue;}
+                let font = self.pdf.font(&character.2);
+                let color = character.3.unwrap_or(Color::BLACK);
+                let style = (font, size.to_bits(), color);
+                if current != Some(style) {
+                    self.fill(color);
+                    self.op(&format!("/F{font} {} Tf", n(size)));
+                    current = Some(style);
+                }
+                let (x, y) = character.origin(size);
+                let glyphs = character.0.chars().filter(|c| *c != '\u{fe0f}' && *c != '\u{200d}').map(|c| {
+                    let glyph = character.2.lookup_glyph_index(c);
+                    //Missing characters all fall back to .notdef, which maps to no text
+                    if glyph != 0 {
+                        self.pdf.fonts[font].1.entry(glyph).or_insert(c);
+                    }
+                    format!("{glyph:04X}")
+                }).collect::<Vec<_>>();
+                //Characters of one grapheme overlap, as they do in the renderer
+                for glyph in glyphs {
+                    self.op(&format!("1 0 0 -1 {} {} Tm <{glyph}> Tj", n(offset.0 + x), n(offset.1 + y)));
+                }
+            }
+        }
+        self.op("ET");
+    }
+
+    fn path_item(&mut self, offset: (f32, f32), path: &Path) {
+        let even_odd = path.fill_rule == FillRule::EvenOdd;
+        self.op("q");
+        self.op(&format!("1 0 0 1 {} {} cm", n(offset.0), n(offset.1)));
+        match &path.gradient {
+            Some(gradient) => match self.pdf.gradient(gradient, &path.data) {
+                Some(index) => {
+                    self.op("q");
+                    self.path(&path.data);
+                    self.op(if even_odd {"W* n"} else {"W n"});
+                    let [a, b, c, d, e, f] = gradient.transform.map(n);
+                    self.op(&format!("{a} {b} {c} {d} {e} {f} cm"));
+                    if gradient.stops.iter().any(|s| s.1.3 < 255) {
+                        self.op(&format!("/M{index} gs"));
+                    }
+                    self.op(&format!("/S{index} sh"));
+                    self.op("Q");
+                },
+                //Degenerate gradients paint their last stop
+                None => if let Some((_, color)) = gradient.stops.last() {
+                    self.fill(*color);
+                    self.path(&path.data);
+                    self.op(if even_odd {"f*"} else {"f"});
+                }
+            },
+            None if path.color.3 > 0 => {
+                self.fill(path.color);
+                self.path(&path.data);
+                self.op(if even_odd {"f*"} else {"f"});
+            },
+            None => {}
+        }
+        if let Some(stroke) = path.stroke.filter(|s| s.width > 0.0) {
+            let cap = match stroke.cap {LineCap::Butt => 0, LineCap::Round => 1, LineCap::Square => 2};
+            let join = match stroke.join {LineJoin::Miter => 0, LineJoin::Round => 1, LineJoin::Bevel => 2};
+            self.stroke(stroke.color, stroke.width, cap, join);
+            self.path(&path.data);
+            self.op("S");
+        }
+        self.op("Q");
+    }
+}
+
+/// Numbered objects of the file with their byte offsets for the cross-reference table.
+struct Writer {
+    bytes: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn object(&mut self, id: usize, body: &str) {
+        self.offsets[id - 1] = self.bytes.len();
+        self.bytes.extend_from_slice(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes());
+    }
+
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) {
+        self.offsets[id - 1] = self.bytes.len();
+        self.bytes.extend_from_slice(format!("{id} 0 obj\n<< {dictionary} /Length {} >>\nstream\n", data.len()).as_bytes());
+        self.bytes.extend_from_slice(data);
+        self.bytes.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        let xref = self.bytes.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        self.offsets.iter().for_each(|offset| table.push_str(&format!("{offset:010} 00000 n \n")));
+        table.push_str(&format!("trailer\n<< /Size {} /Root {root} 0 R >>\nstartxref\n{xref}\n%%EOF\n", self.offsets.len() + 1));
+        self.bytes.extend_from_slice(table.as_bytes());
+        self.bytes
+    }
+}
+
+/// Embeds `font` as a composite font addressed by glyph id, with only `glyphs` kept. Its file is
+/// left to the viewer when it is not known.
+fn write_font(writer: &mut Writer, index: usize, font: &Font, glyphs: &BTreeMap<u16, char>) -> usize {
+    let mut ids = vec![0];
+    ids.extend(glyphs.keys());
+    let bytes = font.bytes();
+    let cff = bytes.as_ref().is_some_and(|b| b.starts_with(b"OTTO"));
+    let file = bytes.map(|bytes| {
+        //A font the subsetter does not understand is embedded whole
+        let data = subsetter::subset(&bytes, 0, subsetter::Profile::pdf(&ids)).unwrap_or_else(|_| bytes.to_vec());
+        let file = writer.reserve();
+        let length = match cff {
+            true => "/Subtype /OpenType".to_string(),
+            false => format!("/Length1 {}", data.len())
+        };
+        writer.stream(file, &format!("/Filter /FlateDecode {length}"), &compress(&data));
+        format!(" /{} {file} 0 R", if cff {"FontFile3"} else {"FontFile2"})
+    }).unwrap_or_default();
+
+    //Font units scaled to the thousandths of an em PDF measures glyphs in
+    let metrics = glyphs.keys().map(|g| font.metrics_indexed(*g, 1000.0)).collect::<Vec<_>>();
+    let bbox = metrics.iter().fold([0.0f32; 4], |b, m| [
+        b[0].min(m.bounds.xmin), b[1].min(m.bounds.ymin),
+        b[2].max(m.bounds.xmin + m.bounds.width), b[3].max(m.bounds.ymin + m.bounds.height)
+    ]);
+    let (ascent, descent) = font.horizontal_line_metrics(1000.0).map(|m| (m.ascent, m.descent)).unwrap_or((bbox[3], bbox[1]));
+    let name = format!("{}+Font{index}", tag(font.file_hash()));
+    let descriptor = writer.reserve();
+    writer.object(descriptor, &format!(
+        "<< /Type /FontDescriptor /FontName /{name} /Flags 4 /FontBBox [{} {} {} {}] /ItalicAngle 0 /Ascent {} /Descent {} /CapHeight {} /StemV 80{file} >>",
+        n(bbox[0]), n(bbox[1]), n(bbox[2]), n(bbox[3]), n(ascent), n(descent), n(ascent)
+    ));
+
+    let widths = glyphs.keys().zip(&metrics).map(|(g, m)| format!("{g} [{}] ", n(m.advance_width))).collect::<String>();
+    let cid = writer.reserve();
+    writer.object(cid, &format!(
+        "<< /Type /Font /Subtype /{} /BaseFont /{name} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {descriptor} 0 R /DW 0 /W [{widths}]{} >>",
+        if cff {"CIDFontType0"} else {"CIDFontType2"},
+        if cff {""} else {" /CIDToGIDMap /Identity"}
+    ));
+
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n/CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n"
+    );
+    let entries = glyphs.iter().collect::<Vec<_>>();
+    for chunk in entries.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for (glyph, c) in chunk {
+            let utf16 = c.encode_utf16(&mut [0; 2]).iter().map(|u| format!("{u:04X}")).collect::<String>();
+            cmap.push_str(&format!("<{glyph:04X}> <{utf16}>\n"));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    let unicode = writer.reserve();
+    writer.stream(unicode, "/Filter /FlateDecode", &compress(cmap.as_bytes()));
+
+    let id = writer.reserve();
+    writer.object(id, &format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /{name} /Encoding /Identity-H /DescendantFonts [{cid} 0 R] /ToUnicode {unicode} 0 R >>"
+    ));
+    id
+}
+
+/// Embeds the part of `image` shown in a shape of `size`, with its alpha as a soft mask.
+fn write_image(writer: &mut Writer, image: &RgbaImage, size: (f32, f32), tint: Option<Color>, filter: Option<ColorMatrix>) -> usize {
+    let image = Image::visible(image, size, tint, filter);
+    let (w, h) = (image.width(), image.height());
+    let dictionary = |space: &str| format!(
+        "/Type /XObject /Subtype /Image /Width {w} /Height {h} /ColorSpace /{space} /BitsPerComponent 8 /Interpolate true /Filter /FlateDecode"
+    );
+    let mask = match image.pixels().any(|p| p.0[3] < 255) {
+        true => {
+            let alpha = image.pixels().map(|p| p.0[3]).collect::<Vec<_>>();
+            let id = writer.reserve();
+            writer.stream(id, &dictionary("DeviceGray"), &compress(&alpha));
+            format!(" /SMask {id} 0 R")
+        },
+        false => String::new()
+    };
+    let rgb = image.pixels().flat_map(|p| [p.0[0], p.0[1], p.0[2]]).collect::<Vec<_>>();
+    let id = writer.reserve();
+    writer.stream(id, &format!("{}{mask}", dictionary("DeviceRGB")), &compress(&rgb));
+    id
+}
+
+/// An axial or radial shading sampling `gradient` (or its opacity when `alpha`) over `range`,
+/// which bakes the spread into the samples.
+fn write_shading(writer: &mut Writer, gradient: &Gradient, (t0, t1): (f32, f32), alpha: bool) -> usize {
+    let samples = (((t1 - t0) * 256.0).ceil() as usize).clamp(2, 4096);
+    let colors = (0..samples).map(|i| gradient.color_at(t0 + (t1 - t0) * i as f32 / (samples - 1) as f32));
+    let (data, range, space) = match alpha {
+        true => (colors.map(|c| c.3).collect::<Vec<_>>(), "0 1", "DeviceGray"),
+        false => (colors.flat_map(|c| [c.0, c.1, c.2]).collect::<Vec<_>>(), "0 1 0 1 0 1", "DeviceRGB"),
+    };
+    let function = writer.reserve();
+    writer.stream(function, &format!(
+        "/FunctionType 0 /Domain [{} {}] /Range [{range}] /Size [{samples}] /BitsPerSample 8 /Filter /FlateDecode",
+        n(t0), n(t1)
+    ), &compress(&data));
+
+    let (kind, coords) = match gradient.kind {
+        GradientKind::Linear(start, end) => {
+            let at = |t: f32| (start.0 + (end.0 - start.0) * t, start.1 + (end.1 - start.1) * t);
+            let (a, b) = (at(t0), at(t1));
+            (2, format!("{} {} {} {}", n(a.0), n(a.1), n(b.0), n(b.1)))
+        },
+        GradientKind::Radial(center, radius) =>
+            (3, format!("{} {} 0 {} {} {}", n(center.0), n(center.1), n(center.0), n(center.1), n(radius * t1))),
+    };
+    let id = writer.reserve();
+    writer.object(id, &format!(
+        "<< /ShadingType {kind} /ColorSpace /{space} /Coords [{coords}] /Domain [{} {}] /Function {function} 0 R /Extend [true true] >>",
+        n(t0), n(t1)
+    ));
+    id
+}
+
+/// The PDF blend mode closest to `mode`.
+fn blend_name(mode: BlendMode) -> &'static str {match mode {
+    BlendMode::Multiply => "Multiply",
+    BlendMode::Screen => "Screen",
+    //Adding has no PDF blend mode, lightening is the closest
+    BlendMode::Add | BlendMode::Lighten => "Lighten",
+    BlendMode::Overlay => "Overlay",
+    BlendMode::Darken => "Darken",
+    BlendMode::Difference => "Difference",
+    _ => "Normal",
+}}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).expect("writing to memory");
+    encoder.finish().expect("writing to memory")
+}
+
+/// The six letter subset tag PDF expects in front of the name of an embedded subset.
+fn tag(hash: usize) -> String {
+    (0..6).map(|i| (b'A' + ((hash >> (i * 5)) % 26) as u8) as char).collect()
+}
+
+fn rgb(color: Color) -> String {
+    format!("{} {} {}", n(color.0 as f32 / 255.0), n(color.1 as f32 / 255.0), n(color.2 as f32 / 255.0))
+}
+
+/// Rounds to a thousandth of a point to keep the content short.
+fn n(value: f32) -> f32 {
+    (value * 1000.0).round() / 1000.0 + 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use super::*;
+
+    fn shape(offset: (f32, f32), shape: ShapeType, color: Color) -> Instruction {
+        Instruction(Area::new(offset, None), Item::Shape(Shape::new(shape, color)))
+    }
+
+    fn inflate(data: &[u8]) -> String {
+        let mut ops = String::new();
+        flate2::read::ZlibDecoder::new(data).read_to_string(&mut ops).unwrap();
+        ops
+    }
+
+    /// Whether every `q` in `ops` is restored by a later `Q`.
+    fn balanced(ops: &str) -> bool {
+        let mut depth = 0;
+        ops.lines().all(|op| {
+            match op {"q" => depth += 1, "Q" => depth -= 1, _ => {}}
+            depth >= 0
+        }) && depth == 0
+    }
+
+    fn document() -> Pdf {
+        let image = Arc::new(RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 255, 128])));
+        let placed = |offset| Instruction(Area::new(offset, None), Item::Image(Image::new(
+            ShapeType::Rectangle(0.0, (20.0, 20.0), 0.0), image.clone(), None
+        )));
+        let mut pdf = Pdf::new();
+        pdf.page(&[
+            shape((0.0, 0.0), ShapeType::Rectangle(0.0, (10.0, 10.0), 0.0), Color(255, 0, 0, 255)),
+            Instruction(Area::default(), Item::PushClip(Clip::Shape(ShapeType::Ellipse(0.0, (50.0, 50.0), 0.0)))),
+            placed((0.0, 0.0)),
+            Instruction(Area::default(), Item::PushLayer(Layer{blend: BlendMode::Screen, ..Layer::new(0.5)})),
+            Instruction(Area::default(), Item::PushMask(Mask::Items(vec![shape((0.0, 0.0), ShapeType::Ellipse(0.0, (5.0, 5.0), 0.0), Color::BLACK)]))),
+            placed((30.0, 0.0)),
+            shape((0.0, 30.0), ShapeType::Arc(4.0, (20.0, 20.0), 0.0, 0.0, 90.0, true), Color(0, 255, 0, 128)),
+        ], (100.0, 50.0));
+        pdf.page(&[shape((0.0, 0.0), ShapeType::Ellipse(0.0, (10.0, 10.0), 0.0), Color(255, 0, 0, 255))], (20.0, 30.0));
+        pdf
+    }
+
+    #[test]
+    fn the_cross_reference_table_finds_every_object() {
+        let bytes = document().finish();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with("%PDF-1.7\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(bytes[xref..].starts_with(b"xref\n"));
+        let offsets = text[xref..].lines().skip(3).take_while(|l| l.ends_with(" n ")).collect::<Vec<_>>();
+        assert!(text.contains(&format!("/Size {}", offsets.len() + 1)));
+        for (i, entry) in offsets.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(bytes[offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()), "object {}", i + 1);
+        }
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/MediaBox [0 0 100 50]") && text.contains("/MediaBox [0 0 20 30]"));
+        assert!(text.contains("/BM /Screen"));
+    }
+
+    #[test]
+    fn pushes_left_open_are_restored() {
+        let pdf = document();
+        let page = inflate(&pdf.pages[0].1);
+        assert!(balanced(&page), "{page}");
+        assert!(page.starts_with("1 0 0 -1 0 50 cm\n"));
+        assert!(page.contains("/G0 gs /L0 Do"));
+        assert!(pdf.layers.iter().all(|(ops, _, _)| balanced(&inflate(ops))));
+        assert!(pdf.masks.iter().all(|ops| balanced(&inflate(ops))));
+    }
+
+    #[test]
+    fn resources_are_shared_between_instructions_and_pages() {
+        let pdf = document();
+        assert_eq!(pdf.images.len(), 1);
+        //Opaque and half transparent red fills, the half transparent arc stroke
+        assert_eq!(pdf.opacities, vec![(255, 255), (255, 128)]);
+        let ops = inflate(&pdf.pages[1].1);
+        assert!(ops.contains("/A0 gs 1 0 0 rg"));
+        //Rounded arcs are stroked along their middle with round caps
+        let layer = inflate(&pdf.layers[0].0);
+        assert!(layer.contains("/A1 gs 0 1 0 RG 4 w 1 J 0 j 4 M"), "{layer}");
+    }
+
+    #[test]
+    fn subset_tags_are_six_capitals() {
+        for hash in [0, 1, usize::MAX, 0x1234_5678] {
+            let tag = tag(hash);
+            assert_eq!(tag.len(), 6);
+            assert!(tag.chars().all(|c| c.is_ascii_uppercase()));
+        }
+        assert_ne!(tag(1), tag(2));
+        assert_eq!(rgb(Color(255, 0, 128, 0)), "1 0 0.502");
+    }
+}
@@ -0,0 +1,726 @@
+use std::io::{self, Read, Write, Cursor};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Weak};
+
+use image::ImageFormat;
+
+use crate::{Instruction, Area, Item, Shape, ShapeType, Border, StrokeAlign, InnerShadow, Shadow, Image, RgbaImage, Color};
+use crate::{Path, PathData, PathCommand, FillRule, Stroke, LineCap, LineJoin, Gradient, GradientKind, GradientSpread};
+use crate::{Text, Span, Align, Font, Transform, ClipShape, Clip, Mask, Layer, BlendMode, Blur, BlurEdge, ColorMatrix};
+
+const MAGIC: &[u8; 4] = b"WGCR";
+/// Raised with every release changing the encoding. Players only read recordings of their
+/// own version: recordings are repros for the build that made them, not an archive format.
+const VERSION: u8 = 1;
+
+/// Every blend mode, by the byte it is recorded as.
+const BLEND_MODES: [BlendMode; 18] = [
+    BlendMode::Normal, BlendMode::Multiply, BlendMode::Screen, BlendMode::Add, BlendMode::Overlay, BlendMode::Darken,
+    BlendMode::Lighten, BlendMode::Difference, BlendMode::Clear, BlendMode::Source, BlendMode::SourceIn, BlendMode::SourceOut,
+    BlendMode::SourceAtop, BlendMode::DestinationOver, BlendMode::DestinationIn, BlendMode::DestinationOut,
+    BlendMode::DestinationAtop, BlendMode::Xor,
+];
+
+/// Most masks of instructions a player reads nested in one another, so a broken recording
+/// cannot overflow its stack.
+const MAX_NESTING: usize = 64;
+
+const FONT: u8 = 0;
+const IMAGE: u8 = 1;
+const FRAME: u8 = 2;
+
+/// One recorded [`Canvas::draw`](crate::Canvas) call: the surface size and what was drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub size: (u32, u32),
+    pub instructions: Vec<Instruction>,
+}
+
+/// Writes frames to a compact binary recording that [`Player`] reads back.
+///
+/// Every font and image is written once, the first time a frame uses it, and referenced by
+/// index afterwards. Images are recognised by allocation first and by their pixels otherwise,
+/// so reloading the same picture every frame does not grow the recording.
+pub struct Recorder<W: Write> {
+    writer: W,
+    fonts: HashMap<u64, u32>,
+    images: HashMap<usize, (Weak<RgbaImage>, u32)>,
+    image_pixels: HashMap<u64, u32>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a recording, writing its header to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Recorder{writer, fonts: HashMap::new(), images: HashMap::new(), image_pixels: HashMap::new()})
+    }
+
+    /// Appends a frame of `size` drawing `instructions`, preceded by any fonts and images it
+    /// is the first to use. Fails with [`io::ErrorKind::InvalidInput`] for a font not loaded
+    /// with [`Font::from_bytes`], its file not being known.
+    pub fn record(&mut self, size: (u32, u32), instructions: &[Instruction]) -> io::Result<()> {
+        let mut frame = Encoder(vec![FRAME]);
+        frame.u32(size.0);
+        frame.u32(size.1);
+        self.instructions(&mut frame, instructions)?;
+        self.writer.write_all(&frame.0)
+    }
+
+    /// Flushes the recording and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn instructions(&mut self, frame: &mut Encoder, instructions: &[Instruction]) -> io::Result<()> {
+        frame.len(instructions.len());
+        for Instruction(area, item) in instructions {
+            frame.area(area);
+            match item {
+                Item::Shape(shape) => {
+                    frame.u8(0);
+                    frame.shape(shape);
+                },
+                Item::Image(image) => {
+                    frame.u8(1);
+                    self.image_item(frame, image)?;
+                },
+                Item::Text(text) => {
+                    frame.u8(2);
+                    frame.len(text.spans.len());
+                    for span in &text.spans {
+                        let font = self.font(&span.font)?;
+                        frame.span(span, font);
+                    }
+                    frame.option(&text.width, |e, w| e.f32(*w));
+                    frame.u8(match text.align {Align::Left => 0, Align::Center => 1, Align::Right => 2});
+                    frame.option(&text.cursor, |e, c| e.len(*c));
+                    frame.option(&text.max_lines, |e, l| e.u32(*l));
+                    frame.f32(text.angle);
+                },
+                Item::Shadow(shadow) => {
+                    frame.u8(3);
+                    frame.shadow(shadow);
+                },
+                Item::Path(path) => {
+                    frame.u8(4);
+                    frame.path(path);
+                },
+                Item::PushClip(Clip::Shape(shape)) => {
+                    frame.u8(5);
+                    frame.shape_type(shape);
+                },
+                Item::PushClip(Clip::Path(data, rule)) => {
+                    frame.u8(6);
+                    frame.path_data(data);
+                    frame.fill_rule(rule);
+                },
+                Item::PopClip => frame.u8(7),
+                Item::PushMask(Mask::Image(image)) => {
+                    frame.u8(8);
+                    frame.u8(0);
+                    self.image_item(frame, image)?;
+                },
+                Item::PushMask(Mask::Items(items)) => {
+                    frame.u8(8);
+                    frame.u8(1);
+                    self.instructions(frame, items)?;
+                },
+                Item::PopMask => frame.u8(9),
+                Item::PushLayer(layer) => {
+                    frame.u8(10);
+                    frame.f32(layer.opacity);
+                    frame.blend_mode(&layer.blend);
+                    frame.option(&layer.blur, |e, b| e.blur(b));
+                    frame.option(&layer.filter, Encoder::color_matrix);
+                },
+                Item::PopLayer => frame.u8(11),
+            }
+        }
+        Ok(())
+    }
+
+    fn image_item(&mut self, frame: &mut Encoder, image: &Image) -> io::Result<()> {
+        frame.shape_type(&image.shape);
+        let index = self.image(&image.image)?;
+        frame.u32(index);
+        frame.option(&image.color, Encoder::color);
+        frame.option(&image.filter, Encoder::color_matrix);
+        Ok(())
+    }
+
+    fn font(&mut self, font: &Font) -> io::Result<u32> {
+        if let Some(index) = self.fonts.get(&font.id()) {
+            return Ok(*index);
+        }
+        let bytes = font.bytes().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "font not loaded with Font::from_bytes"))?;
+        let index = self.fonts.len() as u32;
+        let mut record = Encoder(vec![FONT]);
+        record.bytes(&bytes);
+        self.writer.write_all(&record.0)?;
+        self.fonts.insert(font.id(), index);
+        Ok(index)
+    }
+
+    fn image(&mut self, image: &Arc<RgbaImage>) -> io::Result<u32> {
+        let key = Arc::as_ptr(image) as usize;
+        if let Some((_, index)) = self.images.get(&key).filter(|(i, _)| i.strong_count() > 0) {
+            return Ok(*index);
+        }
+        let mut hasher = DefaultHasher::new();
+        image.dimensions().hash(&mut hasher);
+        image.as_raw().hash(&mut hasher);
+        //Allocations of images gone can not be used again, so they are forgotten with them
+        self.images.retain(|_, (image, _)| image.strong_count() > 0);
+        let index = match self.image_pixels.get(&hasher.finish()) {
+            Some(index) => *index,
+            None => {
+                let index = self.image_pixels.len() as u32;
+                let mut png = Vec::new();
+                image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).map_err(io::Error::other)?;
+                let mut record = Encoder(vec![IMAGE]);
+                record.bytes(&png);
+                self.writer.write_all(&record.0)?;
+                self.image_pixels.insert(hasher.finish(), index);
+                index
+            }
+        };
+        self.images.insert(key, (Arc::downgrade(image), index));
+        Ok(index)
+    }
+}
+
+/// Reads the frames of a recording written by [`Recorder`], in order.
+pub struct Player<R: Read> {
+    reader: R,
+    fonts: Vec<Font>,
+    images: Vec<Arc<RgbaImage>>,
+}
+
+impl<R: Read> Player<R> {
+    /// Opens a recording, checking its header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {return Err(invalid("not a recording"));}
+        if header[4] != VERSION {return Err(invalid(format!("unsupported recording version {}", header[4])));}
+        Ok(Player{reader, fonts: vec![], images: vec![]})
+    }
+
+    /// The next frame, or `None` at the end of the recording.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        loop {
+            let mut tag = [0];
+            if self.reader.read(&mut tag)? == 0 {return Ok(None);}
+            let mut d = Decoder(&mut self.reader);
+            match tag[0] {
+                FONT => {
+                    let font = Font::from_bytes(&d.bytes()?).map_err(invalid)?;
+                    self.fonts.push(font);
+                },
+                IMAGE => {
+                    let image = image::load_from_memory_with_format(&d.bytes()?, ImageFormat::Png).map_err(invalid)?;
+                    self.images.push(Arc::new(image.into_rgba8()));
+                },
+                FRAME => return self.frame().map(Some),
+                tag => return Err(invalid(format!("unknown record {tag}"))),
+            }
+        }
+    }
+
+    fn frame(&mut self) -> io::Result<Frame> {
+        let mut d = Decoder(&mut self.reader);
+        let size = (d.u32()?, d.u32()?);
+        let instructions = Self::instructions(&mut d, &self.fonts, &self.images, 0)?;
+        Ok(Frame{size, instructions})
+    }
+
+    fn instructions(d: &mut Decoder<'_, R>, fonts: &[Font], images: &[Arc<RgbaImage>], depth: usize) -> io::Result<Vec<Instruction>> {
+        if depth > MAX_NESTING {return Err(invalid("masks nested too deep"));}
+        let count = d.len()?;
+        let mut instructions = Vec::with_capacity(count.min(4096));
+        for _ in 0..count {
+            let area = d.area()?;
+            let item = match d.u8()? {
+                0 => Item::Shape(d.shape()?),
+                1 => Item::Image(Self::image(d, images)?),
+                2 => {
+                    let spans = (0..d.len()?).map(|_| {
+                        let text = d.string()?;
+                        let font_size = d.f32()?;
+                        let line_height = d.option(Decoder::f32)?;
+                        let font = fonts.get(d.u32()? as usize).ok_or_else(|| invalid("unknown font"))?.clone();
+                        Ok(Span{text, font_size, line_height, font, color: d.color()?, kerning: d.f32()?})
+                    }).collect::<io::Result<Vec<_>>>()?;
+                    let width = d.option(Decoder::f32)?;
+                    let align = match d.u8()? {0 => Align::Left, 1 => Align::Center, 2 => Align::Right, _ => return Err(invalid("unknown alignment"))};
+                    Item::Text(Text{
+                        spans, width, align, cursor: d.option(Decoder::len)?, max_lines: d.option(Decoder::u32)?, angle: d.f32()?
+                    })
+                },
+                3 => Item::Shadow(d.shadow()?),
+                4 => Item::Path(d.path()?),
+                5 => Item::PushClip(Clip::Shape(d.shape_type()?)),
+                6 => Item::PushClip(Clip::Path(d.path_data()?, d.fill_rule()?)),
+                7 => Item::PopClip,
+                8 => Item::PushMask(match d.u8()? {
+                    0 => Mask::Image(Self::image(d, images)?),
+                    1 => Mask::Items(Self::instructions(d, fonts, images, depth + 1)?),
+                    tag => return Err(invalid(format!("unknown mask {tag}"))),
+                }),
+                9 => Item::PopMask,
+                10 => Item::PushLayer(Layer{opacity: d.f32()?, blend: d.blend_mode()?, blur: d.option(|d| d.blur())?, filter: d.option(Decoder::color_matrix)?}),
+                11 => Item::PopLayer,
+                tag => return Err(invalid(format!("unknown item {tag}"))),
+            };
+            instructions.push(Instruction(area, item));
+        }
+        Ok(instructions)
+    }
+
+    fn image(d: &mut Decoder<'_, R>, images: &[Arc<RgbaImage>]) -> io::Result<Image> {
+        let shape = d.shape_type()?;
+        let image = images.get(d.u32()? as usize).ok_or_else(|| invalid("unknown image"))?.clone();
+        Ok(Image{shape, image, color: d.option(Decoder::color)?, filter: d.option(Decoder::color_matrix)?})
+    }
+}
+
+impl<R: Read> Iterator for Player<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Little endian values, with lengths as LEB128 varints.
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, v: u8) {self.0.push(v);}
+    fn u32(&mut self, v: u32) {self.len(v as usize);}
+    fn f32(&mut self, v: f32) {self.0.extend(v.to_le_bytes());}
+    fn point(&mut self, (x, y): &(f32, f32)) {self.f32(*x); self.f32(*y);}
+    fn color(&mut self, c: &Color) {self.0.extend([c.0, c.1, c.2, c.3]);}
+
+    fn len(&mut self, mut v: usize) {
+        while v >= 0x80 {
+            self.0.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.0.push(v as u8);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.0.extend(bytes);
+    }
+
+    fn option<T>(&mut self, v: &Option<T>, f: impl FnOnce(&mut Self, &T)) {match v {
+        Some(v) => {self.u8(1); f(self, v);},
+        None => self.u8(0),
+    }}
+
+    fn area(&mut self, area: &Area) {
+        self.point(&area.offset);
+        self.option(&area.bounds, |e, b| {e.f32(b.0); e.f32(b.1); e.f32(b.2); e.f32(b.3);});
+        self.option(&area.clip, |e, c| {c.radii.iter().for_each(|r| e.f32(*r)); e.f32(c.angle);});
+        self.option(&area.transform, |e, t| {t.matrix.iter().for_each(|v| e.f32(*v)); e.point(&t.origin); e.point(&t.perspective);});
+        self.blend_mode(&area.blend);
+    }
+
+    fn shape_type(&mut self, shape: &ShapeType) {
+        let (tag, stroke, size, angle) = match shape {
+            ShapeType::Ellipse(s, wh, a) => (0, s, wh, a),
+            ShapeType::Rectangle(s, wh, a) => (1, s, wh, a),
+            ShapeType::RoundedRectangle(s, wh, a, _) => (2, s, wh, a),
+            ShapeType::Arc(s, wh, a, _, _, _) => (3, s, wh, a),
+            ShapeType::Polygon(s, wh, a, _, _) => (4, s, wh, a),
+            ShapeType::Star(s, wh, a, _, _, _) => (5, s, wh, a),
+            ShapeType::SmoothRoundedRectangle(s, wh, a, _, _) => (6, s, wh, a),
+        };
+        self.u8(tag);
+        self.f32(*stroke);
+        self.point(size);
+        self.f32(*angle);
+        match shape {
+            ShapeType::Ellipse(..) | ShapeType::Rectangle(..) => {},
+            ShapeType::RoundedRectangle(_, _, _, c) => self.f32(*c),
+            ShapeType::SmoothRoundedRectangle(_, _, _, c, s) => {self.f32(*c); self.f32(*s);},
+            ShapeType::Arc(_, _, _, st, sw, r) => {self.f32(*st); self.f32(*sw); self.u8(*r as u8);},
+            ShapeType::Polygon(_, _, _, n, c) => {self.u32(*n); self.f32(*c);},
+            ShapeType::Star(_, _, _, n, r, c) => {self.u32(*n); self.f32(*r); self.f32(*c);},
+        }
+    }
+
+    fn shape(&mut self, shape: &Shape) {
+        self.shape_type(&shape.shape);
+        self.color(&shape.color);
+        self.option(&shape.border, |e, b| {
+            e.color(&b.color);
+            e.f32(b.width);
+            e.u8(match b.align {StrokeAlign::Inside => 0, StrokeAlign::Center => 1, StrokeAlign::Outside => 2});
+        });
+        self.option(&shape.inner_shadow, |e, s| {
+            e.color(&s.color);
+            e.point(&s.offset);
+            e.f32(s.blur);
+            e.f32(s.spread);
+        });
+        self.option(&shape.backdrop, |e, b| e.blur(b));
+    }
+
+    fn shadow(&mut self, shadow: &Shadow) {
+        self.shape_type(&shadow.shape);
+        self.color(&shadow.color);
+        self.point(&shadow.offset);
+        self.f32(shadow.blur);
+        self.f32(shadow.spread);
+    }
+
+    fn span(&mut self, span: &Span, font: u32) {
+        self.bytes(span.text.as_bytes());
+        self.f32(span.font_size);
+        self.option(&span.line_height, |e, l| e.f32(*l));
+        self.u32(font);
+        self.color(&span.color);
+        self.f32(span.kerning);
+    }
+
+    fn path_data(&mut self, data: &PathData) {
+        self.len(data.0.len());
+        for command in &data.0 {match command {
+            PathCommand::MoveTo(p) => {self.u8(0); self.point(p);},
+            PathCommand::LineTo(p) => {self.u8(1); self.point(p);},
+            PathCommand::QuadTo(c, p) => {self.u8(2); self.point(c); self.point(p);},
+            PathCommand::CubicTo(c1, c2, p) => {self.u8(3); self.point(c1); self.point(c2); self.point(p);},
+            PathCommand::Close => self.u8(4),
+        }}
+    }
+
+    fn fill_rule(&mut self, rule: &FillRule) {
+        self.u8(match rule {FillRule::NonZero => 0, FillRule::EvenOdd => 1});
+    }
+
+    fn blend_mode(&mut self, mode: &BlendMode) {
+        self.u8(BLEND_MODES.iter().position(|m| m == mode).unwrap_or_default() as u8);
+    }
+
+    fn color_matrix(&mut self, matrix: &ColorMatrix) {
+        for v in matrix.0 {self.f32(v);}
+    }
+
+    fn blur(&mut self, blur: &Blur) {
+        self.f32(blur.radius);
+        self.u8(match blur.edge {BlurEdge::Transparent => 0, BlurEdge::Clamp => 1, BlurEdge::Mirror => 2});
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.path_data(&path.data);
+        self.color(&path.color);
+        self.option(&path.gradient, |e, g| {
+            match g.kind {
+                GradientKind::Linear(start, end) => {e.u8(0); e.point(&start); e.point(&end);},
+                GradientKind::Radial(center, radius) => {e.u8(1); e.point(&center); e.f32(radius);},
+            }
+            e.len(g.stops.len());
+            g.stops.iter().for_each(|(o, c)| {e.f32(*o); e.color(c);});
+            e.u8(match g.spread {GradientSpread::Pad => 0, GradientSpread::Reflect => 1, GradientSpread::Repeat => 2});
+            g.transform.iter().for_each(|v| e.f32(*v));
+        });
+        self.fill_rule(&path.fill_rule);
+        self.option(&path.stroke, |e, s| {
+            e.color(&s.color);
+            e.f32(s.width);
+            e.u8(match s.cap {LineCap::Butt => 0, LineCap::Round => 1, LineCap::Square => 2});
+            e.u8(match s.join {LineJoin::Miter => 0, LineJoin::Round => 1, LineJoin::Bevel => 2});
+        });
+    }
+}
+
+struct Decoder<'a, R: Read>(&'a mut R);
+
+impl<R: Read> Decoder<'_, R> {
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {Ok(self.array::<1>()?[0])}
+    fn u32(&mut self) -> io::Result<u32> {u32::try_from(self.len()?).map_err(invalid)}
+    fn f32(&mut self) -> io::Result<f32> {Ok(f32::from_le_bytes(self.array()?))}
+    fn point(&mut self) -> io::Result<(f32, f32)> {Ok((self.f32()?, self.f32()?))}
+    fn color(&mut self) -> io::Result<Color> {let [r, g, b, a] = self.array()?; Ok(Color(r, g, b, a))}
+    fn bool(&mut self) -> io::Result<bool> {Ok(self.u8()? != 0)}
+
+    fn len(&mut self) -> io::Result<usize> {
+        let mut v = 0usize;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            v |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {return Ok(v);}
+        }
+        Err(invalid("length too long"))
+    }
+
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.len()?;
+        let mut bytes = Vec::new();
+        self.0.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {return Err(io::ErrorKind::UnexpectedEof.into());}
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(invalid)
+    }
+
+    fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<Option<T>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => f(self).map(Some),
+            _ => Err(invalid("invalid option")),
+        }
+    }
+
+    fn area(&mut self) -> io::Result<Area> {
+        Ok(Area{
+            offset: self.point()?,
+            bounds: self.option(|d| Ok((d.f32()?, d.f32()?, d.f32()?, d.f32()?)))?,
+            clip: self.option(|d| Ok(ClipShape{radii: [d.f32()?, d.f32()?, d.f32()?, d.f32()?], angle: d.f32()?}))?,
+            transform: self.option(|d| Ok(Transform{
+                matrix: [d.f32()?, d.f32()?, d.f32()?, d.f32()?, d.f32()?, d.f32()?],
+                origin: d.point()?,
+                perspective: d.point()?,
+            }))?,
+            blend: self.blend_mode()?,
+        })
+    }
+
+    fn shape_type(&mut self) -> io::Result<ShapeType> {
+        let tag = self.u8()?;
+        let (s, wh, a) = (self.f32()?, self.point()?, self.f32()?);
+        Ok(match tag {
+            0 => ShapeType::Ellipse(s, wh, a),
+            1 => ShapeType::Rectangle(s, wh, a),
+            2 => ShapeType::RoundedRectangle(s, wh, a, self.f32()?),
+            3 => ShapeType::Arc(s, wh, a, self.f32()?, self.f32()?, self.bool()?),
+            4 => ShapeType::Polygon(s, wh, a, self.u32()?, self.f32()?),
+            5 => ShapeType::Star(s, wh, a, self.u32()?, self.f32()?, self.f32()?),
+            6 => ShapeType::SmoothRoundedRectangle(s, wh, a, self.f32()?, self.f32()?),
+            tag => return Err(invalid(format!("unknown shape {tag}"))),
+        })
+    }
+
+    fn stroke_align(&mut self) -> io::Result<StrokeAlign> {
+        Ok(match self.u8()? {0 => StrokeAlign::Inside, 1 => StrokeAlign::Center, 2 => StrokeAlign::Outside, _ => return Err(invalid("unknown stroke alignment"))})
+    }
+
+    fn shape(&mut self) -> io::Result<Shape> {
+        Ok(Shape{
+            shape: self.shape_type()?,
+            color: self.color()?,
+            border: self.option(|d| Ok(Border{color: d.color()?, width: d.f32()?, align: d.stroke_align()?}))?,
+            inner_shadow: self.option(|d| Ok(InnerShadow{color: d.color()?, offset: d.point()?, blur: d.f32()?, spread: d.f32()?}))?,
+            backdrop: self.option(|d| d.blur())?,
+        })
+    }
+
+    fn shadow(&mut self) -> io::Result<Shadow> {
+        Ok(Shadow{shape: self.shape_type()?, color: self.color()?, offset: self.point()?, blur: self.f32()?, spread: self.f32()?})
+    }
+
+    fn path_data(&mut self) -> io::Result<PathData> {
+        (0..self.len()?).map(|_| Ok(match self.u8()? {
+            0 => PathCommand::MoveTo(self.point()?),
+            1 => PathCommand::LineTo(self.point()?),
+            2 => PathCommand::QuadTo(self.point()?, self.point()?),
+            3 => PathCommand::CubicTo(self.point()?, self.point()?, self.point()?),
+            4 => PathCommand::Close,
+            tag => return Err(invalid(format!("unknown path command {tag}"))),
+        })).collect::<io::Result<Vec<_>>>().map(PathData)
+    }
+
+    fn fill_rule(&mut self) -> io::Result<FillRule> {
+        match self.u8()? {0 => Ok(FillRule::NonZero), 1 => Ok(FillRule::EvenOdd), _ => Err(invalid("unknown fill rule"))}
+    }
+
+    fn blend_mode(&mut self) -> io::Result<BlendMode> {
+        BLEND_MODES.get(self.u8()? as usize).copied().ok_or_else(|| invalid("unknown blend mode"))
+    }
+
+    fn color_matrix(&mut self) -> io::Result<ColorMatrix> {
+        let mut matrix = [0.0; 20];
+        for v in &mut matrix {*v = self.f32()?;}
+        Ok(ColorMatrix(matrix))
+    }
+
+    fn blur(&mut self) -> io::Result<Blur> {
+        Ok(Blur{
+            radius: self.f32()?,
+            edge: match self.u8()? {
+                0 => BlurEdge::Transparent, 1 => BlurEdge::Clamp, 2 => BlurEdge::Mirror,
+                _ => return Err(invalid("unknown blur edge")),
+            },
+        })
+    }
+
+    fn path(&mut self) -> io::Result<Path> {
+        let data = self.path_data()?;
+        let color = self.color()?;
+        let gradient = self.option(|d| {
+            let kind = match d.u8()? {
+                0 => GradientKind::Linear(d.point()?, d.point()?),
+                1 => GradientKind::Radial(d.point()?, d.f32()?),
+                tag => return Err(invalid(format!("unknown gradient {tag}"))),
+            };
+            let stops = (0..d.len()?).map(|_| Ok((d.f32()?, d.color()?))).collect::<io::Result<Vec<_>>>()?;
+            let spread = match d.u8()? {0 => GradientSpread::Pad, 1 => GradientSpread::Reflect, 2 => GradientSpread::Repeat, _ => return Err(invalid("unknown spread"))};
+            let mut transform = [0.0; 6];
+            for v in &mut transform {*v = d.f32()?;}
+            Ok(Gradient{kind, stops, spread, transform})
+        })?;
+        let fill_rule = self.fill_rule()?;
+        let stroke = self.option(|d| Ok(Stroke{
+            color: d.color()?,
+            width: d.f32()?,
+            cap: match d.u8()? {0 => LineCap::Butt, 1 => LineCap::Round, 2 => LineCap::Square, _ => return Err(invalid("unknown line cap"))},
+            join: match d.u8()? {0 => LineJoin::Miter, 1 => LineJoin::Round, 2 => LineJoin::Bevel, _ => return Err(invalid("unknown line join"))},
+        }))?;
+        Ok(Path{data, color, gradient, fill_rule, stroke})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> Vec<Instruction> {
+        let font = Font::from_bytes(include_bytes!("../emoji.ttf")).unwrap();
+        let image = Arc::new(RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8 * 80, y as u8 * 200, 7, 255])));
+        let area = Area{
+            bounds: Some((1.0, 2.0, 300.0, 200.0)),
+            clip: Some(ClipShape{radii: [1.0, 2.0, 3.0, 4.0], angle: 5.0}),
+            transform: Some(Transform::around((10.0, 10.0)).rotate(30.0).rotate_y(20.0, 400.0)),
+            blend: BlendMode::Multiply,
+            ..Area::new((4.5, -3.0), None)
+        };
+        let mut text = Text::new("hi", font, 24.0, Color(1, 2, 3, 4), Align::Center);
+        text.width = Some(120.0);
+        text.cursor = Some(1);
+        text.angle = -15.0;
+        vec![
+            Instruction(area, Item::Shape(Shape{
+                border: Some(Border{color: Color::WHITE, width: 2.0, align: StrokeAlign::Outside}),
+                inner_shadow: Some(InnerShadow{color: Color::BLACK, offset: (1.0, 2.0), blur: 3.0, spread: 4.0}),
+                backdrop: Some(Blur{radius: 6.0, edge: BlurEdge::Clamp}),
+                ..Shape::new(ShapeType::SmoothRoundedRectangle(1.0, (40.0, 30.0), 10.0, 8.0, 0.6), Color(9, 8, 7, 6))
+            })),
+            Instruction(Area::default(), Item::Shadow(Shadow{
+                shape: ShapeType::Arc(2.0, (20.0, 20.0), 10.0, -30.0, -90.0, true), color: Color::BLACK, offset: (0.0, 4.0), blur: 8.0, spread: 1.0
+            })),
+            Instruction(Area::default(), Item::PushClip(Clip::Path(PathData::parse("M0 0 L10 0 Q10 10 0 10 Z").unwrap(), FillRule::EvenOdd))),
+            Instruction(Area::default(), Item::PushMask(Mask::Items(vec![
+                Instruction(Area::default(), Item::Shape(Shape::new(ShapeType::Ellipse(0.0, (5.0, 5.0), 0.0), Color::BLACK)))
+            ]))),
+            Instruction(Area::default(), Item::PushLayer(Layer{
+                opacity: 0.5, blend: BlendMode::Xor, blur: Some(Blur::new(3.0)), filter: Some(ColorMatrix::sepia(0.5))
+            })),
+            Instruction(Area::default(), Item::Image(Image{filter: Some(ColorMatrix::grayscale(1.0)), ..Image::new(
+                ShapeType::RoundedRectangle(0.0, (30.0, 20.0), 0.0, 4.0), image.clone(), Some(Color::WHITE)
+            )})),
+            Instruction(Area::default(), Item::PushMask(Mask::Image(Image::new(ShapeType::Rectangle(0.0, (3.0, 2.0), 0.0), image, None)))),
+            Instruction(Area::default(), Item::Text(text)),
+            Instruction(Area::default(), Item::Path(Path{
+                data: PathData::ellipse(5.0, 5.0, 5.0, 3.0),
+                color: Color::TRANSPARENT,
+                gradient: Some(Gradient{
+                    kind: GradientKind::Radial((0.5, 0.5), 0.5),
+                    stops: vec![(0.0, Color::WHITE), (1.0, Color::BLACK)],
+                    spread: GradientSpread::Reflect,
+                    transform: [2.0, 0.0, 0.0, 2.0, 1.0, 1.0],
+                }),
+                fill_rule: FillRule::NonZero,
+                stroke: Some(Stroke{color: Color::BLACK, width: 1.5, cap: LineCap::Round, join: LineJoin::Bevel}),
+            })),
+            Instruction(Area::default(), Item::PopMask),
+            Instruction(Area::default(), Item::PopLayer),
+            Instruction(Area::default(), Item::PopMask),
+            Instruction(Area::default(), Item::PopClip),
+        ]
+    }
+
+    #[test]
+    fn frames_play_back_as_recorded() {
+        let instructions = frame();
+        let mut recorder = Recorder::new(vec![]).unwrap();
+        recorder.record((640, 480), &instructions).unwrap();
+        let single = recorder.writer.len();
+        recorder.record((320, 240), &instructions).unwrap();
+        recorder.record((1, 1), &[]).unwrap();
+        let recording = recorder.finish().unwrap();
+
+        let frames = Player::new(recording.as_slice()).unwrap().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(frames, vec![
+            Frame{size: (640, 480), instructions: instructions.clone()},
+            Frame{size: (320, 240), instructions},
+            Frame{size: (1, 1), instructions: vec![]},
+        ]);
+        //The font and image are only written with the first frame using them
+        assert!(recording.len() - single < single / 2);
+    }
+
+    #[test]
+    fn reloaded_images_are_written_once() {
+        let pixels = RgbaImage::from_pixel(8, 8, image::Rgba([1, 2, 3, 4]));
+        let shape = ShapeType::Rectangle(0.0, (8.0, 8.0), 0.0);
+        let mut recorder = Recorder::new(vec![]).unwrap();
+        for _ in 0..3 {
+            let image = Image::new(shape, Arc::new(pixels.clone()), None);
+            recorder.record((8, 8), &[Instruction(Area::default(), Item::Image(image))]).unwrap();
+        }
+        assert_eq!(recorder.image_pixels.len(), 1);
+        //The images dropped before the last were forgotten
+        assert_eq!(recorder.images.len(), 1);
+        let recording = recorder.finish().unwrap();
+        let mut player = Player::new(recording.as_slice()).unwrap();
+        for _ in 0..3 {
+            assert!(player.next_frame().unwrap().is_some());
+        }
+        assert_eq!(player.images.len(), 1);
+        assert!(player.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn foreign_and_broken_recordings_are_rejected() {
+        let error = |bytes: &[u8]| Player::new(bytes).and_then(|mut p| p.next_frame()).unwrap_err().kind();
+        assert_eq!(error(b"RIFF\x01"), io::ErrorKind::InvalidData);
+        assert_eq!(error(b"WGCR\x00"), io::ErrorKind::InvalidData);
+        assert_eq!(error(b"WGCR"), io::ErrorKind::UnexpectedEof);
+        assert_eq!(error(b"WGCR\x01\x07"), io::ErrorKind::InvalidData);
+
+        let mut recorder = Recorder::new(vec![]).unwrap();
+        recorder.record((2, 2), &frame()).unwrap();
+        let recording = recorder.finish().unwrap();
+        assert_eq!(error(&recording[..recording.len() - 1]), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn masks_nested_too_deep_are_rejected() {
+        let nested = |depth: usize| (0..depth).fold(vec![], |items, _| vec![Instruction(Area::default(), Item::PushMask(Mask::Items(items)))]);
+        let mut recorder = Recorder::new(vec![]).unwrap();
+        recorder.record((2, 2), &nested(MAX_NESTING)).unwrap();
+        recorder.record((2, 2), &nested(MAX_NESTING + 1)).unwrap();
+        let recording = recorder.finish().unwrap();
+        let mut player = Player::new(recording.as_slice()).unwrap();
+        assert_eq!(player.next_frame().unwrap().unwrap().instructions, nested(MAX_NESTING));
+        assert_eq!(player.next_frame().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,6 @@
-use wgpu::{DepthStencilState, MultisampleState, TextureFormat, RenderPass, Device, Queue};
+use wgpu::{DepthStencilState, MultisampleState, TextureFormat, RenderPass, Device, Queue, ShaderModule, ShaderModuleDescriptor, ShaderSource, StencilState, StencilFaceState, StencilOperation, CompareFunction};
+
+use std::ops::Range;
 
 mod buffer;
 mod vertex;
@@ -6,27 +8,124 @@ mod color;
 use color::ColorRenderer;
 mod image;
 use image::ImageRenderer;
+mod path;
+mod blur;
+mod filter;
+mod layer;
+use layer::{LayerRenderer, Group, extent, overlap};
 mod atlas;
 pub use atlas::Atlas;
+mod post;
+pub use post::{Effect, PostRenderer};
+
+use crate::{Instruction, Item, Area, Layer, BlendMode};
+
+/// Most clips that can be nested, the stencil having eight bits.
+const MAX_CLIPS: usize = u8::MAX as usize;
+
+/// Creates a shader module from `source` with the signed distance helpers in `renderer/sdf.wgsl` prepended.
+fn sdf_shader(device: &Device, label: &str, source: &str) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor{
+        label: Some(label),
+        source: ShaderSource::Wgsl([include_str!("renderer/sdf.wgsl"), source].concat().into())
+    })
+}
+
+/// Stencil state passing where the reference `compare`s with the clip level, applying `op`.
+fn stencil(compare: CompareFunction, op: StencilOperation) -> StencilState {
+    let face = StencilFaceState{compare, fail_op: StencilOperation::Keep, depth_fail_op: StencilOperation::Keep, pass_op: op};
+    StencilState{front: face, back: face, read_mask: 0xff, write_mask: 0xff}
+}
+
+/// The first z index of every run of draws between two clips, splitting the draws of each
+/// pipeline into those before and after every push and pop.
+pub(crate) struct Segments(Vec<u16>);
+
+impl Segments {
+    /// The segment drawing the item with z index `z`.
+    pub(crate) fn of(&self, z: u16) -> usize {
+        self.0.partition_point(|start| *start <= z) - 1
+    }
+
+    /// The range of indices each segment draws, from the z index and index range of every
+    /// draw of a pipeline in order.
+    pub(crate) fn split(&self, draws: impl IntoIterator<Item = (u16, Range<u32>)>) -> Vec<Range<u32>> {
+        let mut ranges = vec![0..0; self.0.len()];
+        for (z, draw) in draws {
+            let segment = self.of(z);
+            ranges[segment] = match ranges[segment].is_empty() {
+                true => draw,
+                false => ranges[segment].start..draw.end,
+            };
+        }
+        ranges
+    }
+}
 
-use crate::{Instruction, Item, Area};
+/// The instructions a mask or a layer applies to, taken from `items` up to the matching
+/// `PopMask` or `PopLayer` or the end.
+fn grouped(items: &mut impl Iterator<Item = (usize, Instruction)>, mask: bool) -> Vec<Instruction> {
+    let mut depth = 0;
+    let mut grouped = vec![];
+    for (_, instruction) in items {
+        match (&instruction.1, mask) {
+            (Item::PushMask(_), true) | (Item::PushLayer(_), false) => depth += 1,
+            (Item::PopMask, true) | (Item::PopLayer, false) if depth == 0 => break,
+            (Item::PopMask, true) | (Item::PopLayer, false) => depth -= 1,
+            _ => {}
+        }
+        grouped.push(instruction);
+    }
+    grouped
+}
+
+/// Starts a segment at `z` with the clip level of the last one, so what it draws lands over
+/// every item before `z` in every pipeline.
+fn split(starts: &mut Vec<u16>, segments: &mut Vec<(u32, Option<Stencil>)>, z: u16) {
+    if starts.last() != Some(&z) {
+        segments.push((segments.last().map_or(0, |s| s.0), None));
+        starts.push(z);
+    }
+}
+
+/// A clip written into the stencil after a segment, by its index among the clips of the frame.
+enum Stencil {
+    Push(usize),
+    Pop(usize),
+}
 
 pub struct Renderer {
     color_renderer: ColorRenderer,
     image_renderer: ImageRenderer,
+    layer_renderer: LayerRenderer,
+    clipping: bool,
+    /// The clip level of every segment and the clip pushed or popped after it.
+    segments: Vec<(u32, Option<Stencil>)>,
 }
 
 impl Renderer {
     /// Create all unchanging resources here.
+    ///
+    /// Clips need `depth_stencil` to have a stencil aspect, like `Depth24PlusStencil8`, cleared
+    /// to zero before every render; the renderer sets the stencil state itself. Without one,
+    /// [`Item::PushClip`] and [`Item::PopClip`] are ignored.
     pub fn new(
         device: &Device,
         texture_format: &TextureFormat,
         multisample: MultisampleState,
         depth_stencil: Option<DepthStencilState>,
     ) -> Self {
+        let clipping = depth_stencil.as_ref().is_some_and(|d| d.format.has_stencil_aspect());
+        let depth_stencil = depth_stencil.map(|d| match clipping {
+            true => DepthStencilState{stencil: stencil(CompareFunction::Equal, StencilOperation::Keep), ..d},
+            false => d,
+        });
         Renderer{
-            color_renderer: ColorRenderer::new(device, texture_format, multisample, depth_stencil.clone()),
+            color_renderer: ColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), clipping),
             image_renderer: ImageRenderer::new(device, texture_format, multisample, depth_stencil.clone()),
+            layer_renderer: LayerRenderer::new(device, texture_format, multisample, depth_stencil.clone()),
+            clipping,
+            segments: vec![],
         }
     }
 
@@ -35,6 +134,21 @@ impl Renderer {
     ///
     /// Items are given a z_index based on the order in which they are presented. First item in the
     /// vector will be printed in the back of the stack(z = u16::MAX-1)
+    ///
+    /// The items of every [`Item::PushMask`] and [`Item::PushLayer`], and the mask, are drawn
+    /// into textures of their own here, submitted to `queue` before returning, and shown as one
+    /// item at the push.
+    ///
+    /// Items blending other than [`BlendMode::Normal`] are drawn the same way, one layer for
+    /// every run of them sharing a mode without overlapping, and the frame is split around it so
+    /// it blends with every item before it and under every item after it. The modes reading back
+    /// what is under them draw the items before them again, within the frame or layer.
+    ///
+    /// Runs of [`Item::Shadow`]s split the frame around them the same way, as they do not write
+    /// depth.
+    ///
+    /// Shapes with a [`backdrop`](crate::Shape::backdrop) draw the items before them again too, blur them and
+    /// show them in place of what is within the shape, just before its fill, without splitting the render pass.
     #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         &mut self,
@@ -45,26 +159,216 @@ impl Renderer {
         atlas: &mut Atlas,
         items: Vec<Instruction>,
     ) {
-        let (colors, images) = items.into_iter().enumerate().fold((vec![], vec![]), |mut a, (i, Instruction(area, item))| {
+        let mut starts = vec![0];
+        let mut segments = vec![(0, None)];
+        //Pushes past the limit or without a stencil hold `None`, so their pops are skipped too
+        let mut stack: Vec<Option<usize>> = vec![];
+        let mut clips = vec![];
+        let (mut colors, mut images, mut shadows, mut paths) = (vec![], vec![], vec![], vec![]);
+        let mut groups: Vec<(Area, Group, Vec<Instruction>, u16)> = vec![];
+        let frame = match items.iter().any(|Instruction(area, item)| match item {
+            Item::PushLayer(layer) => layer.blend.reads_back(),
+            Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PopLayer => false,
+            Item::Shape(shape) if shape.backdrop.is_some() => true,
+            _ => area.blend.reads_back(),
+        }) {
+            true => items.clone(),
+            false => vec![],
+        };
+        let under = |i: usize, blend: BlendMode| match blend.reads_back() {
+            true => frame[..i].to_vec(),
+            false => vec![],
+        };
+        //The extents of the run of blended items in the last group, ending at anything else
+        let mut blended: Option<(BlendMode, Vec<_>)> = None;
+        //Whether the last item was a shadow, drawn in a segment of its own
+        let mut shadowed = false;
+        let mut items = items.into_iter().enumerate();
+        while let Some((i, Instruction(area, item))) = items.next() {
             let z = i as u16;
+            //Shadows cover most of their quad translucently, so they land over every item before them
+            let shadow = matches!(item, Item::Shadow(_));
+            if shadow != shadowed {split(&mut starts, &mut segments, z);}
+            shadowed = shadow;
+            let blend = match item {
+                Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => BlendMode::Normal,
+                _ => area.blend,
+            };
+            if blend != BlendMode::Normal {
+                let instruction = Instruction(Area{blend: BlendMode::Normal, ..area}, item);
+                let extent = extent(&instruction);
+                match &mut blended {
+                    Some((mode, extents)) if *mode == blend && extent.is_none_or(|e| extents.iter().all(|b| !overlap(e, *b))) => {
+                        extents.extend(extent);
+                        groups.last_mut().unwrap().2.push(instruction);
+                    },
+                    _ => {
+                        split(&mut starts, &mut segments, z);
+                        groups.push((Area::default(), Group::Layer(Layer{blend, ..Layer::default()}, under(i, blend)), vec![instruction], z));
+                        blended = Some((blend, extent.into_iter().collect()));
+                    }
+                }
+                continue;
+            }
+            if blended.take().is_some() {split(&mut starts, &mut segments, z);}
             match item {
-                Item::Shape(shape) => a.0.push((area, shape.shape, shape.color, z)),
-                Item::Image(image) => a.1.push((area, image.shape, image.image, image.color, z)),
-                Item::Text(text) => a.1.extend(atlas.text.get(text).into_iter().map(|(offset, shape, image, color)| (
-                    Area{offset: (area.offset.0+(offset.0), area.offset.1+(offset.1)), bounds: area.bounds},
-                    shape, image, color, z
-                )))
+                Item::PushClip(clip) => {
+                    let level = stack.iter().flatten().count();
+                    if !self.clipping || level >= MAX_CLIPS {
+                        stack.push(None);
+                        continue;
+                    }
+                    segments.last_mut().unwrap().1 = Some(Stencil::Push(clips.len()));
+                    segments.push((level as u32 + 1, None));
+                    starts.push(z);
+                    stack.push(Some(clips.len()));
+                    clips.push((area, clip));
+                },
+                Item::PopClip => if let Some(clip) = stack.pop().flatten() {
+                    segments.last_mut().unwrap().1 = Some(Stencil::Pop(clip));
+                    segments.push((stack.iter().flatten().count() as u32, None));
+                    starts.push(z);
+                },
+                Item::PushMask(mask) => groups.push((area, Group::Mask(mask), grouped(&mut items, true), z)),
+                Item::PushLayer(layer) if layer.blend == BlendMode::Normal => groups.push((area, Group::Layer(layer, vec![]), grouped(&mut items, false), z)),
+                Item::PushLayer(layer) => {
+                    //Blended layers split the frame like blended items, but take none of them in
+                    split(&mut starts, &mut segments, z);
+                    groups.push((area, Group::Layer(layer, under(i, layer.blend)), grouped(&mut items, false), z));
+                    blended = Some((BlendMode::Normal, vec![]));
+                },
+                Item::PopMask | Item::PopLayer => {},
+                Item::Shape(shape) => {
+                    //The blurred backdrop is composited at the end of the segment before the shape
+                    if i > 0 && shape.backdrop.is_some_and(|b| b.radius > 0.0) {
+                        split(&mut starts, &mut segments, z);
+                        groups.push((area, Group::Backdrop(shape), frame[..i].to_vec(), z));
+                    }
+                    colors.push((area, shape, z))
+                },
+                Item::Image(image) => images.push((area, image.shape, image.image, image.color, image.filter, z)),
+                Item::Text(text) => {
+                    let area = text.rotation(area.offset).map_or(area, |m| area.within(m));
+                    images.extend(atlas.text.get(text).into_iter().map(|(offset, shape, image, color)| (
+                        area.inner(offset),
+                        shape, image, color, None, z
+                    )))
+                },
+                Item::Shadow(shadow) => shadows.push((area, shadow, z)),
+                Item::Path(path) => paths.push((area, path, z)),
             }
-            a
-        });
+        }
 
-        self.color_renderer.prepare(device, queue, width, height, colors);
-        self.image_renderer.prepare(device, queue, width, height, &mut atlas.image, images);
+        let starts = Segments(starts);
+        self.color_renderer.prepare(device, queue, width, height, &starts, colors, shadows, paths, clips);
+        self.image_renderer.prepare(device, queue, width, height, &starts, &mut atlas.image, images);
+        self.layer_renderer.prepare(device, queue, width, height, &starts, atlas, groups);
+        self.segments = segments;
     }
 
     /// Render using caller provided render pass.
+    ///
+    /// Every clip is written into the stencil between the draws before and after it, so the
+    /// items up to its pop only show where the stencil holds their clip level.
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
-        self.color_renderer.render(render_pass);
-        self.image_renderer.render(render_pass);
+        for (segment, (level, clip)) in self.segments.iter().enumerate() {
+            if self.clipping {render_pass.set_stencil_reference(*level);}
+            self.color_renderer.render(render_pass, segment);
+            self.image_renderer.render(render_pass, segment);
+            self.layer_renderer.render(render_pass, segment);
+            match clip {
+                Some(Stencil::Push(clip)) => self.color_renderer.render_clip(render_pass, *clip, true),
+                Some(Stencil::Pop(clip)) => {
+                    render_pass.set_stencil_reference(level - 1);
+                    self.color_renderer.render_clip(render_pass, *clip, false);
+                },
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod offscreen;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{RgbaImage, Shape, Shadow, Color, ShapeType, Layer, Image, Mask, Blur};
+
+    /// Renders `items` offscreen like the replay tool does, `None` without an adapter.
+    fn render(width: u32, height: u32, items: Vec<Instruction>) -> Option<RgbaImage> {
+        //Adapters of some drivers do not like being used from several threads at once
+        static LOCK: Mutex<()> = Mutex::new(());
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let (device, queue) = offscreen::device().inspect_err(|e| eprintln!("skipped, no adapter: {e}")).ok()?;
+        let mut renderer = offscreen::renderer(&device);
+        Some(offscreen::render(&device, &queue, &mut renderer, &mut Atlas::default(), (width, height), items))
+    }
+
+    #[test]
+    fn shadows_keep_an_opaque_background_opaque() {
+        let shape = ShapeType::RoundedRectangle(0.0, (40.0, 40.0), 0.0, 8.0);
+        let Some(frame) = render(64, 64, vec![
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(ShapeType::Rectangle(0.0, (64.0, 64.0), 0.0), Color::WHITE))),
+            Instruction(Area::new((12.0, 12.0), None), Item::Shadow(Shadow{shape, color: Color::BLACK, offset: (0.0, 4.0), blur: 16.0, spread: 0.0})),
+            Instruction(Area::new((12.0, 12.0), None), Item::Shape(Shape::new(shape, Color::BLUE))),
+        ]) else {return};
+        for (x, y) in [(0, 0), (5, 5), (10, 32), (32, 56), (63, 63)] {
+            assert_eq!(frame.get_pixel(x, y).0[3], 255, "alpha at ({x}, {y})");
+        }
+        for (x, y) in [(10, 32), (32, 56)] {
+            assert!(frame.get_pixel(x, y).0[0] < 240, "no shadow at ({x}, {y})");
+        }
+        assert_eq!(frame.get_pixel(32, 32).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn translucent_layers_cover_by_their_opacity() {
+        let square = ShapeType::Rectangle(0.0, (64.0, 64.0), 0.0);
+        let Some(frame) = render(64, 64, vec![
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(square, Color::WHITE))),
+            Instruction(Area::new((0.0, 0.0), None), Item::PushLayer(Layer::new(0.5))),
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(square, Color::RED))),
+            Instruction(Area::new((0.0, 0.0), None), Item::PopLayer),
+        ]) else {return};
+        //Half of white over red in linear light is 188 in sRGB, covering twice gives 224
+        let [r, g, b, a] = frame.get_pixel(32, 32).0;
+        assert_eq!((r, a), (255, 255));
+        assert!(g.abs_diff(188) <= 3 && b.abs_diff(188) <= 3, "{:?}", [r, g, b, a]);
+    }
+
+    #[test]
+    fn image_masks_cover_by_their_alpha() {
+        let square = ShapeType::Rectangle(0.0, (100.0, 100.0), 0.0);
+        let gradient = RgbaImage::from_fn(100, 100, |_, y| ::image::Rgba([255, 255, 255, (y * 255 / 99) as u8]));
+        let Some(frame) = render(100, 100, vec![
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(square, Color::BLACK))),
+            Instruction(Area::new((0.0, 0.0), None), Item::PushMask(Mask::Image(Image::new(square, Arc::new(gradient), None)))),
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(square, Color::WHITE))),
+            Instruction(Area::new((0.0, 0.0), None), Item::PopMask),
+        ]) else {return};
+        //Half of white over black in linear light is 188 in sRGB
+        let [r, g, b, a] = frame.get_pixel(50, 50).0;
+        assert_eq!(a, 255);
+        assert!([r, g, b].iter().all(|c| c.abs_diff(188) <= 4), "{:?}", [r, g, b, a]);
+    }
+
+    #[test]
+    fn backdrops_replace_what_is_under_them() {
+        let frosted = Shape{backdrop: Some(Blur::new(20.0)), ..Shape::new(ShapeType::Rectangle(0.0, (50.0, 64.0), 0.0), Color(0, 0, 0, 0))};
+        let Some(frame) = render(100, 64, vec![
+            Instruction(Area::new((0.0, 0.0), None), Item::Shape(Shape::new(ShapeType::Rectangle(0.0, (50.0, 64.0), 0.0), Color::WHITE))),
+            Instruction(Area::new((25.0, 0.0), None), Item::Shape(frosted)),
+        ]) else {return};
+        //White falling off by a gaussian of sigma 10 from x = 50, premultiplied over transparency
+        for (x, expected) in [(20, 255), (40, 235), (45, 214), (50, 184)] {
+            let pixel = frame.get_pixel(x, 32).0;
+            assert!(pixel[0].abs_diff(expected) <= 4, "{pixel:?} at {x}");
+        }
+        let (left, right) = (frame.get_pixel(49, 32).0[0], frame.get_pixel(50, 32).0[0]);
+        assert!(left.abs_diff(right) <= 8, "sharp edge {left} to {right}");
     }
 }
@@ -12,7 +12,9 @@ pub struct Atlas {
     pub(crate) text: TextAtlas,
 }
 impl Atlas {
-    pub(crate) fn trim(&mut self) {
+    /// Drops the textures of images and text no longer used outside the atlas, as
+    /// [`Canvas`](crate::Canvas) does before every frame.
+    pub fn trim(&mut self) {
         self.image.trim();
         self.text.trim();
     }
@@ -0,0 +1,223 @@
+use wgpu::{PipelineCompilationOptions, BindGroupLayoutDescriptor, RenderPipelineDescriptor, PipelineLayoutDescriptor, TextureViewDimension, BindGroupLayoutEntry, TextureSampleType, MultisampleState, BindGroupLayout, RenderPipeline, PrimitiveState, FragmentState, TextureFormat, ShaderStages, BufferUsages, IndexFormat, VertexState, BindingType, Device, Queue, BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, ColorTargetState, ColorWrites, TextureDescriptor, TextureDimension, TextureUsages, TextureView, TextureViewDescriptor, Extent3d, CommandEncoderDescriptor, RenderPassDescriptor, RenderPassColorAttachment, Operations, LoadOp, StoreOp, Sampler, SamplerBindingType, AddressMode, ShaderModuleDescriptor, ShaderSource};
+
+use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use crate::{Blur, BlurEdge};
+
+use super::vertex::{Vertex, BlurVertex};
+
+/// Most texels of sigma blurred at the size a layer is downsampled to.
+const MAX_SIGMA: f32 = 4.0;
+/// Most texels a side downsampled into one.
+const MAX_FACTOR: u32 = 16;
+
+/// Blurs layers with a separable gaussian, at a fraction of their size when the blur is wide.
+pub struct BlurRenderer {
+    texture_format: TextureFormat,
+    bind_group_layout: BindGroupLayout,
+    /// Clamping and mirroring at the edges, transparent edges being clamped padding.
+    samplers: [Sampler; 2],
+    downsample_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+}
+
+impl BlurRenderer {
+    /// Create all unchanging resources here.
+    pub fn new(device: &Device, texture_format: &TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float{filterable: true},
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let samplers = [AddressMode::ClampToEdge, AddressMode::MirrorRepeat].map(|mode| device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: mode,
+            address_mode_v: mode,
+            address_mode_w: mode,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }));
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor{
+            label: Some("blur.wgsl"),
+            source: ShaderSource::Wgsl(include_str!("blur.wgsl").into())
+        });
+        let pipeline = |entry_point| device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[BlurVertex::layout()]
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format: *texture_format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })]
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None
+        });
+
+        let vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+
+        BlurRenderer{
+            texture_format: *texture_format,
+            bind_group_layout,
+            samplers,
+            downsample_pipeline: pipeline("fs_downsample"),
+            blur_pipeline: pipeline("fs_blur"),
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    /// Downsamples `blurred.source` and blurs it horizontally then vertically, submitting the
+    /// passes to `queue` right away. The result is in [`Blurred::view`].
+    pub fn blur(&mut self, device: &Device, queue: &Queue, blurred: &Blurred, blur: Blur) {
+        let (w, h) = blurred.size;
+        let (sw, sh) = blurred.source;
+        let factor = blurred.factor as f32;
+        //The box averaged when downsampling blurs by a part of the sigma already
+        let sigma = blur.radius.max(0.0) / 2.0;
+        let sigma = (sigma * sigma - (factor * factor - 1.0) / 12.0).max(0.0).sqrt() / factor;
+        let vertices = [
+            BlurVertex::new([1.0 / sw as f32, 1.0 / sh as f32], [0.0, factor]),
+            BlurVertex::new([1.0 / w as f32, 0.0], [sigma, 0.0]),
+            BlurVertex::new([0.0, 1.0 / h as f32], [sigma, 0.0]),
+        ].concat();
+        let indices = (0..3u16).flat_map(|i| [0, 1, 2, 1, 2, 3].map(|v| i * 4 + v)).collect::<Vec<_>>();
+        self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&vertices));
+        self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        for (pass, target) in [0, 1, 0].into_iter().enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Blur Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &blurred.views[target],
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(match pass {
+                0 => &self.downsample_pipeline,
+                _ => &self.blur_pipeline,
+            });
+            render_pass.set_bind_group(0, Some(&blurred.bind_groups[pass]), &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
+            render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+            let start = pass as u32 * 6;
+            render_pass.draw_indexed(start..start+6, 0, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// The textures a layer of `source` pixels is blurred through, downsampled by `factor`.
+pub struct Blurred {
+    source: (u32, u32),
+    factor: u32,
+    edge: BlurEdge,
+    size: (u32, u32),
+    views: [TextureView; 2],
+    /// Sampling the layer, then every view in turn.
+    bind_groups: [BindGroup; 3],
+}
+
+impl Blurred {
+    /// The textures to blur `source`, a layer of `size` pixels, by `blur` through.
+    pub fn new(device: &Device, renderer: &BlurRenderer, source: &TextureView, size: (u32, u32), blur: Blur) -> Self {
+        let factor = factor(blur);
+        let scaled = (size.0.div_ceil(factor), size.1.div_ceil(factor));
+        let views = [(); 2].map(|_| device.create_texture(&TextureDescriptor{
+            label: Some("Blur texture"),
+            size: Extent3d{width: scaled.0, height: scaled.1, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: renderer.texture_format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&TextureViewDescriptor::default()));
+        let sampler = match blur.edge {
+            BlurEdge::Transparent | BlurEdge::Clamp => &renderer.samplers[0],
+            BlurEdge::Mirror => &renderer.samplers[1],
+        };
+        let bind_groups = [source, &views[0], &views[1]].map(|view| device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &renderer.bind_group_layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: BindingResource::TextureView(view)},
+                BindGroupEntry{binding: 1, resource: BindingResource::Sampler(sampler)},
+            ]
+        }));
+        Blurred{source: size, factor, edge: blur.edge, size: scaled, views, bind_groups}
+    }
+
+    /// Whether these textures blur a layer of `size` pixels by `blur`.
+    pub fn fits(&self, size: (u32, u32), blur: Blur) -> bool {
+        self.source == size && self.factor == factor(blur) && self.edge == blur.edge
+    }
+
+    /// The blurred layer, once [`BlurRenderer::blur`] ran.
+    pub fn view(&self) -> &TextureView {&self.views[0]}
+}
+
+/// The texels a side downsampled into one for `blur`, the smallest power of two keeping the
+/// sigma left within [`MAX_SIGMA`].
+fn factor(blur: Blur) -> u32 {
+    let sigma = blur.radius.max(0.0) / 2.0;
+    let mut factor = 1;
+    while factor < MAX_FACTOR && sigma / factor as f32 > MAX_SIGMA {factor *= 2;}
+    factor
+}
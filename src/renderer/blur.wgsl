@@ -0,0 +1,63 @@
+struct BlurInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) step: vec2<f32>,
+    @location(3) paint: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) step: vec2<f32>,
+    @location(2) @interpolate(flat) paint: vec2<f32>
+};
+
+@vertex
+fn vs_main(
+    blur: BlurInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(blur.position, 0.0, 1.0);
+    out.uv = blur.uv;
+    out.step = blur.step;
+    out.paint = blur.paint;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+//Averages the box of `paint.y` source texels a side under the texel, two by two through
+//filtered samples at the center of every four.
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half = in.paint.y / 2.0;
+    let taps = max(i32(half), 1);
+    let first = select(1.0 - half, 0.0, half < 1.0);
+    var sum = vec4<f32>(0.0);
+    for (var y = 0; y < taps; y++) {
+        for (var x = 0; x < taps; x++) {
+            let offset = vec2<f32>(first + 2.0 * f32(x), first + 2.0 * f32(y));
+            sum += textureSampleLevel(t_source, s_source, in.uv + offset * in.step, 0.0);
+        }
+    }
+    return sum / f32(taps * taps);
+}
+
+//One direction of a separable gaussian of `paint.x` texels, out to three sigma.
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sigma = max(in.paint.x, 0.0001);
+    let radius = i32(ceil(sigma * 3.0));
+    var sum = textureSampleLevel(t_source, s_source, in.uv, 0.0);
+    var total = 1.0;
+    for (var i = 1; i <= radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        let offset = f32(i) * in.step;
+        sum += (textureSampleLevel(t_source, s_source, in.uv + offset, 0.0) + textureSampleLevel(t_source, s_source, in.uv - offset, 0.0)) * weight;
+        total += 2.0 * weight;
+    }
+    return sum / total;
+}
@@ -1,14 +1,22 @@
 use wgpu::{PipelineCompilationOptions, RenderPipelineDescriptor, PipelineLayoutDescriptor, DepthStencilState, MultisampleState, RenderPipeline, PrimitiveState, FragmentState, TextureFormat, BufferUsages, IndexFormat, VertexState, RenderPass, Device, Queue, VertexBufferLayout, ShaderModule};
 use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
 
-use crate::{Area, Color};
+use std::ops::Range;
+
+use crate::{Area, Shadow, Path, Clip};
 use crate::shape::Shape;
-use super::vertex::{Vertex, ShapeVertex, RoundedRectangleVertex, ColorVertex};
+use super::vertex::{Vertex, ShapeVertex, RoundedRectangleVertex, ArcVertex, PolygonVertex, ShadowVertex, ColorVertex, FillVertex};
+use super::path::PathRenderer;
+use super::{sdf_shader, Segments};
 
 pub struct ColorRenderer {
+    shadow_renderer: GenericColorRenderer,
     ellipse_renderer: GenericColorRenderer,
     rectangle_renderer: GenericColorRenderer,
     rounded_rectangle_renderer: GenericColorRenderer,
+    arc_renderer: GenericColorRenderer,
+    polygon_renderer: GenericColorRenderer,
+    path_renderer: PathRenderer,
 }
 
 impl ColorRenderer {
@@ -18,54 +26,100 @@ impl ColorRenderer {
         texture_format: &TextureFormat,
         multisample: MultisampleState,
         depth_stencil: Option<DepthStencilState>,
+        clipping: bool,
     ) -> Self {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("color/ellipse.wgsl"));
-        let ellipse_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, ColorVertex::<ShapeVertex>::layout());
-        let shader = device.create_shader_module(wgpu::include_wgsl!("color/rectangle.wgsl"));
-        let rectangle_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, ColorVertex::<ShapeVertex>::layout());
-        let shader = device.create_shader_module(wgpu::include_wgsl!("color/rounded_rectangle.wgsl"));
-        let rounded_rectangle_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, ColorVertex::<RoundedRectangleVertex>::layout());
+        let shader = sdf_shader(device, "color/shadow.wgsl", include_str!("color/shadow.wgsl"));
+        //Shadows are drawn in segments of their own, so their translucent quads do not hide what is under them
+        let shadow_depth = depth_stencil.clone().map(|d| DepthStencilState{depth_write_enabled: false, ..d});
+        let shadow_renderer = GenericColorRenderer::new(device, texture_format, multisample, shadow_depth, shader, ColorVertex::<ShadowVertex>::layout());
+        let shader = sdf_shader(device, "color/ellipse.wgsl", include_str!("color/ellipse.wgsl"));
+        let ellipse_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, FillVertex::<ShapeVertex>::layout());
+        let shader = sdf_shader(device, "color/rectangle.wgsl", include_str!("color/rectangle.wgsl"));
+        let rectangle_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, FillVertex::<ShapeVertex>::layout());
+        let shader = sdf_shader(device, "color/rounded_rectangle.wgsl", include_str!("color/rounded_rectangle.wgsl"));
+        let rounded_rectangle_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, FillVertex::<RoundedRectangleVertex>::layout());
+        let shader = sdf_shader(device, "color/arc.wgsl", include_str!("color/arc.wgsl"));
+        let arc_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, FillVertex::<ArcVertex>::layout());
+        let shader = sdf_shader(device, "color/polygon.wgsl", include_str!("color/polygon.wgsl"));
+        let polygon_renderer = GenericColorRenderer::new(device, texture_format, multisample, depth_stencil.clone(), shader, FillVertex::<PolygonVertex>::layout());
+        let path_renderer = PathRenderer::new(device, texture_format, multisample, depth_stencil.clone(), clipping);
         ColorRenderer{
+            shadow_renderer,
             ellipse_renderer,
             rectangle_renderer,
-            rounded_rectangle_renderer
+            rounded_rectangle_renderer,
+            arc_renderer,
+            polygon_renderer,
+            path_renderer
         }
     }
 
     /// Prepare for rendering this frame; create all resources that will be
     /// used during the next render that do not already exist.
+    #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         &mut self,
         device: &Device,
         queue: &Queue,
         width: f32,
         height: f32,
-        items: Vec<(Area, Shape, Color, u16)>,
+        segments: &Segments,
+        items: Vec<(Area, crate::Shape, u16)>,
+        shadows: Vec<(Area, Shadow, u16)>,
+        paths: Vec<(Area, Path, u16)>,
+        clips: Vec<(Area, Clip)>,
     ) {
-        let (ellipses, rects, rounded_rects) = items.into_iter().fold(
-            (vec![], vec![], vec![]),
-            |mut a, (area, shape, color, z)| {
+        let shadows = shadows.into_iter().map(|(area, shadow, z)|
+            (z, ColorVertex::new(ShadowVertex::new(width, height, z, area, shadow), shadow.color))
+        ).collect();
+
+        let (ellipses, rects, rounded_rects, arcs, polygons) = items.into_iter().fold(
+            (vec![], vec![], vec![], vec![], vec![]),
+            |mut a, (area, fill, z)| {
+                let shape = fill.shape;
+                let margin = fill.border.map(|b| b.outset()).unwrap_or_default();
+                let vertices = ShapeVertex::with_margin(width, height, z, area, shape, margin);
                 match shape {
                     Shape::Ellipse(_, _, _) =>
-                        a.0.push(ColorVertex::new(ShapeVertex::new(width, height, z, area, shape), color)),
+                        a.0.push((z, FillVertex::new(vertices, &fill))),
                     Shape::Rectangle(_, _, _) =>
-                        a.1.push(ColorVertex::new(ShapeVertex::new(width, height, z, area, shape), color)),
+                        a.1.push((z, FillVertex::new(vertices, &fill))),
                     Shape::RoundedRectangle(_, _, _, corner_radius) =>
-                        a.2.push(ColorVertex::new(RoundedRectangleVertex::new(width, height, z, area, shape, corner_radius), color)),
+                        a.2.push((z, FillVertex::new(RoundedRectangleVertex::new(vertices, corner_radius, 0.0), &fill))),
+                    Shape::SmoothRoundedRectangle(_, _, _, corner_radius, smoothing) =>
+                        a.2.push((z, FillVertex::new(RoundedRectangleVertex::new(vertices, corner_radius, smoothing), &fill))),
+                    Shape::Arc(..) =>
+                        a.3.push((z, FillVertex::new(ArcVertex::new(vertices, shape), &fill))),
+                    Shape::Polygon(..) | Shape::Star(..) =>
+                        a.4.push((z, FillVertex::new(PolygonVertex::new(vertices, shape), &fill))),
                 }
                 a
             }
         );
-        self.ellipse_renderer.prepare(device, queue, ellipses);
-        self.rectangle_renderer.prepare(device, queue, rects);
-        self.rounded_rectangle_renderer.prepare(device, queue, rounded_rects);
+        self.shadow_renderer.prepare(device, queue, segments, shadows);
+        self.ellipse_renderer.prepare(device, queue, segments, ellipses);
+        self.rectangle_renderer.prepare(device, queue, segments, rects);
+        self.rounded_rectangle_renderer.prepare(device, queue, segments, rounded_rects);
+        self.arc_renderer.prepare(device, queue, segments, arcs);
+        self.polygon_renderer.prepare(device, queue, segments, polygons);
+        self.path_renderer.prepare(device, queue, width, height, segments, paths);
+        self.path_renderer.prepare_clips(device, queue, width, height, clips);
+    }
+
+    /// Render the items of `segment` using caller provided render pass.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize) {
+        self.shadow_renderer.render(render_pass, segment);
+        self.ellipse_renderer.render(render_pass, segment);
+        self.rectangle_renderer.render(render_pass, segment);
+        self.rounded_rectangle_renderer.render(render_pass, segment);
+        self.arc_renderer.render(render_pass, segment);
+        self.polygon_renderer.render(render_pass, segment);
+        self.path_renderer.render(render_pass, segment);
     }
 
-    /// Render using caller provided render pass.
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
-        self.ellipse_renderer.render(render_pass);
-        self.rectangle_renderer.render(render_pass);
-        self.rounded_rectangle_renderer.render(render_pass);
+    /// Pushes or pops the clip at index `clip` in the stencil.
+    pub fn render_clip(&self, render_pass: &mut RenderPass<'_>, clip: usize, push: bool) {
+        self.path_renderer.render_clip(render_pass, clip, push);
     }
 }
 
@@ -73,7 +127,7 @@ pub struct GenericColorRenderer {
     render_pipeline: RenderPipeline,
     vertex_buffer: DynamicBuffer,
     index_buffer: DynamicBuffer,
-    indices: u32
+    segments: Vec<Range<u32>>
 }
 
 impl GenericColorRenderer {
@@ -130,7 +184,7 @@ impl GenericColorRenderer {
             render_pipeline,
             vertex_buffer,
             index_buffer,
-            indices: 0
+            segments: vec![]
         }
     }
 
@@ -140,28 +194,32 @@ impl GenericColorRenderer {
         &mut self,
         device: &Device,
         queue: &Queue,
-        vertices: Vec<[V; 4]>,
+        segments: &Segments,
+        vertices: Vec<(u16, [V; 4])>,
     ) {
 
-        let (vertices, indices) = vertices.into_iter().fold(
-            (vec![], vec![]), |mut a, vertices| {
+        let (vertices, indices, draws) = vertices.into_iter().fold(
+            (vec![], vec![], vec![]), |mut a, (z, vertices)| {
                 let l = a.0.len() as u16;
+                let start = a.1.len() as u32;
                 a.0.extend(vertices);
                 a.1.extend([l, l+1, l+2, l+1, l+2, l+3]);
+                a.2.push((z, start..a.1.len() as u32));
                 a
             }
         );
 
-        self.indices = indices.len() as u32;
+        self.segments = segments.split(draws);
         self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&vertices));
         self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
     }
 
-    /// Render using caller provided render pass.
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+    /// Render the items of `segment` using caller provided render pass.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize) {
+        let Some(indices) = self.segments.get(segment).filter(|r| !r.is_empty()) else {return};
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
         render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
-        render_pass.draw_indexed(0..self.indices, 0, 0..1);
+        render_pass.draw_indexed(indices.clone(), 0, 0..1);
     }
 }
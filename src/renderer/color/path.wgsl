@@ -0,0 +1,89 @@
+struct PathInput {
+    @location(0) position: vec3<f32>,
+    @location(1) local: vec2<f32>,
+    @location(2) bounds: vec4<f32>,
+    @location(3) clip: vec4<f32>,
+    @location(4) clip_angle: f32,
+    @location(5) z: f32,
+    @location(6) paint: vec4<f32>,
+    @location(7) geometry: vec4<f32>,
+    @location(8) inverse_x: vec3<f32>,
+    @location(9) inverse_y: vec3<f32>,
+    @location(10) color: vec4<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) bounds: vec4<f32>,
+    @location(1) @interpolate(flat) paint: vec4<f32>,
+    @location(2) @interpolate(flat) geometry: vec4<f32>,
+    @location(3) @interpolate(flat) inverse_x: vec3<f32>,
+    @location(4) @interpolate(flat) inverse_y: vec3<f32>,
+    @location(5) @interpolate(flat) color: vec4<f32>,
+    @location(6) local: vec2<f32>,
+    @location(7) @interpolate(flat) clip: vec4<f32>,
+    @location(8) @interpolate(flat) clip_angle: f32
+};
+
+const LINEAR: f32 = 1.0;
+const RADIAL: f32 = 2.0;
+const REFLECT: f32 = 1.0;
+const REPEAT: f32 = 2.0;
+const RAMP_WIDTH: f32 = 256.0;
+
+@vertex
+fn vs_main(
+    path: PathInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(path.position.xy, path.z * path.position.z, path.position.z);
+    out.local = path.local;
+    out.bounds = path.bounds;
+    out.clip = path.clip;
+    out.clip_angle = path.clip_angle;
+    out.paint = path.paint;
+    out.geometry = path.geometry;
+    out.inverse_x = path.inverse_x;
+    out.inverse_y = path.inverse_y;
+    out.color = path.color;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_ramps: texture_2d<f32>;
+@group(0) @binding(1)
+var s_ramps: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
+        discard;
+    }
+    if in.paint[0] < LINEAR {
+        return apply_clip(in.color, clipped);
+    }
+
+    let p = vec3<f32>(in.local, 1.0);
+    let g = vec2<f32>(dot(in.inverse_x, p), dot(in.inverse_y, p));
+    var t = 0.0;
+    if in.paint[0] == RADIAL {
+        t = length(g - in.geometry.xy) / max(in.geometry[2], 0.0001);
+    } else {
+        let d = in.geometry.zw - in.geometry.xy;
+        t = dot(g - in.geometry.xy, d) / max(dot(d, d), 0.0001);
+    }
+
+    if in.paint[2] == REPEAT {
+        t = fract(t);
+    } else if in.paint[2] == REFLECT {
+        t = 1.0 - abs(t - 2.0 * floor(t / 2.0) - 1.0);
+    } else {
+        t = clamp(t, 0.0, 1.0);
+    }
+
+    //Sample between the centers of the first and last texel
+    let u = (t * (RAMP_WIDTH - 1.0) + 0.5) / RAMP_WIDTH;
+    let color = textureSampleLevel(t_ramps, s_ramps, vec2<f32>(u, in.paint[1]), 0.0) * in.color;
+    return apply_clip(color, clipped);
+}
@@ -1,11 +1,17 @@
 struct ShapeInput {
     @location(0) uv: vec2<f32>,
-    @location(1) position: vec2<f32>,
+    @location(1) position: vec3<f32>,
     @location(2) size: vec2<f32>,
     @location(3) bounds: vec4<f32>,
-    @location(4) z: f32,
-    @location(5) stroke: f32,
-    @location(6) color: vec4<f32>
+    @location(4) clip: vec4<f32>,
+    @location(5) clip_angle: f32,
+    @location(6) z: f32,
+    @location(7) stroke: f32,
+    @location(8) color: vec4<f32>,
+    @location(9) border_color: vec4<f32>,
+    @location(10) border: vec2<f32>,
+    @location(11) inner_shadow_color: vec4<f32>,
+    @location(12) inner_shadow: vec4<f32>
 }
 
 struct VertexOutput {
@@ -14,8 +20,13 @@ struct VertexOutput {
     @location(1) @interpolate(flat) size: vec2<f32>,
     @location(2) @interpolate(flat) bounds: vec4<f32>,
     @location(3) @interpolate(flat) stroke: f32,
-    @location(4) @interpolate(flat) color: vec4<f32>,
-    @location(5) vertex_position: vec2<f32>
+    @location(4) @interpolate(flat) color: vec2<u32>,
+    @location(5) @interpolate(flat) border_color: vec2<u32>,
+    @location(6) @interpolate(flat) border: vec2<f32>,
+    @location(7) @interpolate(flat) inner_shadow_color: vec2<u32>,
+    @location(8) @interpolate(flat) inner_shadow: vec4<f32>,
+    @location(9) @interpolate(flat) clip: vec4<f32>,
+    @location(10) @interpolate(flat) clip_angle: f32
 };
 
 @vertex
@@ -23,31 +34,41 @@ fn vs_main(
     shape: ShapeInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.position = vec4<f32>(shape.position, shape.z, 1.0);
+    out.position = vec4<f32>(shape.position.xy, shape.z * shape.position.z, shape.position.z);
     out.uv = shape.uv;
 
     out.size = shape.size;
 
     out.bounds = shape.bounds;
+    out.clip = shape.clip;
+    out.clip_angle = shape.clip_angle;
     out.stroke = shape.stroke;
-    out.color = shape.color;
-	out.vertex_position = shape.position;
+    out.color = pack_color(shape.color);
+    out.border_color = pack_color(shape.border_color);
+    out.border = shape.border;
+    out.inner_shadow_color = pack_color(shape.inner_shadow_color);
+    out.inner_shadow = shape.inner_shadow;
 
     return out;
 }
 
+fn sd(p: vec2<f32>, in: VertexOutput) -> f32 {
+    return sd_box(p, in.size / 2.0, 0.0);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    if in.vertex_position.x < in.bounds[0] || in.vertex_position.x > in.bounds[2] ||
-       in.vertex_position.y > in.bounds[1] || in.vertex_position.y < in.bounds[3] {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
         discard;
     }
-    if in.stroke > 0 {
-        if in.uv.x > in.stroke && in.uv.x < in.size.x-in.stroke &&
-           in.uv.y > in.stroke && in.uv.y < in.size.y-in.stroke {
-            discard;
-        }
-    }
-    return in.color;
+
+    let p = in.uv - (in.size / 2.0);
+    let d = sd(p, in);
+    let shadow = sd(p - in.inner_shadow.xy, in);
+    let color = fill(
+        unpack_color(in.color), d, in.stroke, unpack_color(in.border_color), in.border,
+        unpack_color(in.inner_shadow_color), in.inner_shadow, shadow
+    );
+    return apply_clip(color, clipped);
 }
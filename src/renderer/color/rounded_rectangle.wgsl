@@ -1,12 +1,19 @@
 struct ShapeInput {
     @location(0) uv: vec2<f32>,
-    @location(1) position: vec2<f32>,
+    @location(1) position: vec3<f32>,
     @location(2) size: vec2<f32>,
     @location(3) bounds: vec4<f32>,
-    @location(4) z: f32,
-    @location(5) stroke: f32,
-    @location(6) corner_radius: f32,
-    @location(7) color: vec4<f32>,
+    @location(4) clip: vec4<f32>,
+    @location(5) clip_angle: f32,
+    @location(6) z: f32,
+    @location(7) stroke: f32,
+    @location(8) corner_radius: f32,
+    @location(9) smoothing: f32,
+    @location(10) color: vec4<f32>,
+    @location(11) border_color: vec4<f32>,
+    @location(12) border: vec2<f32>,
+    @location(13) inner_shadow_color: vec4<f32>,
+    @location(14) inner_shadow: vec4<f32>
 }
 
 struct VertexOutput {
@@ -16,8 +23,14 @@ struct VertexOutput {
     @location(2) @interpolate(flat) bounds: vec4<f32>,
     @location(3) @interpolate(flat) stroke: f32,
     @location(4) @interpolate(flat) corner_radius: f32,
-    @location(5) @interpolate(flat) color: vec4<f32>,
-    @location(6) vertex_position: vec2<f32>
+    @location(5) @interpolate(flat) smoothing: f32,
+    @location(6) @interpolate(flat) color: vec2<u32>,
+    @location(7) @interpolate(flat) border_color: vec2<u32>,
+    @location(8) @interpolate(flat) border: vec2<f32>,
+    @location(9) @interpolate(flat) inner_shadow_color: vec2<u32>,
+    @location(10) @interpolate(flat) inner_shadow: vec4<f32>,
+    @location(11) @interpolate(flat) clip: vec4<f32>,
+    @location(12) @interpolate(flat) clip_angle: f32
 };
 
 @vertex
@@ -25,68 +38,44 @@ fn vs_main(
     shape: ShapeInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.position = vec4<f32>(shape.position, shape.z, 1.0);
+    out.position = vec4<f32>(shape.position.xy, shape.z * shape.position.z, shape.position.z);
     out.uv = shape.uv;
 
     out.size = shape.size;
 
     out.bounds = shape.bounds;
+    out.clip = shape.clip;
+    out.clip_angle = shape.clip_angle;
     out.stroke = shape.stroke;
     out.corner_radius = shape.corner_radius;
-    out.color = shape.color;
-	out.vertex_position = shape.position;
+    out.smoothing = shape.smoothing;
+    out.color = pack_color(shape.color);
+    out.border_color = pack_color(shape.border_color);
+    out.border = shape.border;
+    out.inner_shadow_color = pack_color(shape.inner_shadow_color);
+    out.inner_shadow = shape.inner_shadow;
 
     return out;
 }
 
-fn alpha(uv: vec2<f32>, size: vec2<f32>, stroke: f32, cr: f32) -> f32 {
-    var x = 0.0;
-    var y = 0.0;
-
-    if uv.x < cr && uv.y < cr {
-        x = (cr-uv.x);
-        y = (cr-uv.y);
-    } else if uv.x > size[0]-cr && uv.y < cr {
-        x = ((size[0]-cr)-uv.x);
-        y = (cr-uv.y);
-    } else if uv.x < cr && uv.y > size[1]-cr {
-        x = (cr-uv.x);
-        y = ((size[1]-cr)-uv.y);
-    } else if uv.x > size[0]-cr && uv.y > size[1]-cr {
-        x = ((size[0]-cr)-uv.x);
-        y = ((size[1]-cr)-uv.y);
-    } else {
-        if stroke > 0 {
-            if uv.x > stroke && uv.x < size.x-stroke &&
-               uv.y > stroke && uv.y < size.y-stroke {
-                return 0.0;
-            }
-            return 1.0;
-        }
-    }
-
-    let dx = x / cr;
-    let dy = y / cr;
-    let d = dx*dx+dy*dy;
-    let p = (2.0/cr);
-
-    var s = 1.0;
-    if stroke > 0 && stroke < cr {
-        let sx = x / (cr-stroke-0.25);
-        let sy = y / (cr-stroke-0.25);
-        let sd = sx*sx+sy*sy;
-        s = smoothstep(1.0, 1.0+p, sd);
-    }
-
-    return (1.0-smoothstep(1.0, 1.0+p, d)) * s;
+fn sd(p: vec2<f32>, in: VertexOutput) -> f32 {
+    let b = in.size / 2.0;
+    return sd_smooth_box(p, b, clamp(in.corner_radius, 0.0, min(b.x, b.y)), in.smoothing);
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-	if in.vertex_position.x < in.bounds[0] || in.vertex_position.x > in.bounds[2] ||
-       in.vertex_position.y > in.bounds[1] || in.vertex_position.y < in.bounds[3] {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
         discard;
     }
-    let alpha = alpha(in.uv, in.size, in.stroke, in.corner_radius);
-    return vec4<f32>(in.color[0], in.color[1], in.color[2], in.color[3]*alpha);
+
+    let p = in.uv - (in.size / 2.0);
+    let d = sd(p, in);
+    let shadow = sd(p - in.inner_shadow.xy, in);
+    let color = fill(
+        unpack_color(in.color), d, in.stroke, unpack_color(in.border_color), in.border,
+        unpack_color(in.inner_shadow_color), in.inner_shadow, shadow
+    );
+    return apply_clip(color, clipped);
 }
@@ -0,0 +1,88 @@
+struct ShapeInput {
+    @location(0) uv: vec2<f32>,
+    @location(1) position: vec3<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) bounds: vec4<f32>,
+    @location(4) clip: vec4<f32>,
+    @location(5) clip_angle: f32,
+    @location(6) z: f32,
+    @location(7) stroke: f32,
+    @location(8) params: vec4<f32>,
+    @location(9) blur: f32,
+    @location(10) spread: f32,
+    @location(11) kind: u32,
+    @location(12) color: vec4<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) size: vec2<f32>,
+    @location(2) @interpolate(flat) bounds: vec4<f32>,
+    @location(3) @interpolate(flat) params: vec4<f32>,
+    @location(4) @interpolate(flat) blur: f32,
+    @location(5) @interpolate(flat) spread: f32,
+    @location(6) @interpolate(flat) kind: u32,
+    @location(7) @interpolate(flat) color: vec4<f32>,
+    @location(8) @interpolate(flat) clip: vec4<f32>,
+    @location(9) @interpolate(flat) clip_angle: f32
+};
+
+const ELLIPSE: u32 = 0u;
+const ARC: u32 = 2u;
+const POLYGON: u32 = 3u;
+
+@vertex
+fn vs_main(
+    shape: ShapeInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(shape.position.xy, shape.z * shape.position.z, shape.position.z);
+    out.uv = shape.uv;
+
+    out.size = shape.size;
+
+    out.bounds = shape.bounds;
+    out.clip = shape.clip;
+    out.clip_angle = shape.clip_angle;
+    out.params = shape.params;
+    out.blur = shape.blur;
+    out.spread = shape.spread;
+    out.kind = shape.kind;
+    out.color = shape.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
+        discard;
+    }
+
+    let p = in.uv - (in.size / 2.0);
+    let b = max((in.size / 2.0) + in.spread, vec2<f32>(0.0001));
+
+    var d = 0.0;
+    if in.kind == ELLIPSE {
+        d = sd_ellipse(p, b);
+    } else if in.kind == ARC {
+        d = sd_arc(p, in.size / 2.0, in.params[0], in.params.yzw) - in.spread;
+    } else if in.kind == POLYGON {
+        d = sd_polygon(p, in.size / 2.0, in.params.xyz) - in.spread;
+    } else {
+        var r = 0.0;
+        if in.params[0] > 0.0 {
+            r = clamp(in.params[0] + in.spread, 0.0, min(b.x, b.y));
+        }
+        d = sd_smooth_box(p, b, r, in.params[1]);
+    }
+
+    let alpha = in.color[3]*coverage(d, in.blur)*clipped;
+    if alpha < 1.0 / 512.0 {
+        discard;
+    }
+
+    return vec4<f32>(in.color[0], in.color[1], in.color[2], alpha);
+}
@@ -0,0 +1,96 @@
+use wgpu::{BindGroupLayoutDescriptor, TextureViewDimension, BindGroupLayoutEntry, TextureSampleType, BindGroupLayout, ShaderStages, BindingType, Device, Queue, BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor, Extent3d, TexelCopyTextureInfo, TexelCopyBufferLayout, Origin3d, TextureAspect};
+
+use crate::ColorMatrix;
+
+/// The color matrices of a frame, one row of five texels each holding a column, so vertices
+/// only carry the row of theirs. Row 0 is the identity, standing for no filter.
+pub struct FilterTable {
+    bind_group_layout: BindGroupLayout,
+    texture: Texture,
+    bind_group: BindGroup,
+    matrices: Vec<ColorMatrix>,
+}
+
+impl FilterTable {
+    /// Create all unchanging resources here.
+    pub fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float{filterable: false},
+                    },
+                    count: None,
+                }
+            ]
+        });
+        let (texture, bind_group) = Self::texture(device, &bind_group_layout, 1);
+        FilterTable{bind_group_layout, texture, bind_group, matrices: vec![]}
+    }
+
+    fn texture(device: &Device, layout: &BindGroupLayout, rows: u32) -> (Texture, BindGroup) {
+        let texture = device.create_texture(&TextureDescriptor{
+            label: Some("Filter texture"),
+            size: Extent3d{width: 5, height: rows, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout,
+            entries: &[BindGroupEntry{
+                binding: 0,
+                resource: BindingResource::TextureView(&texture.create_view(&TextureViewDescriptor::default())),
+            }]
+        });
+        (texture, bind_group)
+    }
+
+    pub fn layout(&self) -> &BindGroupLayout {&self.bind_group_layout}
+
+    pub fn bind_group(&self) -> &BindGroup {&self.bind_group}
+
+    /// Forgets the matrices of the last frame.
+    pub fn clear(&mut self) {self.matrices.clear();}
+
+    /// The row `filter` is written to, 0 for none.
+    pub fn row(&mut self, filter: Option<ColorMatrix>) -> f32 {
+        let Some(filter) = filter.filter(|f| *f != ColorMatrix::IDENTITY) else {return 0.0};
+        let index = match self.matrices.iter().position(|m| *m == filter) {
+            Some(index) => index,
+            None => {
+                self.matrices.push(filter);
+                self.matrices.len() - 1
+            }
+        };
+        (index + 1) as f32
+    }
+
+    /// Writes the rows given out this frame, growing the texture when they do not fit. Rows
+    /// past the largest texture the device allows are left out.
+    pub fn write(&mut self, device: &Device, queue: &Queue) {
+        let limit = device.limits().max_texture_dimension_2d;
+        let rows = (self.matrices.len() as u32 + 1).min(limit);
+        if self.texture.height() < rows {
+            (self.texture, self.bind_group) = Self::texture(device, &self.bind_group_layout, rows.next_power_of_two().min(limit));
+        }
+        let texels = std::iter::once(&ColorMatrix::IDENTITY).chain(&self.matrices).take(rows as usize).flat_map(|m| {
+            (0..5).map(|col| [m.0[col], m.0[5 + col], m.0[10 + col], m.0[15 + col]])
+        }).collect::<Vec<_>>();
+        queue.write_texture(
+            TexelCopyTextureInfo{texture: &self.texture, mip_level: 0, origin: Origin3d::ZERO, aspect: TextureAspect::All},
+            bytemuck::cast_slice(&texels),
+            TexelCopyBufferLayout{offset: 0, bytes_per_row: Some(5 * 16), rows_per_image: None},
+            Extent3d{width: 5, height: rows, depth_or_array_layers: 1},
+        );
+    }
+}
@@ -5,19 +5,28 @@ use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
 use std::collections::HashMap;
 use std::sync::Arc;
 use crate::shape::Shape;
-use crate::{Area, Color, RgbaImage};
+use crate::{Area, Color, RgbaImage, ColorMatrix};
 
 use super::atlas::ImageAtlas;
-use super::vertex::{Vertex, ImageVertex, ShapeVertex, RoundedRectangleVertex};
+use super::filter::FilterTable;
+use super::vertex::{Vertex, ImageVertex, ShapeVertex, RoundedRectangleVertex, ArcVertex, PolygonVertex};
+use super::{sdf_shader, Segments};
 
 type ArcImage = Arc<RgbaImage>;
+/// An image or glyph drawn at `z`, with its tint and color matrix.
+type ImageItem = (Area, Shape, ArcImage, Option<Color>, Option<ColorMatrix>, u16);
+/// Index ranges by image.
+type ImageDraws = HashMap<Arc<BindGroup>, Vec<(u32, u32)>>;
 
 pub struct ImageRenderer {
     bind_group_layout: BindGroupLayout,
     sampler: Sampler,
+    filters: FilterTable,
     ellipse_renderer: GenericImageRenderer,
     rectangle_renderer: GenericImageRenderer,
     rounded_rectangle_renderer: GenericImageRenderer,
+    arc_renderer: GenericImageRenderer,
+    polygon_renderer: GenericImageRenderer,
 }
 
 impl ImageRenderer {
@@ -60,57 +69,84 @@ impl ImageRenderer {
             ..Default::default()
         });
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("image/ellipse.wgsl"));
-        let ellipse_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &bind_group_layout, shader, ImageVertex::<ShapeVertex>::layout());
-        let shader = device.create_shader_module(wgpu::include_wgsl!("image/rectangle.wgsl"));
-        let rectangle_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &bind_group_layout, shader, ImageVertex::<ShapeVertex>::layout());
-        let shader = device.create_shader_module(wgpu::include_wgsl!("image/rounded_rectangle.wgsl"));
-        let rounded_rectangle_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &bind_group_layout, shader, ImageVertex::<RoundedRectangleVertex>::layout());
+        let filters = FilterTable::new(device);
+        let layouts = [&bind_group_layout, filters.layout()];
+
+        let shader = sdf_shader(device, "image/ellipse.wgsl", include_str!("image/ellipse.wgsl"));
+        let ellipse_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &layouts, shader, ImageVertex::<ShapeVertex>::layout());
+        let shader = sdf_shader(device, "image/rectangle.wgsl", include_str!("image/rectangle.wgsl"));
+        let rectangle_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &layouts, shader, ImageVertex::<ShapeVertex>::layout());
+        let shader = sdf_shader(device, "image/rounded_rectangle.wgsl", include_str!("image/rounded_rectangle.wgsl"));
+        let rounded_rectangle_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &layouts, shader, ImageVertex::<RoundedRectangleVertex>::layout());
+        let shader = sdf_shader(device, "image/arc.wgsl", include_str!("image/arc.wgsl"));
+        let arc_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &layouts, shader, ImageVertex::<ArcVertex>::layout());
+        let shader = sdf_shader(device, "image/polygon.wgsl", include_str!("image/polygon.wgsl"));
+        let polygon_renderer = GenericImageRenderer::new(device, texture_format, multisample, depth_stencil.clone(), &layouts, shader, ImageVertex::<PolygonVertex>::layout());
         ImageRenderer{
             bind_group_layout,
             sampler,
+            filters,
             ellipse_renderer,
             rectangle_renderer,
-            rounded_rectangle_renderer
+            rounded_rectangle_renderer,
+            arc_renderer,
+            polygon_renderer
         }
     }
 
     /// Prepare for rendering this frame; create all resources that will be
     /// used during the next render that do not already exist.
+    #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         &mut self,
         device: &Device,
         queue: &Queue,
         width: f32,
         height: f32,
+        segments: &Segments,
         image_atlas: &mut ImageAtlas,
-        items: Vec<(Area, Shape, ArcImage, Option<Color>, u16)>,
+        items: Vec<ImageItem>,
     ) {
-        let (ellipses, rects, rounded_rects) = items.into_iter().fold(
-            (vec![], vec![], vec![]),
-            |mut a, (area, shape, key, color, z)| {
+        self.filters.clear();
+        let (ellipses, rects, rounded_rects, arcs, polygons) = items.into_iter().fold(
+            (vec![], vec![], vec![], vec![], vec![]),
+            |mut a, (area, shape, key, color, filter, z)| {
+                let segment = segments.of(z);
+                let filter = self.filters.row(filter);
                 let image = image_atlas.get(queue, device, &self.bind_group_layout, &self.sampler, &key);
                 match shape {
                     Shape::Ellipse(_, size, _) =>
-                        a.0.push((ImageVertex::new(ShapeVertex::new(width, height, z, area, shape), &key, size, color), image)),
+                        a.0.push((segment, ImageVertex::new(ShapeVertex::new(width, height, z, area, shape), &key, size, color, filter), image)),
                     Shape::Rectangle(_, size, _) =>
-                        a.1.push((ImageVertex::new(ShapeVertex::new(width, height, z, area, shape), &key, size, color), image)),
+                        a.1.push((segment, ImageVertex::new(ShapeVertex::new(width, height, z, area, shape), &key, size, color, filter), image)),
                     Shape::RoundedRectangle(_, size, _, corner_radius) =>
-                        a.2.push((ImageVertex::new(RoundedRectangleVertex::new(width, height, z, area, shape, corner_radius), &key, size, color), image)),
+                        a.2.push((segment, ImageVertex::new(RoundedRectangleVertex::new(ShapeVertex::new(width, height, z, area, shape), corner_radius, 0.0), &key, size, color, filter), image)),
+                    Shape::SmoothRoundedRectangle(_, size, _, corner_radius, smoothing) =>
+                        a.2.push((segment, ImageVertex::new(RoundedRectangleVertex::new(ShapeVertex::new(width, height, z, area, shape), corner_radius, smoothing), &key, size, color, filter), image)),
+                    Shape::Arc(_, size, _, _, _, _) =>
+                        a.3.push((segment, ImageVertex::new(ArcVertex::new(ShapeVertex::new(width, height, z, area, shape), shape), &key, size, color, filter), image)),
+                    Shape::Polygon(_, size, _, _, _) | Shape::Star(_, size, _, _, _, _) =>
+                        a.4.push((segment, ImageVertex::new(PolygonVertex::new(ShapeVertex::new(width, height, z, area, shape), shape), &key, size, color, filter), image)),
                 }
                 a
             }
         );
+        self.filters.write(device, queue);
         self.ellipse_renderer.prepare(device, queue, ellipses);
         self.rectangle_renderer.prepare(device, queue, rects);
         self.rounded_rectangle_renderer.prepare(device, queue, rounded_rects);
+        self.arc_renderer.prepare(device, queue, arcs);
+        self.polygon_renderer.prepare(device, queue, polygons);
     }
 
-    /// Render using caller provided render pass.
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
-        self.ellipse_renderer.render(render_pass);
-        self.rectangle_renderer.render(render_pass);
-        self.rounded_rectangle_renderer.render(render_pass);
+    /// Render the items of `segment` using caller provided render pass.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize) {
+        let filters = self.filters.bind_group();
+        self.ellipse_renderer.render(render_pass, segment, filters);
+        self.rectangle_renderer.render(render_pass, segment, filters);
+        self.rounded_rectangle_renderer.render(render_pass, segment, filters);
+        self.arc_renderer.render(render_pass, segment, filters);
+        self.polygon_renderer.render(render_pass, segment, filters);
     }
 }
 
@@ -118,7 +154,8 @@ pub struct GenericImageRenderer {
     render_pipeline: RenderPipeline,
     vertex_buffer: DynamicBuffer,
     index_buffer: DynamicBuffer,
-    indices: HashMap<Arc<BindGroup>, Vec<(u32, u32)>>,
+    /// The draws of every segment.
+    indices: Vec<ImageDraws>,
 }
 
 impl GenericImageRenderer {
@@ -128,13 +165,13 @@ impl GenericImageRenderer {
         texture_format: &TextureFormat,
         multisample: MultisampleState,
         depth_stencil: Option<DepthStencilState>,
-        bind_group_layout: &BindGroupLayout,
+        bind_group_layouts: &[&BindGroupLayout],
         shader: ShaderModule,
         vertex_layout: VertexBufferLayout
     ) -> Self {
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
             label: None,
-            bind_group_layouts: &[bind_group_layout],
+            bind_group_layouts,
             push_constant_ranges: &[],
         });
 
@@ -174,7 +211,7 @@ impl GenericImageRenderer {
             render_pipeline,
             vertex_buffer,
             index_buffer,
-            indices: HashMap::new(),
+            indices: vec![],
         }
     }
 
@@ -184,13 +221,13 @@ impl GenericImageRenderer {
         &mut self,
         device: &Device,
         queue: &Queue,
-        image_vertices: Vec<([V; 4], Arc<BindGroup>)>,
+        image_vertices: Vec<(usize, [V; 4], Arc<BindGroup>)>,
     ) {
         self.indices.clear();
 
         let (vertices, indices, indices_buffer) = image_vertices.into_iter().fold(
-            (vec![], vec![], HashMap::<Arc<BindGroup>, Vec<(u32, u32)>>::new()),
-            |mut a, (vertices, image)| {
+            (vec![], vec![], Vec::<ImageDraws>::new()),
+            |mut a, (segment, vertices, image)| {
                 let start = a.1.len();
 
                 let l = a.0.len() as u16;
@@ -198,9 +235,10 @@ impl GenericImageRenderer {
                 a.1.extend([l, l+1, l+2, l+1, l+2, l+3]);
 
                 let index = (start as u32, a.1.len() as u32);
-                match a.2.get_mut(&image) {
+                if a.2.len() <= segment {a.2.resize_with(segment + 1, HashMap::new);}
+                match a.2[segment].get_mut(&image) {
                     Some(indices) => indices.push(index),
-                    None => {a.2.insert(image, vec![index]);}
+                    None => {a.2[segment].insert(image, vec![index]);}
                 }
                 a
             }
@@ -211,12 +249,15 @@ impl GenericImageRenderer {
         self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
     }
 
-    /// Render using caller provided render pass.
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) {
+    /// Render the items of `segment` using caller provided render pass, their color matrices
+    /// in `filters`.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize, filters: &BindGroup) {
+        let Some(images) = self.indices.get(segment).filter(|i| !i.is_empty()) else {return};
         render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(1, Some(filters), &[]);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
         render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
-        for (bind_group, indices) in &self.indices {
+        for (bind_group, indices) in images {
             render_pass.set_bind_group(0, Some(&**bind_group), &[]);
             for (start, end) in indices {
                 render_pass.draw_indexed(*start..*end, 0, 0..1);
@@ -0,0 +1,72 @@
+struct ShapeInput {
+    @location(0) uv: vec2<f32>,
+    @location(1) position: vec3<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) bounds: vec4<f32>,
+    @location(4) clip: vec4<f32>,
+    @location(5) clip_angle: f32,
+    @location(6) z: f32,
+    @location(7) stroke: f32,
+    @location(8) arc: vec3<f32>,
+    @location(9) color: vec4<f32>,
+    @location(10) texture: vec2<f32>,
+    @location(11) filter_row: f32
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) size: vec2<f32>,
+    @location(2) @interpolate(flat) bounds: vec4<f32>,
+    @location(3) @interpolate(flat) stroke: f32,
+    @location(4) @interpolate(flat) arc: vec3<f32>,
+    @location(5) @interpolate(flat) color: vec4<f32>,
+    @location(6) texture: vec2<f32>,
+    @location(7) @interpolate(flat) clip: vec4<f32>,
+    @location(8) @interpolate(flat) clip_angle: f32,
+    @location(9) @interpolate(flat) filter_row: f32
+};
+
+@vertex
+fn vs_main(
+    shape: ShapeInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(shape.position.xy, shape.z * shape.position.z, shape.position.z);
+    out.uv = shape.uv;
+
+    out.size = shape.size;
+
+    out.bounds = shape.bounds;
+    out.clip = shape.clip;
+    out.clip_angle = shape.clip_angle;
+    out.stroke = shape.stroke;
+    out.arc = shape.arc;
+    out.color = shape.color;
+    out.texture = shape.texture;
+    out.filter_row = shape.filter_row;
+
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(1) @binding(0)
+var t_filters: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
+        discard;
+    }
+    var color = textureSample(t_diffuse, s_diffuse, in.texture);
+    if in.color[3] > 0.0 {
+        color = vec4<f32>(in.color[0], in.color[1], in.color[2], in.color[3]*color[3]);
+    }
+    color = filtered(color, t_filters, in.filter_row);
+    let alpha = coverage(sd_arc(in.uv - (in.size / 2.0), in.size / 2.0, in.stroke, in.arc), 0.0);
+    return vec4<f32>(color[0], color[1], color[2], color[3]*alpha*clipped);
+}
@@ -1,12 +1,15 @@
 struct ShapeInput {
     @location(0) uv: vec2<f32>,
-    @location(1) position: vec2<f32>,
+    @location(1) position: vec3<f32>,
     @location(2) size: vec2<f32>,
     @location(3) bounds: vec4<f32>,
-    @location(4) z: f32,
-    @location(5) stroke: f32,
-    @location(6) color: vec4<f32>,
-    @location(7) texture: vec2<f32>
+    @location(4) clip: vec4<f32>,
+    @location(5) clip_angle: f32,
+    @location(6) z: f32,
+    @location(7) stroke: f32,
+    @location(8) color: vec4<f32>,
+    @location(9) texture: vec2<f32>,
+    @location(10) filter_row: f32
 }
 
 struct VertexOutput {
@@ -17,7 +20,9 @@ struct VertexOutput {
     @location(3) @interpolate(flat) stroke: f32,
     @location(4) @interpolate(flat) color: vec4<f32>,
     @location(5) texture: vec2<f32>,
-    @location(6) vertex_position: vec2<f32>
+    @location(6) @interpolate(flat) clip: vec4<f32>,
+    @location(7) @interpolate(flat) clip_angle: f32,
+    @location(8) @interpolate(flat) filter_row: f32
 };
 
 @vertex
@@ -25,16 +30,18 @@ fn vs_main(
     shape: ShapeInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.position = vec4<f32>(shape.position, shape.z, 1.0);
+    out.position = vec4<f32>(shape.position.xy, shape.z * shape.position.z, shape.position.z);
     out.uv = shape.uv;
 
     out.size = shape.size;
 
     out.bounds = shape.bounds;
+    out.clip = shape.clip;
+    out.clip_angle = shape.clip_angle;
     out.stroke = shape.stroke;
     out.color = shape.color;
     out.texture = shape.texture;
-	out.vertex_position = shape.position;
+    out.filter_row = shape.filter_row;
 
     return out;
 }
@@ -43,11 +50,13 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(1) @binding(0)
+var t_filters: texture_2d<f32>;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-	if in.vertex_position.x < in.bounds[0] || in.vertex_position.x > in.bounds[2] ||
-       in.vertex_position.y > in.bounds[1] || in.vertex_position.y < in.bounds[3] {
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if clipped <= 0.0 {
         discard;
     }
 
@@ -74,5 +83,6 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     if in.color[3] > 0.0 {
         color = vec4<f32>(in.color[0], in.color[1], in.color[2], in.color[3]*color[3]);
     }
-    return vec4<f32>(color[0], color[1], color[2], color[3]*alpha);
+    color = filtered(color, t_filters, in.filter_row);
+    return vec4<f32>(color[0], color[1], color[2], color[3]*alpha*clipped);
 }
@@ -0,0 +1,583 @@
+use wgpu::{PipelineCompilationOptions, PipelineLayout, ShaderModule, BindGroupLayoutDescriptor, RenderPipelineDescriptor, PipelineLayoutDescriptor, TextureViewDimension, BindGroupLayoutEntry, DepthStencilState, TextureSampleType, MultisampleState, BindGroupLayout, RenderPipeline, PrimitiveState, FragmentState, TextureFormat, ShaderStages, BufferUsages, IndexFormat, VertexState, BindingType, RenderPass, Device, Queue, BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, BlendState, BlendComponent, BlendFactor, BlendOperation, ColorTargetState, ColorWrites, TextureDescriptor, TextureDimension, TextureUsages, TextureView, TextureViewDescriptor, Extent3d, CommandEncoder, CommandEncoderDescriptor, RenderPassDescriptor, RenderPassColorAttachment, RenderPassDepthStencilAttachment, Operations, LoadOp, StoreOp, Sampler, SamplerBindingType};
+
+use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use std::collections::HashMap;
+
+use crate::transform::{project, projective, invert3, apply, IDENTITY};
+use crate::{Instruction, Item, Area, Mask, Layer, BlendMode, Blur, BlurEdge, Shape, Color};
+
+use super::vertex::{Vertex, LayerVertex};
+use super::blur::{BlurRenderer, Blurred};
+use super::filter::FilterTable;
+use super::{sdf_shader, Segments, Renderer, Atlas};
+
+/// Pixels `(x, y, width, height)` a layer is drawn from.
+type Region = (i32, i32, u32, u32);
+
+/// What a group of instructions drawn into a layer is composited with.
+pub enum Group {
+    Mask(Mask),
+    /// A layer, with the instructions under it when its blend mode reads them back.
+    Layer(Layer, Vec<Instruction>),
+    /// The backdrop blur of a shape, its instructions being those under it.
+    Backdrop(Shape),
+}
+
+/// Which texture of a layer [`LayerRenderer::draw`] draws into.
+#[derive(Clone, Copy)]
+enum Pass {
+    Content,
+    Mask,
+    Backdrop,
+}
+
+/// Draws groups of instructions into textures of their own, then composites them into the
+/// frame like a single item.
+pub struct LayerRenderer {
+    texture_format: TextureFormat,
+    multisample: MultisampleState,
+    depth_stencil: Option<DepthStencilState>,
+    /// Draws the groups, created with the first one and reused by every group after it.
+    renderer: Option<Box<Renderer>>,
+    /// Draws the masks without alpha to coverage, their alpha being all that is read of them.
+    mask_renderer: Option<Box<Renderer>>,
+    /// Created with the first blurred layer.
+    blur_renderer: Option<BlurRenderer>,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    filters: FilterTable,
+    shader: ShaderModule,
+    pipeline_layout: PipelineLayout,
+    /// Created with the first layer of every blend mode.
+    render_pipelines: HashMap<BlendMode, RenderPipeline>,
+    /// Clears the coverage of a mask, created with the first backdrop.
+    clear_pipeline: Option<RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    /// Kept between frames and only recreated when their region changes size.
+    textures: Vec<Textures>,
+    /// The layers each segment composites, with their blend mode, `None` clearing under
+    /// their mask.
+    indices: Vec<Vec<(usize, Option<BlendMode>)>>,
+}
+
+impl LayerRenderer {
+    /// Create all unchanging resources here.
+    pub fn new(
+        device: &Device,
+        texture_format: &TextureFormat,
+        multisample: MultisampleState,
+        depth_stencil: Option<DepthStencilState>,
+    ) -> Self {
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                multisampled: false,
+                view_dimension: TextureViewDimension::D2,
+                sample_type: TextureSampleType::Float{filterable: true},
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                texture(0),
+                texture(1),
+                texture(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let filters = FilterTable::new(device);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, filters.layout()],
+            push_constant_ranges: &[],
+        });
+
+        let shader = sdf_shader(device, "layer.wgsl", include_str!("layer.wgsl"));
+
+        let vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+
+        LayerRenderer{
+            texture_format: *texture_format,
+            multisample,
+            depth_stencil,
+            renderer: None,
+            mask_renderer: None,
+            blur_renderer: None,
+            bind_group_layout,
+            sampler,
+            filters,
+            shader,
+            pipeline_layout,
+            render_pipelines: HashMap::new(),
+            clear_pipeline: None,
+            vertex_buffer,
+            index_buffer,
+            textures: vec![],
+            indices: vec![],
+        }
+    }
+
+    /// Prepare for rendering this frame; draws every group, and the mask of masked ones, into
+    /// textures covering the pixels it can show, submitting them to `queue` right away. Layers
+    /// reading back what is under them draw it into a third texture covering where they land,
+    /// blurred layers are blurred into textures of their own.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: f32,
+        height: f32,
+        segments: &Segments,
+        atlas: &mut Atlas,
+        groups: Vec<(Area, Group, Vec<Instruction>, u16)>,
+    ) {
+        self.indices.clear();
+        self.filters.clear();
+        let limit = device.limits().max_texture_dimension_2d;
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        for (area, group, items, z) in groups {
+            let region = match &group {
+                Group::Mask(mask) => mask_region(width, height, &area, mask),
+                Group::Layer(layer, _) if layer.opacity <= 0.0 => None,
+                Group::Layer(layer, _) => layer_region(width, height, &area, layer, &items, limit),
+                Group::Backdrop(shape) => backdrop_region(width, height, &area, shape, limit),
+            };
+            let Some(region) = region else {continue};
+            let by = (-(region.0 as f32), -(region.1 as f32));
+            //Backdrops go before the shape, after everything under it
+            let segment = segments.of(z) - matches!(group, Group::Backdrop(_)) as usize;
+            let moved = |items: Vec<Instruction>, by: (f32, f32)| items.into_iter().map(|Instruction(a, item)| Instruction(a.moved(by), item)).collect();
+            let replace = matches!(group, Group::Backdrop(_));
+            let (mask, matrix, opacity, blend, under, blur, filter) = match group {
+                Group::Mask(Mask::Image(image)) => (
+                    Some(vec![Instruction(area.moved(by), Item::Image(image))]), projective(IDENTITY), 1.0, BlendMode::Normal, vec![], None, None
+                ),
+                Group::Mask(Mask::Items(mask)) => (
+                    Some(moved(mask, (area.offset.0 + by.0, area.offset.1 + by.1))), projective(IDENTITY), 1.0, BlendMode::Normal, vec![], None, None
+                ),
+                Group::Layer(layer, under) => (
+                    None, area.projection(), layer.opacity.min(1.0), layer.blend, under, layer.blur.filter(|b| b.radius > 0.0), layer.filter
+                ),
+                //Masked by the fill of the shape, added where it was cleared
+                Group::Backdrop(shape) => (
+                    Some(vec![Instruction(area.moved(by), Item::Shape(Shape::new(shape.shape, Color::BLACK)))]),
+                    projective(IDENTITY), 1.0, BlendMode::Add, vec![], shape.backdrop.filter(|b| b.radius > 0.0), None
+                ),
+            };
+
+            //The pixels of the target the layer lands on, read back texel for texel
+            let backdrop = blend.reads_back().then(|| {
+                let (x, y) = (region.0 as f32, region.1 as f32);
+                let (x2, y2) = (x + region.2 as f32, y + region.3 as f32);
+                let landed = projected(matrix, [(x, y), (x2, y), (x, y2), (x2, y2)]).unwrap_or((f32::MIN, f32::MIN, f32::MAX, f32::MAX));
+                self::region(intersect(landed, visible(width, height, &area)), limit)
+            }).flatten();
+
+            if blur.is_some() && self.blur_renderer.is_none() {
+                self.blur_renderer = Some(BlurRenderer::new(device, &self.texture_format));
+            }
+            let index = vertices.len() / 4;
+            let masked = mask.is_some();
+            let size = (region.2, region.3);
+            let backdrop_size = backdrop.map(|b| (b.2, b.3));
+            match self.textures.get(index) {
+                Some(textures) if textures.size == size && textures.mask.is_some() == mask.is_some()
+                    && textures.backdrop.as_ref().map(|b| b.0) == backdrop_size
+                    && textures.blurred.as_ref().map(|b| blur.is_some_and(|blur| b.fits(size, blur))).unwrap_or(blur.is_none()) => {},
+                _ => {
+                    let textures = Textures::new(device, self, size, mask.is_some(), backdrop_size, blur);
+                    match index < self.textures.len() {
+                        true => self.textures[index] = textures,
+                        false => self.textures.push(textures),
+                    }
+                }
+            }
+            self.draw(device, queue, atlas, index, moved(items, by), Pass::Content);
+            if let (Some(blurred), Some(blur), Some(blur_renderer)) = (&self.textures[index].blurred, blur, &mut self.blur_renderer) {
+                blur_renderer.blur(device, queue, blurred, blur);
+            }
+            if let Some(mask) = mask {self.draw(device, queue, atlas, index, mask, Pass::Mask);}
+            if let Some(b) = backdrop {self.draw(device, queue, atlas, index, moved(under, (-(b.0 as f32), -(b.1 as f32))), Pass::Backdrop);}
+
+            if !self.render_pipelines.contains_key(&blend) {
+                let pipeline = self.pipeline(device, "fs_main", blend_state(blend));
+                self.render_pipelines.insert(blend, pipeline);
+            }
+            if replace && self.clear_pipeline.is_none() {
+                let clear = BlendComponent{src_factor: BlendFactor::Zero, dst_factor: BlendFactor::OneMinusSrcAlpha, operation: BlendOperation::Add};
+                self.clear_pipeline = Some(self.pipeline(device, "fs_clear", BlendState{color: clear, alpha: clear}));
+            }
+            if self.indices.len() <= segment {self.indices.resize_with(segment + 1, Vec::new);}
+            //Backdrops replace what is under them, cleared before they are added
+            if replace {self.indices[segment].push((index, None));}
+            self.indices[segment].push((index, Some(blend)));
+            let paint = [opacity, masked as u8 as f32, backdrop.map_or(0, |_| read_back(blend)) as f32, self.filters.row(filter)];
+            let l = vertices.len() as u16;
+            let origin = backdrop.map_or([0.0; 2], |b| [b.0 as f32, b.1 as f32]);
+            vertices.extend(LayerVertex::new(width, height, z, area, region, matrix, paint, origin));
+            indices.extend([l, l+1, l+2, l+1, l+2, l+3]);
+        }
+        self.textures.truncate(vertices.len() / 4);
+        self.filters.write(device, queue);
+        self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&vertices));
+        self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
+    }
+
+    /// Render the layers of `segment` using caller provided render pass.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize) {
+        let Some(layers) = self.indices.get(segment).filter(|l| !l.is_empty()) else {return};
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+        render_pass.set_bind_group(1, Some(self.filters.bind_group()), &[]);
+        for (index, blend) in layers {
+            render_pass.set_pipeline(match blend {
+                Some(blend) => &self.render_pipelines[blend],
+                None => self.clear_pipeline.as_ref().unwrap(),
+            });
+            render_pass.set_bind_group(0, Some(&self.textures[*index].bind_group), &[]);
+            let start = *index as u32 * 6;
+            render_pass.draw_indexed(start..start+6, 0, 0..1);
+        }
+    }
+
+    fn pipeline(&self, device: &Device, entry_point: &str, blend: BlendState) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[LayerVertex::layout()]
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: Some(entry_point),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format: self.texture_format,
+                    blend: Some(blend),
+                    write_mask: ColorWrites::ALL,
+                })]
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: self.depth_stencil.clone(),
+            //Layers already carry their coverage in their alpha, which would cover it twice
+            multisample: MultisampleState{alpha_to_coverage_enabled: false, ..self.multisample},
+            multiview: None,
+            cache: None
+        })
+    }
+
+    /// Draws `items` into the content, the mask or the backdrop of a layer.
+    fn draw(&mut self, device: &Device, queue: &Queue, atlas: &mut Atlas, index: usize, items: Vec<Instruction>, pass: Pass) {
+        let (renderer, multisample) = match pass {
+            Pass::Mask => (&mut self.mask_renderer, MultisampleState{alpha_to_coverage_enabled: false, ..self.multisample}),
+            Pass::Content | Pass::Backdrop => (&mut self.renderer, self.multisample),
+        };
+        let renderer = renderer.get_or_insert_with(|| Box::new(
+            Renderer::new(device, &self.texture_format, multisample, self.depth_stencil.clone())
+        ));
+        let textures = &self.textures[index];
+        let (target, size) = match (pass, &textures.mask, &textures.backdrop) {
+            (Pass::Mask, Some(mask), _) => (mask, textures.size),
+            (Pass::Backdrop, _, Some((size, backdrop))) => (backdrop, *size),
+            _ => (&textures.content, textures.size),
+        };
+        renderer.prepare(device, queue, size.0 as f32, size.1 as f32, atlas, items);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        let mut render_pass = target.begin(&mut encoder);
+        renderer.render(&mut render_pass);
+        drop(render_pass);
+        queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// Blending of colors premultiplied by alpha for `mode`. The modes reading back what is under
+/// them are computed by the shader and drawn over.
+fn blend_state(mode: BlendMode) -> BlendState {
+    use BlendFactor::{One, Zero, Dst, OneMinusSrc, SrcAlpha, OneMinusSrcAlpha, DstAlpha, OneMinusDstAlpha};
+    let component = |src_factor, dst_factor| BlendComponent{src_factor, dst_factor, operation: BlendOperation::Add};
+    let (src, dst) = match mode {
+        BlendMode::Normal | BlendMode::Overlay | BlendMode::Darken | BlendMode::Lighten | BlendMode::Difference => (One, OneMinusSrcAlpha),
+        BlendMode::Multiply => (Dst, OneMinusSrcAlpha),
+        BlendMode::Screen => (One, OneMinusSrc),
+        BlendMode::Add => (One, One),
+        BlendMode::Clear => (Zero, Zero),
+        BlendMode::Source => (One, Zero),
+        BlendMode::SourceIn => (DstAlpha, Zero),
+        BlendMode::SourceOut => (OneMinusDstAlpha, Zero),
+        BlendMode::SourceAtop => (DstAlpha, OneMinusSrcAlpha),
+        BlendMode::DestinationOver => (OneMinusDstAlpha, One),
+        BlendMode::DestinationIn => (Zero, SrcAlpha),
+        BlendMode::DestinationOut => (Zero, OneMinusSrcAlpha),
+        BlendMode::DestinationAtop => (OneMinusDstAlpha, SrcAlpha),
+        BlendMode::Xor => (OneMinusDstAlpha, OneMinusSrcAlpha),
+    };
+    let alpha = match mode {
+        //Their alpha is the one of drawing over
+        BlendMode::Multiply | BlendMode::Screen => component(One, OneMinusSrcAlpha),
+        _ => component(src, dst),
+    };
+    BlendState{color: component(src, dst), alpha}
+}
+
+/// The formula `layer.wgsl` blends with for the modes reading back what is under them, 0 for
+/// the others.
+fn read_back(mode: BlendMode) -> u32 {match mode {
+    BlendMode::Overlay => 1,
+    BlendMode::Darken => 2,
+    BlendMode::Lighten => 3,
+    BlendMode::Difference => 4,
+    _ => 0,
+}}
+
+/// Whether the boxes `(x0, y0, x1, y1)` share any area.
+pub(super) fn overlap(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> bool {
+    let (x0, y0, x1, y1) = intersect(a, b);
+    x1 > x0 && y1 > y0
+}
+
+/// The pixels a mask can show: the bounding box of its image, or the whole target for
+/// instructions, within the bounds of its area. `None` when that is empty.
+fn mask_region(width: f32, height: f32, area: &Area, mask: &Mask) -> Option<Region> {
+    let mut extent = visible(width, height, area);
+    if let Mask::Image(image) = mask {
+        let corners = image.shape.positions(area.offset).map(|p| (p[0], p[1]));
+        extent = projected(area.projection(), corners).map_or(extent, |e| intersect(extent, e));
+    }
+    region(extent, u32::MAX)
+}
+
+/// The pixels the items of a layer draw on before its transform moves them, only those that
+/// land on the target within the bounds of its area. A transparent edged blur grows them by
+/// three sigma on every side, and takes in what lands that far outside. `None` when that is
+/// empty.
+fn layer_region(width: f32, height: f32, area: &Area, layer: &Layer, items: &[Instruction], limit: u32) -> Option<Region> {
+    let margin = |layer: &Layer| layer.blur.filter(|b| b.edge == BlurEdge::Transparent).map_or(0.0, |b| b.radius.max(0.0) * 1.5);
+    let grow = |(x0, y0, x1, y1): (f32, f32, f32, f32), by: f32| (x0 - by, y0 - by, x1 + by, y1 + by);
+    let visible = visible(width, height, area);
+    let visible = match area.transform {
+        Some(_) => invert3(area.projection()).and_then(|m| projected(m, [
+            (visible.0, visible.1), (visible.2, visible.1), (visible.0, visible.3), (visible.2, visible.3)
+        ])),
+        None => Some(visible),
+    };
+    //Blurred layers inside spread their items by their own margin
+    let spread = items.iter().map(|i| match &i.1 {
+        Item::PushLayer(layer) => margin(layer),
+        _ => 0.0,
+    }).sum::<f32>();
+    let extent = items.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |e, instruction| {
+        let extent = match instruction.1 {
+            //Transformed layers inside can move their items anywhere
+            Item::PushLayer(_) if instruction.0.transform.is_some() => Some((f32::MIN, f32::MIN, f32::MAX, f32::MAX)),
+            _ => self::extent(instruction),
+        };
+        extent.map_or(e, |i| (e.0.min(i.0), e.1.min(i.1), e.2.max(i.2), e.3.max(i.3)))
+    });
+    let extent = grow(extent, margin(layer) + spread);
+    region(visible.map_or(extent, |v| intersect(extent, grow(v, margin(layer)))), limit)
+}
+
+/// The pixels of the target a shape blurs the backdrop of, grown by three sigma on every side
+/// to take in what blurs into it. `None` when that is empty.
+fn backdrop_region(width: f32, height: f32, area: &Area, shape: &Shape, limit: u32) -> Option<Region> {
+    let margin = shape.backdrop.map_or(0.0, |b| b.radius.max(0.0) * 1.5);
+    let extent = extent(&Instruction(*area, Item::Shape(*shape)))?;
+    let visible = visible(width, height, area);
+    let (x0, y0, x1, y1) = intersect(extent, visible);
+    if x1 <= x0 || y1 <= y0 {return None;}
+    region(intersect((x0 - margin, y0 - margin, x1 + margin, y1 + margin), (0.0, 0.0, width, height)), limit)
+}
+
+/// The target within the bounds of `area`, as `(x0, y0, x1, y1)`.
+fn visible(width: f32, height: f32, area: &Area) -> (f32, f32, f32, f32) {
+    let target = (0.0, 0.0, width, height);
+    area.bounds.map_or(target, |b| intersect(target, (b.0, b.1, b.0 + b.2, b.1 + b.3)))
+}
+
+/// The box `(x0, y0, x1, y1)` the item of `instruction` draws in, grown for borders, strokes
+/// and shadows and within the bounds of its area. The whole plane when its transform sends
+/// part of it behind the viewer, `None` when it draws nothing.
+pub(super) fn extent(Instruction(area, item): &Instruction) -> Option<(f32, f32, f32, f32)> {
+    let centered = |(w, h): (f32, f32), (cx, cy): (f32, f32), margin: f32| {
+        let (x, y) = (area.offset.0 + cx, area.offset.1 + cy);
+        [(x - w/2.0 - margin, y - h/2.0 - margin), (x + w/2.0 + margin, y - h/2.0 - margin), (x - w/2.0 - margin, y + h/2.0 + margin), (x + w/2.0 + margin, y + h/2.0 + margin)]
+    };
+    let boxed = |(x, y, x2, y2): (f32, f32, f32, f32)| [(x, y), (x2, y), (x, y2), (x2, y2)].map(|(px, py)| (area.offset.0 + px, area.offset.1 + py));
+    let corners = match item {
+        Item::Shape(shape) => {
+            let [w, h] = shape.shape.wh();
+            centered(shape.size(), (w / 2.0, h / 2.0), 1.0 + shape.border.map(|b| b.outset()).unwrap_or_default())
+        },
+        Item::Image(image) => {
+            let [w, h] = image.shape.wh();
+            centered(image.size(), (w / 2.0, h / 2.0), 1.0)
+        },
+        Item::Shadow(shadow) => {
+            let [w, h] = shadow.shape.wh();
+            let margin = 1.0 + shadow.spread.max(0.0) + shadow.blur.max(0.0) * 1.5;
+            centered(shadow.size(), (w / 2.0 + shadow.offset.0, h / 2.0 + shadow.offset.1), margin)
+        },
+        Item::Path(path) => {
+            let (x, y, w, h) = path.data.bounds();
+            let margin = 1.0 + path.stroke.map(|s| s.width.max(0.0) * 2.0).unwrap_or_default();
+            boxed((x - margin, y - margin, x + w + margin, y + h + margin))
+        },
+        Item::Text(text) => {
+            let (w, h) = text.size();
+            let corners = boxed((-1.0, -1.0, w + 1.0, h + 1.0));
+            match text.rotation(area.offset) {
+                Some(m) => corners.map(|p| apply(m, p)),
+                None => corners,
+            }
+        },
+        Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => return None,
+    };
+    let extent = projected(area.projection(), corners).unwrap_or((f32::MIN, f32::MIN, f32::MAX, f32::MAX));
+    Some(match area.bounds {
+        Some(b) => intersect(extent, (b.0, b.1, b.0 + b.2, b.1 + b.3)),
+        None => extent,
+    })
+}
+
+/// The box `(x0, y0, x1, y1)` of `corners` through the row major 3x3 `matrix`, `None` when
+/// part of them is behind the viewer.
+fn projected(matrix: [f32; 9], corners: [(f32, f32); 4]) -> Option<(f32, f32, f32, f32)> {
+    let corners = corners.map(|p| project(matrix, p));
+    corners.iter().all(|(_, _, w)| *w > 0.0).then(|| corners.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |r, (x, y, w)| {
+        (r.0.min(x / w), r.1.min(y / w), r.2.max(x / w), r.3.max(y / w))
+    }))
+}
+
+fn intersect(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+    (a.0.max(b.0), a.1.max(b.1), a.2.min(b.2), a.3.min(b.3))
+}
+
+/// The whole pixels covering `extent`, at most `limit` on a side. `None` when it is empty.
+fn region(extent: (f32, f32, f32, f32), limit: u32) -> Option<Region> {
+    let range = i32::MIN as f32..i32::MAX as f32;
+    let (x, y) = (extent.0.floor().clamp(range.start, range.end), extent.1.floor().clamp(range.start, range.end));
+    let (x2, y2) = (extent.2.ceil().min(x + limit as f32), extent.3.ceil().min(y + limit as f32));
+    (x2 > x && y2 > y).then_some((x as i32, y as i32, (x2 - x) as u32, (y2 - y) as u32))
+}
+
+/// The textures a group, its mask and what is under it are drawn into.
+struct Textures {
+    size: (u32, u32),
+    content: Target,
+    mask: Option<Target>,
+    /// Sized to the pixels the group lands on.
+    backdrop: Option<((u32, u32), Target)>,
+    /// Composited in place of the content when the group is blurred.
+    blurred: Option<Blurred>,
+    bind_group: BindGroup,
+}
+
+impl Textures {
+    fn new(device: &Device, renderer: &LayerRenderer, size: (u32, u32), masked: bool, backdrop: Option<(u32, u32)>, blur: Option<Blur>) -> Self {
+        let new = |size| Target::new(device, renderer.texture_format, renderer.multisample.count, renderer.depth_stencil.as_ref().map(|d| d.format), size);
+        let content = new(size);
+        let mask = masked.then(|| new(size));
+        let backdrop = backdrop.map(|size| (size, new(size)));
+        let blurred = blur.zip(renderer.blur_renderer.as_ref()).map(|(blur, r)| Blurred::new(device, r, &content.view, size, blur));
+        let bind_group = device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &renderer.bind_group_layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: BindingResource::TextureView(blurred.as_ref().map_or(&content.view, |b| b.view()))},
+                BindGroupEntry{binding: 1, resource: BindingResource::TextureView(&mask.as_ref().unwrap_or(&content).view)},
+                BindGroupEntry{binding: 2, resource: BindingResource::TextureView(&backdrop.as_ref().map_or(&content, |b| &b.1).view)},
+                BindGroupEntry{binding: 3, resource: BindingResource::Sampler(&renderer.sampler)},
+            ]
+        });
+        Textures{size, content, mask, backdrop, blurred, bind_group}
+    }
+}
+
+/// A texture to draw into, with the multisampled and depth textures its render passes need.
+struct Target {
+    view: TextureView,
+    msaa: Option<TextureView>,
+    depth: Option<(TextureView, bool)>,
+}
+
+impl Target {
+    fn new(device: &Device, format: TextureFormat, samples: u32, depth: Option<TextureFormat>, size: (u32, u32)) -> Self {
+        let texture = |format, sample_count, usage| device.create_texture(&TextureDescriptor{
+            label: Some("Layer texture"),
+            size: Extent3d{width: size.0, height: size.1, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        }).create_view(&TextureViewDescriptor::default());
+        Target{
+            view: texture(format, 1, TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING),
+            msaa: (samples > 1).then(|| texture(format, samples, TextureUsages::RENDER_ATTACHMENT)),
+            depth: depth.map(|d| (texture(d, samples, TextureUsages::RENDER_ATTACHMENT), d.has_stencil_aspect())),
+        }
+    }
+
+    /// A render pass clearing the target to transparency.
+    fn begin<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Layer Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: self.msaa.as_ref().unwrap_or(&self.view),
+                resolve_target: self.msaa.as_ref().map(|_| &self.view),
+                ops: Operations {
+                    load: LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth.as_ref().map(|(view, stencil)| RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(0.0),
+                    store: StoreOp::Discard,
+                }),
+                stencil_ops: stencil.then_some(Operations {
+                    load: LoadOp::Clear(0),
+                    store: StoreOp::Discard,
+                }),
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
@@ -0,0 +1,94 @@
+struct LayerInput {
+    @location(0) uv: vec2<f32>,
+    @location(1) position: vec3<f32>,
+    @location(2) bounds: vec4<f32>,
+    @location(3) clip: vec4<f32>,
+    @location(4) clip_angle: f32,
+    @location(5) z: f32,
+    @location(6) paint: vec4<f32>,
+    @location(7) backdrop: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) bounds: vec4<f32>,
+    @location(2) @interpolate(flat) clip: vec4<f32>,
+    @location(3) @interpolate(flat) clip_angle: f32,
+    @location(4) @interpolate(flat) paint: vec4<f32>,
+    @location(5) @interpolate(flat) backdrop: vec2<f32>
+};
+
+@vertex
+fn vs_main(
+    layer: LayerInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(layer.position.xy, layer.z * layer.position.z, layer.position.z);
+    out.uv = layer.uv;
+    out.bounds = layer.bounds;
+    out.clip = layer.clip;
+    out.clip_angle = layer.clip_angle;
+    out.paint = layer.paint;
+    out.backdrop = layer.backdrop;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_content: texture_2d<f32>;
+@group(0) @binding(1)
+var t_mask: texture_2d<f32>;
+@group(0) @binding(2)
+var t_backdrop: texture_2d<f32>;
+@group(0) @binding(3)
+var s_layer: sampler;
+@group(1) @binding(0)
+var t_filters: texture_2d<f32>;
+
+//Separable blend of the unpremultiplied colors `s` over `b` by the formula `mode`.
+fn mixed(s: vec3<f32>, b: vec3<f32>, mode: u32) -> vec3<f32> {
+    switch mode {
+        case 1u: {return select(1.0 - 2.0 * (1.0 - s) * (1.0 - b), 2.0 * s * b, b <= vec3<f32>(0.5));}
+        case 2u: {return min(s, b);}
+        case 3u: {return max(s, b);}
+        default: {return abs(s - b);}
+    }
+}
+
+//Layers drawn where they were taken from land texel for texel, transformed ones are filtered.
+//Their colors are premultiplied, having been drawn over transparency, and filtered without.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(t_content, s_layer, in.uv);
+    if in.paint.w > 0.0 {
+        let f = filtered(vec4<f32>(color.rgb / max(color.a, 0.0001), color.a), t_filters, in.paint.w);
+        color = vec4<f32>(f.rgb * f.a, f.a);
+    }
+    let mask = textureSample(t_mask, s_layer, in.uv).a;
+    let clipped = clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    let alpha = select(1.0, mask, in.paint.y > 0.0) * in.paint.x * clipped;
+    if alpha * color.a <= 0.0 {
+        discard;
+    }
+    let source = color * alpha;
+    if in.paint.z <= 0.0 {
+        return source;
+    }
+    //What is under, read back from the texel of the target this lands on
+    let last = vec2<i32>(textureDimensions(t_backdrop)) - 1;
+    let backdrop = textureLoad(t_backdrop, clamp(vec2<i32>(floor(in.position.xy - in.backdrop)), vec2<i32>(0), last), 0);
+    let blended = mixed(source.rgb / source.a, backdrop.rgb / max(backdrop.a, 0.0001), u32(in.paint.z));
+    //Drawn over the target, which adds what is under where this is translucent
+    return vec4<f32>(source.rgb * (1.0 - backdrop.a) + source.a * backdrop.a * blended, source.a);
+}
+
+//The coverage of the mask as alpha, clearing what is under it for backdrops replacing it.
+@fragment
+fn fs_clear(in: VertexOutput) -> @location(0) vec4<f32> {
+    let mask = textureSample(t_mask, s_layer, in.uv).a;
+    let alpha = mask * clip_coverage(in.position.xy, in.bounds, in.clip, in.clip_angle);
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, alpha);
+}
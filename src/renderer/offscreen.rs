@@ -0,0 +1,126 @@
+//! Rendering frames into images without a surface, shared by the replay tool and the tests of
+//! the renderer, which include this file as a module of their own.
+
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+
+use wgpu::{RenderPassDepthStencilAttachment, RenderPassColorAttachment, CommandEncoderDescriptor, TextureViewDescriptor, RequestAdapterOptions, RenderPassDescriptor, InstanceDescriptor, DepthStencilState, TextureDescriptor, TextureDimension, MultisampleState, BufferDescriptor, DeviceDescriptor, PowerPreference, CompareFunction, DepthBiasState, TextureUsages, TextureFormat, StencilState, TextureView, BufferUsages, Operations, Instance, Features, Extent3d, StoreOp, LoadOp, Limits, Device, Queue, Trace, MapMode, PollType};
+
+use crate::{Renderer, Atlas, Instruction, RgbaImage};
+
+const SAMPLE_COUNT: u32 = 4;
+const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// A device and queue of the default adapter.
+pub fn device() -> Result<(Device, Queue), Box<dyn std::error::Error>> {
+    let instance = Instance::new(&InstanceDescriptor::from_env_or_default());
+    let adapter = block_on(instance.request_adapter(&RequestAdapterOptions {
+        power_preference: PowerPreference::None,
+        compatible_surface: None,
+        force_fallback_adapter: false,
+    }))?;
+    Ok(block_on(adapter.request_device(&DeviceDescriptor {
+        required_features: Features::empty(),
+        required_limits: Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        label: None,
+        memory_hints: Default::default(),
+        trace: Trace::Off
+    }))?)
+}
+
+/// A renderer set up like the canvas sets up its own, for [`render`].
+pub fn renderer(device: &Device) -> Renderer {
+    let multisample = MultisampleState {
+        count: SAMPLE_COUNT,
+        mask: !0,
+        alpha_to_coverage_enabled: true,
+    };
+    let depth_stencil = DepthStencilState {
+        format: TextureFormat::Depth24PlusStencil8,
+        depth_write_enabled: true,
+        depth_compare: CompareFunction::GreaterEqual,
+        stencil: StencilState::default(),
+        bias: DepthBiasState::default(),
+    };
+    Renderer::new(device, &FORMAT, multisample, Some(depth_stencil))
+}
+
+/// Draws `instructions` over transparency into an image of `size`, clamped to the textures the
+/// device can create.
+pub fn render(device: &Device, queue: &Queue, renderer: &mut Renderer, atlas: &mut Atlas, size: (u32, u32), instructions: Vec<Instruction>) -> RgbaImage {
+    let max = device.limits().max_texture_dimension_2d;
+    let (width, height) = (size.0.clamp(1, max), size.1.clamp(1, max));
+    atlas.trim();
+    renderer.prepare(device, queue, width as f32, height as f32, atlas, instructions);
+
+    let texture = |label, sample_count, format, usage| device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {width, height, depth_or_array_layers: 1},
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    });
+    let view = |texture: &wgpu::Texture| -> TextureView {texture.create_view(&TextureViewDescriptor::default())};
+    let frame_texture = texture("Frame", 1, FORMAT, TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC);
+    let msaa = view(&texture("Multisampled frame", SAMPLE_COUNT, FORMAT, TextureUsages::RENDER_ATTACHMENT));
+    let depth = view(&texture("Depth Stencil Texture", SAMPLE_COUNT, TextureFormat::Depth24PlusStencil8, TextureUsages::RENDER_ATTACHMENT));
+    let frame_view = view(&frame_texture);
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+    let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: &msaa,
+            resolve_target: Some(&frame_view),
+            ops: Operations {load: LoadOp::Clear(wgpu::Color::TRANSPARENT), store: StoreOp::Store},
+        })],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+            view: &depth,
+            depth_ops: Some(Operations {load: LoadOp::Clear(0.0), store: StoreOp::Store}),
+            stencil_ops: Some(Operations {load: LoadOp::Clear(0), store: StoreOp::Discard}),
+        }),
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    renderer.render(&mut rpass);
+    drop(rpass);
+
+    //Rows of a texture copy have to be aligned to 256 bytes
+    let row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Frame readback"),
+        size: (row * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    encoder.copy_texture_to_buffer(
+        frame_texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {offset: 0, bytes_per_row: Some(row), rows_per_image: Some(height)},
+        },
+        Extent3d {width, height, depth_or_array_layers: 1},
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(MapMode::Read, |result| result.expect("failed to map frame"));
+    device.poll(PollType::Wait).expect("failed to read back frame");
+    let data = slice.get_mapped_range();
+    let pixels = data.chunks(row as usize).flat_map(|r| &r[..(width * 4) as usize]).copied().collect();
+    RgbaImage::from_raw(width, height, pixels).unwrap()
+}
+
+/// Drives a future to completion on this thread. wgpu resolves adapter and device requests
+/// without needing an executor to wake it.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {return output;}
+        std::thread::yield_now();
+    }
+}
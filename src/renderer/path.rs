@@ -0,0 +1,305 @@
+use wgpu::{PipelineCompilationOptions, PipelineLayout, ShaderModule, ColorWrites, CompareFunction, StencilOperation, BindGroupLayoutDescriptor, RenderPipelineDescriptor, PipelineLayoutDescriptor, TextureViewDimension, BindGroupLayoutEntry, DepthStencilState, TextureSampleType, MultisampleState, BindGroupLayout, RenderPipeline, PrimitiveState, FragmentState, TextureFormat, ShaderStages, BufferUsages, IndexFormat, VertexState, BindingType, RenderPass, Device, Queue, Sampler, SamplerBindingType, BindGroup, Texture, TextureDescriptor, TextureDimension, TextureUsages, TexelCopyTextureInfo, TexelCopyBufferLayout, TextureAspect, Origin3d, Extent3d};
+
+use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
+use super::{sdf_shader, stencil, Segments};
+
+use std::ops::Range;
+
+use crate::{Area, Path, Clip, Color};
+use super::vertex::{Vertex, PathVertex, ColorVertex};
+
+/// Texels in a baked gradient ramp.
+const RAMP_WIDTH: u32 = 256;
+
+/// Draws tessellated [`Path`]s. Gradient fills are baked into one row each of a ramp texture
+/// that the fragment shader samples by the position along the gradient.
+///
+/// Clips are tessellated the same way and drawn into the stencil alone, by a pipeline that
+/// increments the clip level inside them and one that brings it back down.
+pub struct PathRenderer {
+    render_pipeline: RenderPipeline,
+    clip_pipelines: Option<(RenderPipeline, RenderPipeline)>,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    ramps: Texture,
+    bind_group: BindGroup,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    segments: Vec<Range<u32>>,
+    clip_vertex_buffer: DynamicBuffer,
+    clip_index_buffer: DynamicBuffer,
+    clips: Vec<Range<u32>>,
+}
+
+impl PathRenderer {
+    /// Create all unchanging resources here.
+    pub fn new(
+        device: &Device,
+        texture_format: &TextureFormat,
+        multisample: MultisampleState,
+        depth_stencil: Option<DepthStencilState>,
+        clipping: bool,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float{filterable: true},
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = sdf_shader(device, "color/path.wgsl", include_str!("color/path.wgsl"));
+        let pipeline = |depth_stencil, write_mask| Self::pipeline(device, &pipeline_layout, &shader, texture_format, multisample, depth_stencil, write_mask);
+        let render_pipeline = pipeline(depth_stencil.clone(), ColorWrites::ALL);
+        //Clips ignore the depth of what was drawn before them and only write the stencil
+        let clip_pipelines = depth_stencil.filter(|_| clipping).map(|d| {
+            let clip = DepthStencilState{depth_write_enabled: false, depth_compare: CompareFunction::Always, ..d};
+            (
+                pipeline(Some(DepthStencilState{stencil: stencil(CompareFunction::Equal, StencilOperation::IncrementClamp), ..clip.clone()}), ColorWrites::empty()),
+                pipeline(Some(DepthStencilState{stencil: stencil(CompareFunction::Less, StencilOperation::Replace), ..clip}), ColorWrites::empty()),
+            )
+        });
+
+        let ramps = Self::ramps(device, 1);
+        let bind_group = Self::bind_group(device, &bind_group_layout, &sampler, &ramps);
+
+        let vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+
+        PathRenderer{
+            render_pipeline,
+            clip_pipelines,
+            bind_group_layout,
+            sampler,
+            ramps,
+            bind_group,
+            vertex_buffer,
+            index_buffer,
+            segments: vec![],
+            clip_vertex_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            clip_index_buffer: DynamicBuffer::new(device, &DynamicBufferDescriptor {
+                label: None,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            }),
+            clips: vec![],
+        }
+    }
+
+    fn pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        texture_format: &TextureFormat,
+        multisample: MultisampleState,
+        depth_stencil: Option<DepthStencilState>,
+        write_mask: ColorWrites,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[ColorVertex::<PathVertex>::layout()]
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[
+                    Some(wgpu::ColorTargetState{
+                        format: *texture_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask,
+                    })
+                ]
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil,
+            multisample,
+            multiview: None,
+            cache: None
+        })
+    }
+
+    fn ramps(device: &Device, rows: u32) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            size: Extent3d{width: RAMP_WIDTH, height: rows, depth_or_array_layers: 1},
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            label: None,
+            view_formats: &[],
+        })
+    }
+
+    fn bind_group(device: &Device, layout: &BindGroupLayout, sampler: &Sampler, ramps: &Texture) -> BindGroup {
+        let view = ramps.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                }
+            ],
+            label: None,
+        })
+    }
+
+    /// Prepare for rendering this frame; create all resources that will be
+    /// used during the next render that do not already exist.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: f32,
+        height: f32,
+        segments: &Segments,
+        paths: Vec<(Area, Path, u16)>,
+    ) {
+        let max_rows = device.limits().max_texture_dimension_2d;
+        let gradients = paths.iter().filter(|(_, path, _)| path.gradient.is_some()).count() as u32;
+        let rows = gradients.clamp(1, max_rows).next_power_of_two().min(max_rows);
+        if rows > self.ramps.height() {
+            self.ramps = Self::ramps(device, rows);
+            self.bind_group = Self::bind_group(device, &self.bind_group_layout, &self.sampler, &self.ramps);
+        }
+        let rows = self.ramps.height();
+
+        let mut texels: Vec<u8> = vec![];
+        let (vertices, indices, draws) = paths.into_iter().fold((vec![], vec![], vec![]), |mut a, (area, path, z)| {
+            let row = (texels.len() as u32 / (4 * RAMP_WIDTH)).min(rows - 1);
+            if let Some(gradient) = &path.gradient && texels.len() < (4 * RAMP_WIDTH * rows) as usize {
+                texels.extend((0..RAMP_WIDTH).flat_map(|i| {
+                    let c = gradient.color_at(i as f32 / (RAMP_WIDTH - 1) as f32);
+                    [c.0, c.1, c.2, c.3]
+                }));
+            }
+            let ramp = (row as f32 + 0.5) / rows as f32;
+            let (vertices, indices) = PathVertex::new(width, height, z, area, &path, ramp);
+            let (l, start) = (a.0.len() as u32, a.1.len() as u32);
+            a.0.extend(vertices);
+            a.1.extend(indices.into_iter().map(|i| l+i));
+            a.2.push((z, start..a.1.len() as u32));
+            a
+        });
+
+        if !texels.is_empty() {
+            let baked = texels.len() as u32 / (4 * RAMP_WIDTH);
+            queue.write_texture(
+                TexelCopyTextureInfo {
+                    texture: &self.ramps,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                &texels,
+                TexelCopyBufferLayout{
+                    offset: 0,
+                    bytes_per_row: Some(4 * RAMP_WIDTH),
+                    rows_per_image: Some(baked),
+                },
+                Extent3d{width: RAMP_WIDTH, height: baked, depth_or_array_layers: 1}
+            );
+        }
+
+        self.segments = segments.split(draws);
+        self.vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&vertices));
+        self.index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
+    }
+
+    /// Tessellates the fill of every clip pushed this frame, in order.
+    pub fn prepare_clips(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: f32,
+        height: f32,
+        clips: Vec<(Area, Clip)>,
+    ) {
+        self.clips.clear();
+        if self.clip_pipelines.is_none() {return;}
+        let (vertices, indices) = clips.into_iter().fold((vec![], vec![]), |mut a, (area, clip)| {
+            let (data, fill_rule) = clip.outline();
+            let path = Path{data, color: Color::WHITE, gradient: None, fill_rule, stroke: None};
+            let (vertices, indices) = PathVertex::new(width, height, 0, area, &path, 0.0);
+            let (l, start) = (a.0.len() as u32, a.1.len() as u32);
+            a.0.extend(vertices);
+            a.1.extend(indices.into_iter().map(|i| l+i));
+            self.clips.push(start..a.1.len() as u32);
+            a
+        });
+        self.clip_vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&vertices));
+        self.clip_index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&indices));
+    }
+
+    /// Render the paths of `segment` using caller provided render pass.
+    pub fn render(&self, render_pass: &mut RenderPass<'_>, segment: usize) {
+        let Some(indices) = self.segments.get(segment).filter(|r| !r.is_empty()) else {return};
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(indices.clone(), 0, 0..1);
+    }
+
+    /// Raises the clip level inside the clip at index `clip` where it equals the stencil
+    /// reference when `push`ing, and lowers it back to the reference otherwise.
+    pub fn render_clip(&self, render_pass: &mut RenderPass<'_>, clip: usize, push: bool) {
+        let (Some((increment, restore)), Some(indices)) = (&self.clip_pipelines, self.clips.get(clip)) else {return};
+        if indices.is_empty() {return;}
+        render_pass.set_pipeline(if push {increment} else {restore});
+        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+        render_pass.set_vertex_buffer(0, self.clip_vertex_buffer.as_ref().slice(..));
+        render_pass.set_index_buffer(self.clip_index_buffer.as_ref().slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(indices.clone(), 0, 0..1);
+    }
+}
@@ -0,0 +1,308 @@
+use wgpu::{PipelineCompilationOptions, PipelineLayout, BindGroupLayoutDescriptor, RenderPipelineDescriptor, PipelineLayoutDescriptor, TextureViewDimension, BindGroupLayoutEntry, TextureSampleType, MultisampleState, BindGroupLayout, RenderPipeline, PrimitiveState, FragmentState, TextureFormat, ShaderStages, BufferUsages, BufferBinding, BufferBindingType, IndexFormat, VertexState, BindingType, Device, Queue, BindGroupDescriptor, BindGroupEntry, BindingResource, ColorTargetState, ColorWrites, TextureDescriptor, TextureDimension, TextureUsages, TextureView, TextureViewDescriptor, Extent3d, CommandEncoder, RenderPassDescriptor, RenderPassColorAttachment, Operations, LoadOp, StoreOp, Sampler, SamplerBindingType, ShaderModuleDescriptor, ShaderSource, TexelCopyTextureInfo, TexelCopyBufferLayout, Origin3d, TextureAspect};
+
+use super::buffer::{DynamicBufferDescriptor, DynamicBuffer};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::RgbaImage;
+
+use super::vertex::{Vertex, PostVertex};
+
+/// A pass over a whole rendered frame, like [`Canvas::set_effects`](crate::Canvas) runs
+/// before presenting it.
+#[derive(Clone)]
+pub enum Effect {
+    /// Darkens toward the corners by a strength from 0.0 to 1.0, starting at a fraction of the
+    /// way from the center to a corner and fading in over another.
+    Vignette(f32, f32, f32),
+    /// Maps every color through a lookup table in sRGB, mixed in by a strength from 0.0 to 1.0.
+    /// The table is a strip of square slices, one for every blue from left to right, each with
+    /// red increasing to the right and green down, like a 256 by 16 pixel image of 16 slices.
+    /// Its height is the side of the slices and their count.
+    ColorGrade(Arc<RgbaImage>, f32),
+    /// Adds noise of an intensity, a fraction of the range of a color, that changes with the
+    /// seed.
+    FilmGrain(f32, f32),
+    /// A WGSL fragment shader and the bytes of its uniforms. The source follows
+    /// `renderer/post.wgsl`, which declares `PostOutput`, the frame so far as `t_frame` with
+    /// `s_frame`, and the helpers of the renderer such as `to_srgb`. It declares the uniforms
+    /// at `@group(1) @binding(0)` when there are any, and
+    /// `@fragment fn fs_main(in: PostOutput) -> @location(0) vec4<f32>`. It is compiled the
+    /// first time it runs, an invalid one failing like any other shader of the device.
+    Custom(Arc<str>, Vec<u8>),
+}
+
+impl Effect {
+    fn source(&self) -> &str {match self {
+        Effect::Vignette(..) => include_str!("post/vignette.wgsl"),
+        Effect::ColorGrade(..) => include_str!("post/color_grade.wgsl"),
+        Effect::FilmGrain(..) => include_str!("post/film_grain.wgsl"),
+        Effect::Custom(source, _) => source,
+    }}
+
+    fn uniforms(&self) -> Vec<u8> {match self {
+        Effect::Vignette(strength, start, fade) => bytemuck::cast_slice(&[*strength, *start, *fade, 0.0]).to_vec(),
+        Effect::ColorGrade(table, strength) => bytemuck::cast_slice(&[*strength, table.height() as f32, 0.0, 0.0]).to_vec(),
+        Effect::FilmGrain(intensity, seed) => bytemuck::cast_slice(&[*intensity, *seed, 0.0, 0.0]).to_vec(),
+        Effect::Custom(_, uniforms) => uniforms.clone(),
+    }}
+}
+
+/// Runs effects over a rendered frame, each reading the result of the last from one texture
+/// and drawing into another, the last into the target.
+pub struct PostRenderer {
+    texture_format: TextureFormat,
+    frame_layout: BindGroupLayout,
+    effect_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    sampler: Sampler,
+    /// By shader source, created with the first effect using it.
+    render_pipelines: HashMap<String, RenderPipeline>,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    uniform_buffer: DynamicBuffer,
+    /// Bound to the effects without a lookup table.
+    blank: TextureView,
+    /// The lookup tables of the last frame.
+    tables: Vec<(Arc<RgbaImage>, TextureView)>,
+}
+
+impl PostRenderer {
+    /// Create all unchanging resources here.
+    pub fn new(device: &Device, queue: &Queue, texture_format: &TextureFormat) -> Self {
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                multisampled: false,
+                view_dimension: TextureViewDimension::D2,
+                sample_type: TextureSampleType::Float{filterable: true},
+            },
+            count: None,
+        };
+        let frame_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                texture(0),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                }
+            ]
+        });
+        let effect_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor{
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture(1),
+            ]
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor{
+            label: None,
+            bind_group_layouts: &[&frame_layout, &effect_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut vertex_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        vertex_buffer.write_buffer(device, queue, bytemuck::cast_slice(&PostVertex::new()));
+
+        let mut index_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        });
+        index_buffer.write_buffer(device, queue, bytemuck::cast_slice(&[0u16, 1, 2, 1, 2, 3]));
+
+        let uniform_buffer = DynamicBuffer::new(device, &DynamicBufferDescriptor {
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let blank = table(device, queue, &RgbaImage::new(1, 1));
+
+        PostRenderer{
+            texture_format: *texture_format,
+            frame_layout,
+            effect_layout,
+            pipeline_layout,
+            sampler,
+            render_pipelines: HashMap::new(),
+            vertex_buffer,
+            index_buffer,
+            uniform_buffer,
+            blank,
+            tables: vec![],
+        }
+    }
+
+    /// Records a pass for every effect into `encoder`, the first reading `views[0]`, which
+    /// holds the frame, and the last drawing into `target`, going back and forth between
+    /// `views` in between. Both views need to be of `target`'s size and texture format.
+    pub fn render(&mut self, device: &Device, queue: &Queue, encoder: &mut CommandEncoder, effects: &[Effect], views: &[TextureView; 2], target: &TextureView) {
+        if effects.is_empty() {return;}
+        //Every effect's uniforms start at an offset uniform bindings allow, at least 16 bytes
+        let stride = |size: usize| size.max(16).next_multiple_of(16);
+        let align = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let mut uniforms = vec![];
+        let mut ranges = vec![];
+        for effect in effects {
+            let mut bytes = effect.uniforms();
+            bytes.resize(stride(bytes.len()), 0);
+            uniforms.resize(uniforms.len().next_multiple_of(align), 0);
+            ranges.push((uniforms.len() as u64, bytes.len() as u64));
+            uniforms.extend(bytes);
+        }
+        self.uniform_buffer.write_buffer(device, queue, &uniforms);
+
+        let tables = effects.iter().filter_map(|e| match e {
+            Effect::ColorGrade(table, _) => Some(table.clone()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        self.tables.retain(|(t, _)| tables.iter().any(|u| Arc::ptr_eq(t, u)));
+        for t in tables {
+            if !self.tables.iter().any(|(u, _)| Arc::ptr_eq(&t, u)) {
+                let view = table(device, queue, &t);
+                self.tables.push((t, view));
+            }
+        }
+
+        for effect in effects {
+            if !self.render_pipelines.contains_key(effect.source()) {
+                let pipeline = self.pipeline(device, effect.source());
+                self.render_pipelines.insert(effect.source().to_string(), pipeline);
+            }
+        }
+
+        let frames = views.each_ref().map(|view| device.create_bind_group(&BindGroupDescriptor{
+            label: None,
+            layout: &self.frame_layout,
+            entries: &[
+                BindGroupEntry{binding: 0, resource: BindingResource::TextureView(view)},
+                BindGroupEntry{binding: 1, resource: BindingResource::Sampler(&self.sampler)},
+            ]
+        }));
+        for (i, (effect, (offset, size))) in effects.iter().zip(ranges).enumerate() {
+            let table = match effect {
+                Effect::ColorGrade(table, _) => self.tables.iter().find(|(t, _)| Arc::ptr_eq(t, table)).map_or(&self.blank, |(_, v)| v),
+                _ => &self.blank,
+            };
+            let bind_group = device.create_bind_group(&BindGroupDescriptor{
+                label: None,
+                layout: &self.effect_layout,
+                entries: &[
+                    BindGroupEntry{binding: 0, resource: BindingResource::Buffer(BufferBinding{
+                        buffer: self.uniform_buffer.as_ref(),
+                        offset,
+                        size: std::num::NonZeroU64::new(size),
+                    })},
+                    BindGroupEntry{binding: 1, resource: BindingResource::TextureView(table)},
+                ]
+            });
+            let view = match i + 1 == effects.len() {
+                true => target,
+                false => &views[(i + 1) % 2],
+            };
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Post Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.render_pipelines[effect.source()]);
+            render_pass.set_bind_group(0, Some(&frames[i % 2]), &[]);
+            render_pass.set_bind_group(1, Some(&bind_group), &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().slice(..));
+            render_pass.set_index_buffer(self.index_buffer.as_ref().slice(..), IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+    }
+
+    fn pipeline(&self, device: &Device, source: &str) -> RenderPipeline {
+        let shader = device.create_shader_module(ShaderModuleDescriptor{
+            label: Some("post.wgsl"),
+            source: ShaderSource::Wgsl([include_str!("sdf.wgsl"), include_str!("post.wgsl"), source].concat().into())
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&self.pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[PostVertex::layout()]
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format: self.texture_format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })]
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None
+        })
+    }
+}
+
+/// A lookup table uploaded as sRGB, so it is sampled in the linear colors of the frame.
+fn table(device: &Device, queue: &Queue, image: &RgbaImage) -> TextureView {
+    let size = Extent3d{width: image.width().max(1), height: image.height().max(1), depth_or_array_layers: 1};
+    let texture = device.create_texture(&TextureDescriptor{
+        label: Some("Lookup table texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8UnormSrgb,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    if image.width() > 0 && image.height() > 0 {
+        queue.write_texture(
+            TexelCopyTextureInfo{texture: &texture, mip_level: 0, origin: Origin3d::ZERO, aspect: TextureAspect::All},
+            image,
+            TexelCopyBufferLayout{offset: 0, bytes_per_row: Some(4 * image.width()), rows_per_image: None},
+            size,
+        );
+    }
+    texture.create_view(&TextureViewDescriptor::default())
+}
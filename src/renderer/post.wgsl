@@ -0,0 +1,27 @@
+//Prepended to every effect after the signed distance helpers. An effect declares its uniforms
+//at group 1 binding 0 and `fs_main`, reading the frame so far from `t_frame`.
+
+struct PostInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>
+}
+
+struct PostOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>
+};
+
+@vertex
+fn vs_main(
+    post: PostInput,
+) -> PostOutput {
+    var out: PostOutput;
+    out.position = vec4<f32>(post.position, 0.0, 1.0);
+    out.uv = post.uv;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var s_frame: sampler;
@@ -0,0 +1,23 @@
+//Strength, and the texels a side of the slices of the table.
+@group(1) @binding(0)
+var<uniform> params: vec4<f32>;
+@group(1) @binding(1)
+var t_lut: texture_2d<f32>;
+
+//The table at the sRGB `c`, filtered within the two slices around its blue and between them.
+fn graded(c: vec3<f32>, n: f32) -> vec3<f32> {
+    let blue = c.b * (n - 1.0);
+    let slice = floor(blue);
+    let next = min(slice + 1.0, n - 1.0);
+    let inner = (c.rg * (n - 1.0) + 0.5) / vec2<f32>(n * n, n);
+    let a = textureSampleLevel(t_lut, s_frame, inner + vec2<f32>(slice / n, 0.0), 0.0).rgb;
+    let b = textureSampleLevel(t_lut, s_frame, inner + vec2<f32>(next / n, 0.0), 0.0).rgb;
+    return mix(a, b, blue - slice);
+}
+
+@fragment
+fn fs_main(in: PostOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_frame, s_frame, in.uv);
+    let c = to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+    return vec4<f32>(mix(color.rgb, graded(c, params.y), clamp(params.x, 0.0, 1.0)), color.a);
+}
@@ -0,0 +1,19 @@
+//Intensity and seed.
+@group(1) @binding(0)
+var<uniform> params: vec4<f32>;
+
+//Uniform noise in 0..1 for a pixel and seed.
+fn hash(p: vec2<f32>, seed: f32) -> f32 {
+    let q = fract(vec3<f32>(p.xyx + seed) * 0.1031);
+    let r = q + dot(q, q.yzx + 33.33);
+    return fract((r.x + r.y) * r.z);
+}
+
+//The grain is added in sRGB so it is as visible in the shadows as in the highlights.
+@fragment
+fn fs_main(in: PostOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_frame, s_frame, in.uv);
+    let grain = (hash(floor(in.position.xy), params.y) - 0.5) * params.x;
+    let c = to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0))) + grain;
+    return vec4<f32>(to_linear(clamp(c, vec3<f32>(0.0), vec3<f32>(1.0))), color.a);
+}
@@ -0,0 +1,11 @@
+//Strength, where it starts and how far it fades in, as fractions of the way to a corner.
+@group(1) @binding(0)
+var<uniform> params: vec4<f32>;
+
+@fragment
+fn fs_main(in: PostOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_frame, s_frame, in.uv);
+    let d = length(in.uv * 2.0 - 1.0) / sqrt(2.0);
+    let shade = 1.0 - params.x * smoothstep(params.y, params.y + max(params.z, 0.0001), d);
+    return vec4<f32>(color.rgb * shade, color.a);
+}
@@ -0,0 +1,211 @@
+//Shared signed distance helpers, prepended to the shaders that need them.
+//Distances are in pixels and negative inside the shape.
+
+//Abramowitz and Stegun 7.1.27, max error 5e-4
+fn erf(x: f32) -> f32 {
+    let a = abs(x);
+    let t = 1.0 + a*(0.278393 + a*(0.230389 + a*(0.000972 + a*0.078108)));
+    let t2 = t*t;
+    return sign(x) * (1.0 - 1.0/(t2*t2));
+}
+
+//Coverage of a pixel at distance `d` from an edge, blurred by a gaussian of radius `blur`
+fn coverage(d: f32, blur: f32) -> f32 {
+    let sigma = blur / 2.0;
+    if sigma > 0.0 {
+        return 0.5 - 0.5*erf(d / (sigma * sqrt(2.0)));
+    }
+    return clamp(0.5 - d, 0.0, 1.0);
+}
+
+fn sd_box(p: vec2<f32>, b: vec2<f32>, r: f32) -> f32 {
+    let q = abs(p) - b + vec2<f32>(r);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
+//Rounded box whose corners blend from circular arcs (smoothing 0) to superellipses (smoothing 1).
+//Smoothed corners spread over up to twice the radius with a higher exponent, keeping roughly the
+//same visual size while the curvature ramps up continuously from the straight edges.
+fn sd_smooth_box(p: vec2<f32>, b: vec2<f32>, r: f32, smoothing: f32) -> f32 {
+    let s = clamp(smoothing, 0.0, 1.0);
+    if s <= 0.0 || r <= 0.0 {
+        return sd_box(p, b, r);
+    }
+    let rs = min(r * (1.0 + s), min(b.x, b.y));
+    let n = 2.0 + 3.0 * s;
+    let q = abs(p) - b + vec2<f32>(rs);
+    let c = max(q, vec2<f32>(0.0)) / rs;
+    let corner = rs * pow(pow(c.x, n) + pow(c.y, n), 1.0 / n);
+    return corner + min(max(q.x, q.y), 0.0) - rs;
+}
+
+fn sd_ellipse(p: vec2<f32>, b: vec2<f32>) -> f32 {
+    let k0 = length(p / b);
+    let k1 = length(p / (b*b));
+    if k1 == 0.0 {
+        return -min(b.x, b.y);
+    }
+    return k0*(k0-1.0) / k1;
+}
+
+//Composites an inner shadow over `color`. `d` is the distance to the shape at the shadow's
+//offset, `shadow` holds the offset, blur and spread.
+fn inner_shadow(color: vec4<f32>, shadow_color: vec4<f32>, shadow: vec4<f32>, d: f32) -> vec4<f32> {
+    let sa = shadow_color[3] * (1.0 - coverage(d + shadow[3], shadow[2]));
+    let a = sa + color[3]*(1.0-sa);
+    if a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let rgb = (shadow_color.rgb*sa + color.rgb*color[3]*(1.0-sa)) / a;
+    return vec4<f32>(rgb, a);
+}
+
+//Shades a shape at signed distance `d`. The fill is hollowed out past `stroke` pixels when
+//`stroke` is positive and carries the inner shadow at distance `shadow_d`. `border` holds the
+//width of the border band and how far its outer edge sits past the shape. Fill and border
+//are split by coverage so their shared edge antialiases without a seam.
+fn fill(
+    color: vec4<f32>, d: f32, stroke: f32,
+    border_color: vec4<f32>, border: vec2<f32>,
+    shadow_color: vec4<f32>, shadow: vec4<f32>, shadow_d: f32
+) -> vec4<f32> {
+    let inner = border[1] - border[0];
+    let solid = coverage(d - inner, 0.0);
+    let edge = max(coverage(d - border[1], 0.0) - solid, 0.0);
+    var body = solid;
+    if stroke > 0.0 {
+        body = max(solid - coverage(d + stroke, 0.0), 0.0);
+    }
+
+    let c = inner_shadow(color, shadow_color, shadow, shadow_d - inner);
+    let fa = c[3] * body;
+    let ba = border_color[3] * edge;
+    let a = fa + ba;
+    if a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>((c.rgb*fa + border_color.rgb*ba) / a, min(a, 1.0));
+}
+
+const PI: f32 = 3.14159265358979;
+
+//Slice of the ellipse with radii `b`. `arc` holds the start and sweep, in radians clockwise
+//from the top, and 1.0 for rounded ends. A `thickness` of 0 makes a pie slice, anything else
+//a ring segment. Elliptical arcs are evaluated as circles and scaled, so their distance is approximate.
+fn sd_arc(p: vec2<f32>, b: vec2<f32>, thickness: f32, arc: vec3<f32>) -> f32 {
+    let r = max(min(b.x, b.y), 0.0001);
+    let pc = p * (r / max(b, vec2<f32>(0.0001)));
+    let mid = arc[0] + (arc[1] / 2.0);
+    //Mirror around the middle of the arc
+    let q = vec2<f32>(
+        abs(dot(pc, vec2<f32>(cos(mid), sin(mid)))),
+        dot(pc, vec2<f32>(sin(mid), -cos(mid)))
+    );
+    let half = clamp(arc[1] / 2.0, 0.0, PI);
+    let c = vec2<f32>(sin(half), cos(half));
+    let full = half >= PI;
+
+    if thickness <= 0.0 || thickness >= r {
+        let l = length(q) - r;
+        if full {
+            return l;
+        }
+        let m = length(q - c*clamp(dot(q, c), 0.0, r));
+        return max(l, m*sign(c.y*q.x - c.x*q.y));
+    }
+
+    let rb = thickness / 2.0;
+    let ra = r - rb;
+    let ring = abs(length(q) - ra) - rb;
+    if full {
+        return ring;
+    }
+    if arc[2] > 0.0 {
+        if c.y*q.x > c.x*q.y {
+            return length(q - c*ra) - rb;
+        }
+        return ring;
+    }
+    let m = length(q - c*max(dot(q, c), 0.0));
+    return max(ring, m*sign(c.y*q.x - c.x*q.y));
+}
+
+//Star inscribed in the ellipse with radii `b`, its first point at the top. `polygon` holds the
+//point count, the ratio of the inner to the outer radius and the corner radius. Regular polygons
+//are stars whose inner vertices sit on their edges.
+fn sd_polygon(p: vec2<f32>, b: vec2<f32>, polygon: vec3<f32>) -> f32 {
+    let r = max(min(b.x, b.y), 0.0001);
+    let pc = p * (r / max(b, vec2<f32>(0.0001)));
+    let n = max(polygon[0], 2.0);
+    let an = PI / n;
+
+    //Outer vertex on the y axis, inner vertex `an` radians clockwise from it
+    let outer = vec2<f32>(0.0, 1.0);
+    let inner = polygon[1] * vec2<f32>(sin(an), cos(an));
+    let e = inner - outer;
+    //Distance from the center to the edge, the shape is shrunk by the corner radius along it
+    let h = abs(e.x) / length(e);
+    let cr = clamp(polygon[2], 0.0, r*h*0.999);
+    let scale = r * (h - cr/r) / h;
+
+    //Fold into the first half sector
+    let q = vec2<f32>(pc.x, -pc.y);
+    let a = atan2(q.x, q.y);
+    let bn = abs(a - (2.0*an)*floor((a + an) / (2.0*an)));
+    let f = (length(q) / scale) * vec2<f32>(sin(bn), cos(bn));
+
+    let w = f - outer;
+    let t = clamp(dot(w, e) / dot(e, e), 0.0, 1.0);
+    let d = length(w - e*t) * sign(e.x*w.y - e.y*w.x);
+    return d*scale - cr;
+}
+
+//Coverage of the fragment at `position` by the clip of its area: the `bounds` corners rounded by
+//`radii` (clockwise from the top left) and rotated clockwise by `angle` radians about their center.
+fn clip_coverage(position: vec2<f32>, bounds: vec4<f32>, radii: vec4<f32>, angle: f32) -> f32 {
+    let half = (bounds.zw - bounds.xy) / 2.0;
+    let d = position - (bounds.xy + bounds.zw) / 2.0;
+    let s = sin(angle);
+    let c = cos(angle);
+    let p = vec2<f32>(d.x*c + d.y*s, d.y*c - d.x*s);
+    let side = select(radii.xw, radii.yz, p.x > 0.0);
+    let r = select(side.x, side.y, p.y > 0.0);
+    return coverage(sd_box(p, half, clamp(r, 0.0, min(half.x, half.y))), 0.0);
+}
+
+fn apply_clip(color: vec4<f32>, clipped: f32) -> vec4<f32> {
+    return vec4<f32>(color.rgb, color[3] * clipped);
+}
+
+//Colors cross to the fragment stage as half floats, which keeps the fill shaders within the
+//31 inter-stage components WebGL2 allows.
+fn pack_color(color: vec4<f32>) -> vec2<u32> {
+    return vec2<u32>(pack2x16float(color.rg), pack2x16float(color.ba));
+}
+
+fn unpack_color(color: vec2<u32>) -> vec4<f32> {
+    return vec4<f32>(unpack2x16float(color.x), unpack2x16float(color.y));
+}
+
+fn to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+//Filters the straight alpha `color` by the color matrix in `row` of `filters`, one column a
+//texel, in sRGB like the matrix is given in. Row 0 leaves it as it is.
+fn filtered(color: vec4<f32>, filters: texture_2d<f32>, row: f32) -> vec4<f32> {
+    let r = i32(row);
+    if r <= 0 {
+        return color;
+    }
+    let c = vec4<f32>(to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0))), color.a);
+    let out = textureLoad(filters, vec2<i32>(0, r), 0) * c.r + textureLoad(filters, vec2<i32>(1, r), 0) * c.g
+        + textureLoad(filters, vec2<i32>(2, r), 0) * c.b + textureLoad(filters, vec2<i32>(3, r), 0) * c.a
+        + textureLoad(filters, vec2<i32>(4, r), 0);
+    let f = clamp(out, vec4<f32>(0.0), vec4<f32>(1.0));
+    return vec4<f32>(to_linear(f.rgb), f.a);
+}
@@ -2,10 +2,18 @@
 
 use wgpu::{VertexBufferLayout, VertexStepMode, BufferAddress, VertexAttribute, VertexFormat};
 
-use crate::{RgbaImage, Area, Color};
+use crate::{RgbaImage, Area, Color, Shadow, Path, PathCommand, FillRule, LineCap, LineJoin, Gradient, GradientKind, GradientSpread};
+use lyon_tessellation as lyon;
 use crate::shape::Shape;
+use crate::transform::project;
 use std::sync::Arc;
 
+/// Converts an sRGB `Color` into the linear components the shaders blend with.
+pub fn linear(color: Color) -> [f32; 4] {
+    let c = |f: u8| if f == 0 {0.0} else {(((f as f32 / u8::MAX as f32) + 0.055) / 1.055).powf(2.4)};
+    [c(color.0), c(color.1), c(color.2), c(color.3)]
+}
+
 pub trait Vertex: std::fmt::Debug + bytemuck::Pod + bytemuck::Zeroable{
     fn attributes() -> Vec<VertexFormat> where Self: Sized;
 
@@ -31,9 +39,14 @@ pub trait Vertex: std::fmt::Debug + bytemuck::Pod + bytemuck::Zeroable{
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ShapeVertex {
     pub uv: [f32; 2],
-    pub position: [f32; 2],
+    /// Clip space position, `w` included so `uv` interpolates correctly under perspective.
+    pub position: [f32; 3],
     pub size: [f32; 2],
     pub bounds: [f32; 4],
+    /// Corner radii of the clip, clockwise from the top left.
+    pub clip: [f32; 4],
+    /// Rotation of the clip around the center of `bounds`, in radians.
+    pub clip_angle: f32,
     pub z_index: f32,
     pub stroke: f32
 }
@@ -41,44 +54,58 @@ pub struct ShapeVertex {
 impl Vertex for ShapeVertex {
     fn attributes() -> Vec<VertexFormat> {
         vec![
-            VertexFormat::Float32x2, VertexFormat::Float32x2, VertexFormat::Float32x2,
-            VertexFormat::Float32x4, VertexFormat::Float32, VertexFormat::Float32
+            VertexFormat::Float32x2, VertexFormat::Float32x3, VertexFormat::Float32x2,
+            VertexFormat::Float32x4, VertexFormat::Float32x4, VertexFormat::Float32,
+            VertexFormat::Float32, VertexFormat::Float32
         ]
     }
 }
 
 impl ShapeVertex {
-    pub fn transform_point(width: f32, height: f32, p: [f32; 2]) -> [f32; 2] {
-        let w = |x: f32| ((x / width) * 2.0) - 1.0;
-        let h = |y: f32| 1.0 - ((y / height) * 2.0);
-        [w(p[0]), h(p[1])]
+    /// The clip of `area` in pixels as the corners of its bounds, its corner radii and its
+    /// angle. The fragment shaders test them against the framebuffer position.
+    pub fn clip(width: f32, height: f32, area: Area) -> ([f32; 4], [f32; 4], f32) {
+        let bounds = area.bounds.unwrap_or((0.0, 0.0, width, height));
+        let clip = area.clip.filter(|_| area.bounds.is_some()).unwrap_or_default();
+        ([bounds.0, bounds.1, bounds.0+bounds.2, bounds.1+bounds.3], clip.radii, clip.angle.to_radians())
     }
-    pub fn transform(width: f32, height: f32, p: [[f32; 2]; 4]) -> [[f32; 2]; 4] {
-        [
-            Self::transform_point(width, height, p[0]),
-            Self::transform_point(width, height, p[1]),
-            Self::transform_point(width, height, p[2]),
-            Self::transform_point(width, height, p[3])
-        ]
+
+    /// Maps `p` through the row major 3x3 `matrix` into clip space, leaving the divide by `w`
+    /// to the rasterizer.
+    pub fn transform_point(width: f32, height: f32, matrix: [f32; 9], p: [f32; 2]) -> [f32; 3] {
+        let (x, y, w) = project(matrix, (p[0], p[1]));
+        [(x / width) * 2.0 - w, w - (y / height) * 2.0, w]
     }
 
     pub fn new(width: f32, height: f32, z: u16, area: Area, shape: Shape) -> [ShapeVertex; 4] {
+        Self::with_margin(width, height, z, area, shape, 0.0)
+    }
+
+    /// Grows the quad by `margin` on every side while keeping `uv` relative to the shape,
+    /// so the fragment shader can also shade pixels outside of it.
+    pub fn with_margin(width: f32, height: f32, z: u16, area: Area, shape: Shape, margin: f32) -> [ShapeVertex; 4] {
+        let (sin, cos) = shape.angle().to_radians().sin_cos();
+        let grow = |p: [f32; 2], dx: f32, dy: f32| [p[0] + dx * cos - dy * sin, p[1] + dx * sin + dy * cos];
         let op = shape.positions(area.offset);
-        let positions = Self::transform(width, height, op);
+        let op = [
+            grow(op[0], -margin, -margin), grow(op[1], margin, -margin), grow(op[2], -margin, margin), grow(op[3], margin, margin)
+        ];
+        let matrix = area.projection();
+        let positions = op.map(|p| Self::transform_point(width, height, matrix, p));
         let size = shape.wh();
         let stroke = shape.stroke();
-        let bounds = area.bounds.unwrap_or((0.0, 0.0, width, height));
-        let [bx,  by]  = Self::transform_point(width, height, [bounds.0, bounds.1]);
-        let [bx2, by2] = Self::transform_point(width, height, [bounds.0+bounds.2, bounds.1+bounds.3]);
-        let bounds = [bx, by, bx2, by2];
+        let (bounds, clip, clip_angle) = Self::clip(width, height, area);
 
         let z_index = z as f32 / u16::MAX as f32;
 
+        let (x, y) = (-margin, -margin);
+        let (x2, y2) = (size[0]+margin, size[1]+margin);
+
         [
-            ShapeVertex{uv: [0.0, 0.0],         position: positions[0], size, bounds, z_index, stroke},
-            ShapeVertex{uv: [size[0], 0.0],      position: positions[1], size, bounds, z_index, stroke},
-            ShapeVertex{uv: [0.0, size[1]],      position: positions[2], size, bounds, z_index, stroke},
-            ShapeVertex{uv: [size[0], size[1]],  position: positions[3], size, bounds, z_index, stroke},
+            ShapeVertex{uv: [x,  y],  position: positions[0], size, bounds, clip, clip_angle, z_index, stroke},
+            ShapeVertex{uv: [x2, y],  position: positions[1], size, bounds, clip, clip_angle, z_index, stroke},
+            ShapeVertex{uv: [x,  y2], position: positions[2], size, bounds, clip, clip_angle, z_index, stroke},
+            ShapeVertex{uv: [x2, y2], position: positions[3], size, bounds, clip, clip_angle, z_index, stroke},
         ]
     }
 }
@@ -88,18 +115,294 @@ impl ShapeVertex {
 pub struct RoundedRectangleVertex {
     pub shape: ShapeVertex,
     pub corner_radius: f32,
+    pub smoothing: f32,
 }
 
 impl Vertex for RoundedRectangleVertex {
     fn attributes() -> Vec<VertexFormat> {
-        [ShapeVertex::attributes(), vec![VertexFormat::Float32]].concat()
+        [ShapeVertex::attributes(), vec![VertexFormat::Float32, VertexFormat::Float32]].concat()
     }
 }
 
 impl RoundedRectangleVertex {
-    pub fn new(width: f32, height: f32, z: u16, area: Area, shape: Shape, corner_radius: f32) -> [RoundedRectangleVertex; 4] {
-        ShapeVertex::new(width, height, z, area, shape).into_iter().map(|shape|
-            RoundedRectangleVertex{shape, corner_radius}
+    pub fn new(shape: [ShapeVertex; 4], corner_radius: f32, smoothing: f32) -> [RoundedRectangleVertex; 4] {
+        let smoothing = smoothing.clamp(0.0, 1.0);
+        shape.into_iter().map(|shape|
+            RoundedRectangleVertex{shape, corner_radius, smoothing}
+        ).collect::<Vec<_>>().try_into().unwrap()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ArcVertex {
+    pub shape: ShapeVertex,
+    pub arc: [f32; 3],
+}
+
+impl Vertex for ArcVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        [ShapeVertex::attributes(), vec![VertexFormat::Float32x3]].concat()
+    }
+}
+
+impl ArcVertex {
+    pub fn new(shape: [ShapeVertex; 4], arc: Shape) -> [ArcVertex; 4] {
+        let arc = Self::arc(arc);
+        shape.into_iter().map(|shape|
+            ArcVertex{shape, arc}
+        ).collect::<Vec<_>>().try_into().unwrap()
+    }
+
+    /// Start and sweep in radians, and 1.0 for rounded ends.
+    pub fn arc(shape: Shape) -> [f32; 3] {match shape {
+        Shape::Arc(_, _, _, _, _, rounded) => {
+            let (start, sweep) = shape.sweep().unwrap();
+            [start.to_radians(), sweep.to_radians(), if rounded {1.0} else {0.0}]
+        },
+        _ => [0.0, std::f32::consts::TAU, 0.0]
+    }}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PolygonVertex {
+    pub shape: ShapeVertex,
+    pub polygon: [f32; 3],
+}
+
+impl Vertex for PolygonVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        [ShapeVertex::attributes(), vec![VertexFormat::Float32x3]].concat()
+    }
+}
+
+impl PolygonVertex {
+    pub fn new(shape: [ShapeVertex; 4], polygon: Shape) -> [PolygonVertex; 4] {
+        let polygon = Self::polygon(polygon);
+        shape.into_iter().map(|shape|
+            PolygonVertex{shape, polygon}
+        ).collect::<Vec<_>>().try_into().unwrap()
+    }
+
+    /// Point count, inner radius ratio and corner radius.
+    pub fn polygon(shape: Shape) -> [f32; 3] {
+        let (n, ratio) = shape.star().unwrap_or((3, 0.5));
+        let corner_radius = match shape {
+            Shape::Polygon(_, _, _, _, c) | Shape::Star(_, _, _, _, _, c) => c.max(0.0),
+            _ => 0.0
+        };
+        [n as f32, ratio, corner_radius]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PathVertex {
+    pub position: [f32; 3],
+    /// Position before the transform of the area, where gradients are painted.
+    pub local: [f32; 2],
+    pub bounds: [f32; 4],
+    pub clip: [f32; 4],
+    pub clip_angle: f32,
+    pub z_index: f32,
+    /// Kind (0 solid, 1 linear, 2 radial), gradient ramp row, spread and an unused slot.
+    pub paint: [f32; 4],
+    /// Start and end of a linear gradient or the center and radius of a radial one.
+    pub geometry: [f32; 4],
+    /// Rows of the matrix taking untransformed pixels to gradient space.
+    pub inverse: [[f32; 3]; 2],
+}
+
+impl Vertex for PathVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        vec![
+            VertexFormat::Float32x3, VertexFormat::Float32x2, VertexFormat::Float32x4, VertexFormat::Float32x4,
+            VertexFormat::Float32, VertexFormat::Float32, VertexFormat::Float32x4, VertexFormat::Float32x4,
+            VertexFormat::Float32x3, VertexFormat::Float32x3
+        ]
+    }
+}
+
+impl PathVertex {
+    /// Tolerance in pixels when flattening curves.
+    const TOLERANCE: f32 = 0.1;
+
+    /// Tessellates the fill and the stroke of `path` into indexed triangles. `ramp` is the
+    /// texture coordinate of the row its gradient was baked into, if it has one.
+    pub fn new(width: f32, height: f32, z: u16, area: Area, path: &Path, ramp: f32) -> (Vec<ColorVertex<PathVertex>>, Vec<u32>) {
+        let outline = Self::outline(area, path);
+        //Tessellated before the transform, so curves are flattened finer where it scales them up
+        let matrix = area.projection();
+        let tolerance = Self::TOLERANCE / area.transform.map(|t| t.max_scale()).unwrap_or(1.0).max(f32::EPSILON);
+        let (bounds, clip, clip_angle) = ShapeVertex::clip(width, height, area);
+        let z_index = z as f32 / u16::MAX as f32;
+        let solid = ([0.0; 4], [0.0; 4], [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut append = |buffers: lyon::VertexBuffers<[f32; 2], u32>, color: Color, (paint, geometry, inverse)| {
+            let l = vertices.len() as u32;
+            let color = linear(color);
+            vertices.extend(buffers.vertices.into_iter().map(|p| ColorVertex{
+                shape: PathVertex{
+                    position: ShapeVertex::transform_point(width, height, matrix, p), local: p, bounds, clip, clip_angle, z_index, paint, geometry, inverse
+                },
+                color
+            }));
+            indices.extend(buffers.indices.into_iter().map(|i| l+i));
+        };
+
+        let fill = match &path.gradient {
+            Some(gradient) => Some((Color::WHITE, Self::gradient(area, gradient, ramp))),
+            None => (path.color.3 > 0).then_some((path.color, solid))
+        };
+        if let Some((color, paint)) = fill {
+            let rule = match path.fill_rule {
+                FillRule::NonZero => lyon::FillRule::NonZero,
+                FillRule::EvenOdd => lyon::FillRule::EvenOdd,
+            };
+            let options = lyon::FillOptions::tolerance(tolerance).with_fill_rule(rule);
+            let mut buffers = lyon::VertexBuffers::new();
+            let mut builder = lyon::BuffersBuilder::new(&mut buffers, |v: lyon::FillVertex| v.position().to_array());
+            if lyon::FillTessellator::new().tessellate_path(&outline, &options, &mut builder).is_ok() {
+                append(buffers, color, paint);
+            }
+        }
+
+        if let Some(stroke) = path.stroke.filter(|s| s.width > 0.0 && s.color.3 > 0) {
+            let cap = match stroke.cap {
+                LineCap::Butt => lyon::LineCap::Butt,
+                LineCap::Round => lyon::LineCap::Round,
+                LineCap::Square => lyon::LineCap::Square,
+            };
+            let join = match stroke.join {
+                LineJoin::Miter => lyon::LineJoin::Miter,
+                LineJoin::Round => lyon::LineJoin::Round,
+                LineJoin::Bevel => lyon::LineJoin::Bevel,
+            };
+            let options = lyon::StrokeOptions::tolerance(tolerance)
+                .with_line_width(stroke.width)
+                .with_line_cap(cap)
+                .with_line_join(join)
+                .with_miter_limit(4.0);
+            let mut buffers = lyon::VertexBuffers::new();
+            let mut builder = lyon::BuffersBuilder::new(&mut buffers, |v: lyon::StrokeVertex| v.position().to_array());
+            if lyon::StrokeTessellator::new().tessellate_path(&outline, &options, &mut builder).is_ok() {
+                append(buffers, stroke.color, solid);
+            }
+        }
+
+        (vertices, indices)
+    }
+
+    fn gradient(area: Area, gradient: &Gradient, ramp: f32) -> ([f32; 4], [f32; 4], [[f32; 3]; 2]) {
+        let spread = match gradient.spread {
+            GradientSpread::Pad => 0.0,
+            GradientSpread::Reflect => 1.0,
+            GradientSpread::Repeat => 2.0,
+        };
+        let (kind, geometry) = match gradient.kind {
+            GradientKind::Linear(start, end) => (1.0, [start.0, start.1, end.0, end.1]),
+            GradientKind::Radial(center, radius) => (2.0, [center.0, center.1, radius, 0.0]),
+        };
+        //Gradient space to pixels, then inverted
+        let [a, b, c, d, e, f] = gradient.transform;
+        let (e, f) = (e + area.offset.0, f + area.offset.1);
+        let det = a*d - b*c;
+        let det = if det.abs() < f32::EPSILON {f32::EPSILON} else {det};
+        let inverse = [
+            [d / det, -c / det, (c*f - d*e) / det],
+            [-b / det, a / det, (b*e - a*f) / det],
+        ];
+        ([kind, ramp, spread, 0.0], geometry, inverse)
+    }
+
+    /// Rebuilds the path commands in pixels, starting a new subpath wherever one is drawn after a close.
+    fn outline(area: Area, path: &Path) -> lyon::path::Path {
+        let point = |p: (f32, f32)| lyon::math::point(area.offset.0+p.0, area.offset.1+p.1);
+        let mut builder = lyon::path::Path::builder();
+        let mut open = false;
+        let mut start = point((0.0, 0.0));
+        let mut current = start;
+        for command in &path.data.0 {
+            if !open && !matches!(command, PathCommand::MoveTo(_) | PathCommand::Close) {
+                builder.begin(current);
+                open = true;
+            }
+            match *command {
+                PathCommand::MoveTo(p) => {
+                    if open {builder.end(false);}
+                    start = point(p);
+                    builder.begin(start);
+                    open = true;
+                },
+                PathCommand::LineTo(p) => {builder.line_to(point(p));},
+                PathCommand::QuadTo(c, p) => {builder.quadratic_bezier_to(point(c), point(p));},
+                PathCommand::CubicTo(c1, c2, p) => {builder.cubic_bezier_to(point(c1), point(c2), point(p));},
+                PathCommand::Close => {
+                    if open {builder.end(true);}
+                    open = false;
+                    current = start;
+                    continue;
+                }
+            }
+            current = match *command {
+                PathCommand::MoveTo(p) | PathCommand::LineTo(p) | PathCommand::QuadTo(_, p) | PathCommand::CubicTo(_, _, p) => point(p),
+                PathCommand::Close => start,
+            };
+        }
+        if open {builder.end(false);}
+        builder.build()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowVertex {
+    pub shape: ShapeVertex,
+    pub params: [f32; 4],
+    pub blur: f32,
+    pub spread: f32,
+    pub kind: u32,
+}
+
+impl Vertex for ShadowVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        [ShapeVertex::attributes(), vec![
+            VertexFormat::Float32x4, VertexFormat::Float32, VertexFormat::Float32, VertexFormat::Uint32
+        ]].concat()
+    }
+}
+
+impl ShadowVertex {
+    pub const ELLIPSE: u32 = 0;
+    pub const RECTANGLE: u32 = 1;
+    pub const ARC: u32 = 2;
+    pub const POLYGON: u32 = 3;
+
+    pub fn new(width: f32, height: f32, z: u16, area: Area, shadow: Shadow) -> [ShadowVertex; 4] {
+        //The per kind parameters match the ones given to the fill shaders
+        let (params, kind) = match shadow.shape {
+            Shape::Ellipse(_, _, _) => ([0.0; 4], Self::ELLIPSE),
+            Shape::Rectangle(_, _, _) => ([0.0; 4], Self::RECTANGLE),
+            Shape::RoundedRectangle(_, _, _, c) => ([c, 0.0, 0.0, 0.0], Self::RECTANGLE),
+            Shape::SmoothRoundedRectangle(_, _, _, c, sm) => ([c, sm.clamp(0.0, 1.0), 0.0, 0.0], Self::RECTANGLE),
+            Shape::Arc(..) => {
+                let [start, sweep, rounded] = ArcVertex::arc(shadow.shape);
+                ([shadow.shape.stroke(), start, sweep, rounded], Self::ARC)
+            },
+            Shape::Polygon(..) | Shape::Star(..) => {
+                let [n, ratio, corner_radius] = PolygonVertex::polygon(shadow.shape);
+                ([n, ratio, corner_radius, 0.0], Self::POLYGON)
+            }
+        };
+        let blur = shadow.blur.max(0.0);
+        //Three sigmas past the spread silhouette, plus a pixel for antialiasing
+        let margin = shadow.spread.max(0.0) + 1.5 * blur + 1.0;
+        let area = Area{offset: (area.offset.0+shadow.offset.0, area.offset.1+shadow.offset.1), ..area};
+        ShapeVertex::with_margin(width, height, z, area, shadow.shape, margin).into_iter().map(|shape|
+            ShadowVertex{shape, params, blur, spread: shadow.spread, kind}
         ).collect::<Vec<_>>().try_into().unwrap()
     }
 }
@@ -119,59 +422,158 @@ impl<V: Vertex> Vertex for ColorVertex<V> {
 
 impl<V: Vertex> ColorVertex<V> {
     pub fn new(shape: [V; 4], color: Color) -> [ColorVertex<V>; 4] {
-        let c = |f: u8| if f == 0 {0.0} else {(((f as f32 / u8::MAX as f32) + 0.055) / 1.055).powf(2.4)};
-        let color = [c(color.0), c(color.1), c(color.2), c(color.3)];
+        let color = linear(color);
         shape.into_iter().map(|shape|
             ColorVertex{shape, color}
         ).collect::<Vec<_>>().try_into().unwrap()
     }
 }
 
+#[repr(packed, C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FillVertex<V: Vertex = ShapeVertex> {
+    pub color: ColorVertex<V>,
+    pub border_color: [f32; 4],
+    pub border: [f32; 2],
+    pub inner_shadow_color: [f32; 4],
+    pub inner_shadow: [f32; 4],
+}
+
+impl<V: Vertex> Vertex for FillVertex<V> {
+    fn attributes() -> Vec<VertexFormat> {
+        [ColorVertex::<V>::attributes(), vec![
+            VertexFormat::Float32x4, VertexFormat::Float32x2, VertexFormat::Float32x4, VertexFormat::Float32x4
+        ]].concat()
+    }
+}
+
+impl<V: Vertex> FillVertex<V> {
+    pub fn new(shape: [V; 4], fill: &crate::Shape) -> [FillVertex<V>; 4] {
+        let (border_color, border) = fill.border.map(|b| (
+            linear(b.color), [b.width.max(0.0), b.outset()]
+        )).unwrap_or_default();
+        let (inner_shadow_color, inner_shadow) = fill.inner_shadow.map(|s| (
+            linear(s.color), [s.offset.0, s.offset.1, s.blur.max(0.0), s.spread]
+        )).unwrap_or_default();
+        ColorVertex::new(shape, fill.color).into_iter().map(|color|
+            FillVertex{color, border_color, border, inner_shadow_color, inner_shadow}
+        ).collect::<Vec<_>>().try_into().unwrap()
+    }
+}
+
 #[repr(packed, C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ImageVertex<V: Vertex = ShapeVertex> {
     pub color: ColorVertex<V>,
-    pub texture: [f32; 2]
+    pub texture: [f32; 2],
+    /// Row of the color matrix in the filter table, 0 for none.
+    pub filter: f32,
 }
 
 impl<V: Vertex> Vertex for ImageVertex<V> {
     fn attributes() -> Vec<VertexFormat> {
-        [ColorVertex::<V>::attributes(), vec![VertexFormat::Float32x2]].concat()
+        [ColorVertex::<V>::attributes(), vec![VertexFormat::Float32x2, VertexFormat::Float32]].concat()
     }
 }
 
 impl<V: Vertex> ImageVertex<V> {
-    pub fn new(shape: [V; 4], image: &Arc<RgbaImage>, size: (f32, f32), color: Option<Color>) -> [ImageVertex<V>; 4] {
-        let mut x  = 0.0;
-        let mut y  = 0.0;
-        let mut x2 = 1.0;
-        let mut y2 = 1.0;
-
-        let wi = image.width()  as f32;
-        let hi = image.height() as f32;
-        let ws = size.0;
-        let hs = size.1;
-
-        let wr = ws / wi;
-        let hr = hs / hi;
-
-        if hr > wr {
-            let d = (1.0 - (wr / hr)) / 2.0;
-            x  = d;
-            x2 = 1.0 - d;
-        } else {
-            let d = (1.0 - (hr / wr)) / 2.0;
-            y  = d;
-            y2 = 1.0 - d;
-        }
+    pub fn new(shape: [V; 4], image: &Arc<RgbaImage>, size: (f32, f32), color: Option<Color>, filter: f32) -> [ImageVertex<V>; 4] {
+        let [x, y, x2, y2] = crate::Image::crop(image, size);
 
         let color = ColorVertex::new(shape, color.unwrap_or_default());
 
         [
-            ImageVertex{color: color[0], texture: [x,  y]},
-            ImageVertex{color: color[1], texture: [x2, y]},
-            ImageVertex{color: color[2], texture: [x,  y2]},
-            ImageVertex{color: color[3], texture: [x2, y2]},
+            ImageVertex{color: color[0], texture: [x,  y], filter},
+            ImageVertex{color: color[1], texture: [x2, y], filter},
+            ImageVertex{color: color[2], texture: [x,  y2], filter},
+            ImageVertex{color: color[3], texture: [x2, y2], filter},
         ]
     }
-}
\ No newline at end of file
+}
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LayerVertex {
+    pub uv: [f32; 2],
+    pub position: [f32; 3],
+    pub bounds: [f32; 4],
+    pub clip: [f32; 4],
+    pub clip_angle: f32,
+    pub z_index: f32,
+    /// Opacity, 1.0 when the layer has a mask, the formula of a blend mode reading back what
+    /// is under it and the row of its color matrix in the filter table.
+    pub paint: [f32; 4],
+    /// Where the texture read back from the target starts on it.
+    pub backdrop: [f32; 2],
+}
+
+impl Vertex for LayerVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        vec![
+            VertexFormat::Float32x2, VertexFormat::Float32x3, VertexFormat::Float32x4, VertexFormat::Float32x4,
+            VertexFormat::Float32, VertexFormat::Float32, VertexFormat::Float32x4, VertexFormat::Float32x2
+        ]
+    }
+}
+
+impl LayerVertex {
+    /// Covers the pixels `(x, y, width, height)` a layer was drawn from, placed on the target
+    /// through the row major 3x3 `matrix` and clipped like `area`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(width: f32, height: f32, z: u16, area: Area, region: (i32, i32, u32, u32), matrix: [f32; 9], paint: [f32; 4], backdrop: [f32; 2]) -> [LayerVertex; 4] {
+        let (x, y) = (region.0 as f32, region.1 as f32);
+        let (x2, y2) = (x + region.2 as f32, y + region.3 as f32);
+        let (bounds, clip, clip_angle) = ShapeVertex::clip(width, height, area);
+        let z_index = z as f32 / u16::MAX as f32;
+        [([0.0, 0.0], [x, y]), ([1.0, 0.0], [x2, y]), ([0.0, 1.0], [x, y2]), ([1.0, 1.0], [x2, y2])].map(|(uv, p)| LayerVertex{
+            uv,
+            position: ShapeVertex::transform_point(width, height, matrix, p),
+            bounds, clip, clip_angle, z_index, paint, backdrop
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BlurVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    /// The uv of one texel of the texture sampled, along the direction blurred.
+    pub step: [f32; 2],
+    /// The gaussian sigma in texels, and the texels a side taken together when downsampling.
+    pub paint: [f32; 2],
+}
+
+impl Vertex for BlurVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        vec![VertexFormat::Float32x2, VertexFormat::Float32x2, VertexFormat::Float32x2, VertexFormat::Float32x2]
+    }
+}
+
+impl BlurVertex {
+    /// Covers the whole target.
+    pub fn new(step: [f32; 2], paint: [f32; 2]) -> [BlurVertex; 4] {
+        [([-1.0, 1.0], [0.0, 0.0]), ([1.0, 1.0], [1.0, 0.0]), ([-1.0, -1.0], [0.0, 1.0]), ([1.0, -1.0], [1.0, 1.0])]
+            .map(|(position, uv)| BlurVertex{position, uv, step, paint})
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+}
+
+impl Vertex for PostVertex {
+    fn attributes() -> Vec<VertexFormat> {
+        vec![VertexFormat::Float32x2, VertexFormat::Float32x2]
+    }
+}
+
+impl PostVertex {
+    /// Covers the whole target.
+    pub fn new() -> [PostVertex; 4] {
+        [([-1.0, 1.0], [0.0, 0.0]), ([1.0, 1.0], [1.0, 0.0]), ([-1.0, -1.0], [0.0, 1.0]), ([1.0, -1.0], [1.0, 1.0])]
+            .map(|(position, uv)| PostVertex{position, uv})
+    }
+}
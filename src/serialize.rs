@@ -0,0 +1,112 @@
+use std::fmt;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor, SeqAccess};
+
+use crate::Font;
+
+/// Fonts are written as their [`Font::id`] and resolved against the fonts loaded in this
+/// process, so they have to be loaded with [`Font::from_bytes`] before deserializing.
+impl Serialize for Font {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for Font {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u64::deserialize(deserializer)?;
+        Font::find(id).ok_or_else(|| de::Error::custom(format!("unknown font {id:016x}, load it before deserializing")))
+    }
+}
+
+/// Images are embedded as PNG bytes.
+pub(crate) mod image {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use ::image::{RgbaImage, ImageFormat};
+
+    pub fn serialize<S: Serializer>(image: &Arc<RgbaImage>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&png)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<RgbaImage>, D::Error> {
+        let png = deserializer.deserialize_bytes(Bytes)?;
+        let image = ::image::load_from_memory_with_format(&png, ImageFormat::Png).map_err(de::Error::custom)?;
+        Ok(Arc::new(image.into_rgba8()))
+    }
+
+    /// Accepts native byte strings as well as the integer sequences text formats write them as.
+    struct Bytes;
+    impl<'de> Visitor<'de> for Bytes {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("PNG bytes")
+        }
+
+        fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(bytes)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::{Instruction, Item, Area, Shape, ShapeType, Image, Text, Color, Align, RgbaImage, Layer, BlendMode};
+    use super::*;
+
+    #[test]
+    fn instructions_round_trip_through_a_text_format() {
+        let font = Font::from_bytes(include_bytes!("../emoji.ttf")).unwrap();
+        let image = Arc::new(RgbaImage::from_fn(2, 3, |x, y| ::image::Rgba([x as u8, y as u8, 9, 255])));
+        let instructions = vec![
+            Instruction(Area{blend: BlendMode::Screen, ..Area::new((1.0, 2.0), Some((0.0, 0.0, 10.0, 10.0)))},
+                Item::Shape(Shape::new(ShapeType::SmoothRoundedRectangle(0.0, (5.0, 5.0), 0.0, 2.0, 0.5), Color::WHITE))),
+            Instruction(Area::default(), Item::Image(Image::new(ShapeType::Rectangle(0.0, (2.0, 3.0), 0.0), image, None))),
+            Instruction(Area::default(), Item::PushLayer(Layer::new(0.25))),
+            Instruction(Area::default(), Item::Text(Text::new("hi", font.clone(), 12.0, Color::BLACK, Align::Right))),
+            Instruction(Area::default(), Item::PopLayer),
+        ];
+        let json = serde_json::to_string(&instructions).unwrap();
+        assert!(json.contains(&font.id().to_string()));
+        assert_eq!(serde_json::from_str::<Vec<Instruction>>(&json).unwrap(), instructions);
+    }
+
+    #[test]
+    fn fields_added_later_have_defaults() {
+        let area: Area = serde_json::from_str(r#"{"offset": [1.0, 2.0], "bounds": null, "clip": null, "transform": null}"#).unwrap();
+        assert_eq!(area, Area::new((1.0, 2.0), None));
+        let shape: Shape = serde_json::from_str(
+            r#"{"shape": {"Ellipse": [0.0, [4.0, 4.0], 0.0]}, "color": [1, 2, 3, 4], "border": null, "inner_shadow": null}"#
+        ).unwrap();
+        assert_eq!(shape, Shape::new(ShapeType::Ellipse(0.0, (4.0, 4.0), 0.0), Color(1, 2, 3, 4)));
+        let text = Text::new("hi", Font::from_bytes(include_bytes!("../emoji.ttf")).unwrap(), 12.0, Color::BLACK, Align::Left);
+        let mut json = serde_json::to_value(&text).unwrap();
+        json.as_object_mut().unwrap().remove("angle").unwrap();
+        assert_eq!(serde_json::from_value::<Text>(json).unwrap(), text);
+    }
+
+    #[test]
+    fn unknown_fonts_and_broken_images_are_errors() {
+        let error = serde_json::from_str::<Font>("12345").unwrap_err().to_string();
+        assert!(error.contains("unknown font 0000000000003039"), "{error}");
+        let error = serde_json::from_str::<Image>(r#"{"shape": {"Rectangle": [0.0, [1.0, 1.0], 0.0]}, "image": [1, 2, 3], "color": null}"#);
+        assert!(error.is_err());
+    }
+}
@@ -1,40 +1,123 @@
+use std::f32::consts::PI;
+
+use crate::path::{PathData, PathCommand, arc_point, elliptic_arc};
+
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     Ellipse(f32, (f32, f32), f32),
     Rectangle(f32, (f32, f32), f32),
     RoundedRectangle(f32, (f32, f32), f32, f32),
+    /// A rounded rectangle with smoothed corners:
+    /// `SmoothRoundedRectangle(stroke, size, angle, corner_radius, smoothing)`.
+    ///
+    /// `smoothing` blends the corners from circular arcs (0.0) to continuous-curvature
+    /// superellipse corners (1.0), which spread over up to twice the corner radius.
+    SmoothRoundedRectangle(f32, (f32, f32), f32, f32, f32),
+    /// A slice of the ellipse: `Arc(thickness, size, angle, start, sweep, rounded_ends)`.
+    ///
+    /// `start` and `sweep` are in degrees, clockwise from the top, a negative `sweep` running
+    /// counterclockwise. A `thickness` of 0 draws a pie slice, anything else a ring segment of
+    /// that thickness, with round caps if `rounded_ends`.
+    Arc(f32, (f32, f32), f32, f32, f32, bool),
+    /// A regular polygon inscribed in the ellipse: `Polygon(stroke, size, angle, sides, corner_radius)`.
+    ///
+    /// The first vertex points to the top.
+    Polygon(f32, (f32, f32), f32, u32, f32),
+    /// A star inscribed in the ellipse: `Star(stroke, size, angle, points, inner_ratio, corner_radius)`.
+    ///
+    /// The inner vertices sit at `inner_ratio` of the outer radius, the first point is at the top.
+    Star(f32, (f32, f32), f32, u32, f32, f32),
 }
 impl Shape {
     pub fn scale(self, scale: f32) -> Shape {match self {
         Shape::Ellipse(s, (w, h), a) => Shape::Ellipse(scale*s, (scale*w, scale*h), a),
         Shape::Rectangle(s, (w, h), a) => Shape::Rectangle(scale*s, (scale*w, scale*h), a),
         Shape::RoundedRectangle(s, (w, h), a, c) => Shape::RoundedRectangle(scale*s, (scale*w, scale*h), a, scale*c),
+        Shape::SmoothRoundedRectangle(s, (w, h), a, c, sm) => Shape::SmoothRoundedRectangle(scale*s, (scale*w, scale*h), a, scale*c, sm),
+        Shape::Arc(s, (w, h), a, st, sw, r) => Shape::Arc(scale*s, (scale*w, scale*h), a, st, sw, r),
+        Shape::Polygon(s, (w, h), a, n, c) => Shape::Polygon(scale*s, (scale*w, scale*h), a, n, scale*c),
+        Shape::Star(s, (w, h), a, n, r, c) => Shape::Star(scale*s, (scale*w, scale*h), a, n, r, scale*c),
     }}
 
     pub fn stroke(&self) -> f32 {match self {
         Shape::Ellipse(s, (w, h), _) => s.min(w.min(*h)),
         Shape::Rectangle(s, (w, h), _) => s.min(w.min(*h)),
         Shape::RoundedRectangle(s, (w, h), _, _) => s.min(w.min(*h)),
+        Shape::SmoothRoundedRectangle(s, (w, h), _, _, _) => s.min(w.min(*h)),
+        Shape::Arc(s, (w, h), _, _, _, _) => s.min(w.min(*h)),
+        Shape::Polygon(s, (w, h), _, _, _) => s.min(w.min(*h)),
+        Shape::Star(s, (w, h), _, _, _, _) => s.min(w.min(*h)),
     }}
 
     pub fn angle(&self) -> f32 {match self {
         Shape::Ellipse(_, _, a) => *a,
         Shape::Rectangle(_, _, a) => *a,
         Shape::RoundedRectangle(_, _, a, _) => *a,
+        Shape::SmoothRoundedRectangle(_, _, a, _, _) => *a,
+        Shape::Arc(_, _, a, _, _, _) => *a,
+        Shape::Polygon(_, _, a, _, _) => *a,
+        Shape::Star(_, _, a, _, _, _) => *a,
     }}
 
     pub fn wh(&self) -> [f32; 2] {match self {
         Shape::Ellipse(_, (w, h), _) => [*w, *h],
         Shape::Rectangle(_, (w, h), _) => [*w, *h],
         Shape::RoundedRectangle(_, (w, h), _, _) => [*w, *h],
+        Shape::SmoothRoundedRectangle(_, (w, h), _, _, _) => [*w, *h],
+        Shape::Arc(_, (w, h), _, _, _, _) => [*w, *h],
+        Shape::Polygon(_, (w, h), _, _, _) => [*w, *h],
+        Shape::Star(_, (w, h), _, _, _, _) => [*w, *h],
     }}
 
+    /// Number of points and the ratio of the inner to the outer radius of a polygon or star.
+    ///
+    /// A regular polygon is a star whose inner vertices sit on the middle of its edges.
+    pub fn star(&self) -> Option<(u32, f32)> {match self {
+        Shape::Polygon(_, _, _, n, _) => {
+            let n = (*n).max(3);
+            Some((n, (std::f32::consts::PI / n as f32).cos()))
+        },
+        Shape::Star(_, _, _, n, r, _) => Some(((*n).max(2), r.clamp(0.0, 1.0))),
+        _ => None
+    }}
+
+    /// Start and sweep in degrees of an arc, covering the same slice with a sweep from 0 up to a
+    /// full turn: a negative sweep runs back from `start`.
+    pub(crate) fn sweep(&self) -> Option<(f32, f32)> {match *self {
+        Shape::Arc(_, _, _, start, sweep, _) => {
+            let sweep = sweep.clamp(-360.0, 360.0);
+            Some(if sweep < 0.0 {(start + sweep, -sweep)} else {(start, sweep)})
+        },
+        _ => None
+    }}
+
+    /// Vertices of a polygon or star relative to its center, before rotation.
+    pub(crate) fn vertices(&self) -> Vec<[f32; 2]> {
+        let Some((n, ratio)) = self.star() else {return vec![]};
+        let [w, h] = self.wh();
+        (0..2*n).map(|i| {
+            let r = if i % 2 == 0 {1.0} else {ratio};
+            let t = i as f32 * std::f32::consts::PI / n as f32;
+            [r * w * 0.5 * t.sin(), -r * h * 0.5 * t.cos()]
+        }).collect()
+    }
+
+    /// Extent of the rotated vertices of a polygon or star, relative to its center.
+    fn vertex_bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let (sin, cos) = self.angle().to_radians().sin_cos();
+        self.vertices().into_iter().map(|[x, y]| [x * cos - y * sin, x * sin + y * cos]).fold(
+            ([f32::MAX; 2], [f32::MIN; 2]),
+            |(min, max), p| ([min[0].min(p[0]), min[1].min(p[1])], [max[0].max(p[0]), max[1].max(p[1])])
+        )
+    }
+
     pub fn size(&self) -> (f32, f32) {
         let theta = self.angle().to_radians();
         let cos = theta.cos().abs();
         let sin = theta.sin().abs();
         match self {
-            Shape::Ellipse(_, (w, h), _) => {
+            Shape::Ellipse(_, (w, h), _) | Shape::Arc(_, (w, h), _, _, _, _) => {
                 let rx = w * 0.5;
                 let ry = h * 0.5;
                 let rx2 = rx * rx;
@@ -46,7 +129,7 @@ impl Shape {
 
                 (bb_width, bb_height)
             }
-            Shape::Rectangle(_, (w, h), _) | Shape::RoundedRectangle(_, (w, h), _, _) => {
+            Shape::Rectangle(_, (w, h), _) | Shape::RoundedRectangle(_, (w, h), _, _) | Shape::SmoothRoundedRectangle(_, (w, h), _, _, _) => {
                 let half_w_proj = (w * 0.5) * cos + (h * 0.5) * sin;
                 let half_h_proj = (w * 0.5) * sin + (h * 0.5) * cos;
 
@@ -54,9 +137,112 @@ impl Shape {
                 let bb_height = 2.0 * half_h_proj;
                 (bb_width, bb_height)
             },
+            Shape::Polygon(..) | Shape::Star(..) => {
+                let (min, max) = self.vertex_bounds();
+                (max[0] - min[0], max[1] - min[1])
+            }
         }
     }
 
+    /// Center of the shape when its bounding box starts at `offset`.
+    pub fn center(&self, offset: (f32, f32)) -> (f32, f32) {
+        let corners = self.positions(offset);
+        (
+            corners.iter().map(|p| p[0]).sum::<f32>() / 4.0,
+            corners.iter().map(|p| p[1]).sum::<f32>() / 4.0,
+        )
+    }
+
+    /// Outline of the filled shape around the origin before rotation, grown by `grow` on every
+    /// side (shrunk when negative). Stroked shapes are outlined by their outer edge, except ring
+    /// segments which keep their hole.
+    ///
+    /// Corner smoothing and the corner radius of polygons and stars are not followed.
+    pub fn outline(&self, grow: f32) -> PathData {
+        let [w, h] = self.wh();
+        let (gw, gh) = ((w + 2.0*grow).max(0.0), (h + 2.0*grow).max(0.0));
+        match *self {
+            Shape::Ellipse(..) => PathData::ellipse(0.0, 0.0, gw/2.0, gh/2.0),
+            Shape::Rectangle(..) => PathData::rectangle(-gw/2.0, -gh/2.0, gw, gh, 0.0, 0.0),
+            Shape::RoundedRectangle(_, _, _, r) | Shape::SmoothRoundedRectangle(_, _, _, r, _) => {
+                let r = (r + grow).clamp(0.0, gw.min(gh)/2.0);
+                PathData::rectangle(-gw/2.0, -gh/2.0, gw, gh, r, r)
+            },
+            Shape::Arc(..) => {
+                let (start, sweep) = self.sweep().unwrap();
+                let outer = (gw/2.0, gh/2.0);
+                let thickness = self.stroke();
+                let full = sweep >= 360.0;
+                let mut commands = match full {
+                    true => vec![PathCommand::MoveTo(arc_point((0.0, 0.0), outer, start))],
+                    false => vec![PathCommand::MoveTo((0.0, 0.0)), PathCommand::LineTo(arc_point((0.0, 0.0), outer, start))],
+                };
+                commands.extend(elliptic_arc((0.0, 0.0), outer, start, sweep));
+                if thickness > 0.0 && thickness < w.min(h)/2.0 {
+                    let inner = ((gw/2.0 - thickness - 2.0*grow).max(0.0), (gh/2.0 - thickness - 2.0*grow).max(0.0));
+                    if full {
+                        commands.push(PathCommand::Close);
+                        commands.push(PathCommand::MoveTo(arc_point((0.0, 0.0), inner, start + sweep)));
+                    } else {
+                        commands.remove(0);
+                        commands[0] = PathCommand::MoveTo(arc_point((0.0, 0.0), outer, start));
+                        commands.push(PathCommand::LineTo(arc_point((0.0, 0.0), inner, start + sweep)));
+                    }
+                    commands.extend(elliptic_arc((0.0, 0.0), inner, start + sweep, -sweep));
+                }
+                commands.push(PathCommand::Close);
+                PathData(commands)
+            },
+            Shape::Polygon(..) | Shape::Star(..) => {
+                let (sx, sy) = (if w > 0.0 {gw/w} else {0.0}, if h > 0.0 {gh/h} else {0.0});
+                let mut commands = self.vertices().into_iter().enumerate().map(|(i, [x, y])| match i {
+                    0 => PathCommand::MoveTo((x*sx, y*sy)),
+                    _ => PathCommand::LineTo((x*sx, y*sy)),
+                }).collect::<Vec<_>>();
+                commands.push(PathCommand::Close);
+                PathData(commands)
+            }
+        }
+    }
+
+    /// Signed distance from `p`, relative to the center before rotation, to the filled shape:
+    /// negative inside. Matches the distance the shaders draw with, corner smoothing and
+    /// polygon corner radii included.
+    pub fn distance(&self, p: (f32, f32)) -> f32 {
+        let [w, h] = self.wh();
+        let b = (w / 2.0, h / 2.0);
+        match *self {
+            Shape::Ellipse(..) => sd_ellipse(p, b),
+            Shape::Rectangle(..) => sd_box(p, b, 0.0),
+            Shape::RoundedRectangle(_, _, _, r) => sd_box(p, b, r.clamp(0.0, b.0.min(b.1))),
+            Shape::SmoothRoundedRectangle(_, _, _, r, smoothing) => sd_smooth_box(p, b, r.clamp(0.0, b.0.min(b.1)), smoothing),
+            Shape::Arc(_, _, _, _, _, rounded) => {
+                let (start, sweep) = self.sweep().unwrap();
+                sd_arc(p, b, self.stroke(), start.to_radians(), sweep.to_radians(), rounded)
+            },
+            Shape::Polygon(_, _, _, _, c) | Shape::Star(_, _, _, _, _, c) => {
+                let (n, ratio) = self.star().unwrap();
+                sd_polygon(p, b, n as f32, ratio, c.max(0.0))
+            }
+        }
+    }
+
+    /// Maps `point` into the space of [`Shape::distance`] for the shape placed at `offset`.
+    pub fn local(&self, offset: (f32, f32), point: (f32, f32)) -> (f32, f32) {
+        let (cx, cy) = self.center(offset);
+        let (sin, cos) = self.angle().to_radians().sin_cos();
+        let (dx, dy) = (point.0 - cx, point.1 - cy);
+        (dx * cos + dy * sin, dy * cos - dx * sin)
+    }
+
+    /// Whether `point` is on the shape placed at `offset`, leaving out the hole of stroked
+    /// shapes. Arcs are never hollow, their stroke being the thickness of the ring.
+    pub fn contains(&self, offset: (f32, f32), point: (f32, f32)) -> bool {
+        let d = self.distance(self.local(offset, point));
+        let stroke = self.stroke();
+        d <= 0.0 && (stroke <= 0.0 || matches!(self, Shape::Arc(..)) || d >= -stroke)
+    }
+
     pub fn positions(&self, offset: (f32, f32)) -> [[f32; 2]; 4] {
         let theta = self.angle().to_radians();
         let cos = theta.cos();
@@ -81,11 +267,11 @@ impl Shape {
         ];
 
         let shift = match self {
-            Shape::Rectangle(_, _, _) | Shape::RoundedRectangle(_, _, _, _) => {
+            Shape::Rectangle(_, _, _) | Shape::RoundedRectangle(_, _, _, _) | Shape::SmoothRoundedRectangle(_, _, _, _, _) => {
                 let min = positions.into_iter().reduce(|r, i| [r[0].min(i[0]), r[1].min(i[1])]).unwrap();
                 [(offset.0-min[0]), (offset.1-min[1])]
             },
-            Shape::Ellipse(_, _, _) => {
+            Shape::Ellipse(_, _, _) | Shape::Arc(_, _, _, _, _, _) => {
                 let rx2 = hw * hw;
                 let ry2 = hh * hh;
 
@@ -95,9 +281,174 @@ impl Shape {
                 let min_x = cx - half_w;
                 let min_y = cy - half_h;
                 [offset.0-min_x, offset.1-min_y]
+            },
+            Shape::Polygon(..) | Shape::Star(..) => {
+                let (min, _) = self.vertex_bounds();
+                [offset.0-(cx+min[0]), offset.1-(cy+min[1])]
             }
         };
         positions.iter_mut().for_each(|p| {p[0] += shift[0]; p[1] += shift[1]});
         positions
     }
 }
+
+//CPU versions of the signed distance functions in renderer/sdf.wgsl, see there for details.
+
+fn length(x: f32, y: f32) -> f32 {(x*x + y*y).sqrt()}
+
+pub(crate) fn sd_box(p: (f32, f32), b: (f32, f32), r: f32) -> f32 {
+    let q = (p.0.abs() - b.0 + r, p.1.abs() - b.1 + r);
+    length(q.0.max(0.0), q.1.max(0.0)) + q.0.max(q.1).min(0.0) - r
+}
+
+fn sd_smooth_box(p: (f32, f32), b: (f32, f32), r: f32, smoothing: f32) -> f32 {
+    let s = smoothing.clamp(0.0, 1.0);
+    if s <= 0.0 || r <= 0.0 {return sd_box(p, b, r);}
+    let rs = (r * (1.0 + s)).min(b.0.min(b.1));
+    let n = 2.0 + 3.0 * s;
+    let q = (p.0.abs() - b.0 + rs, p.1.abs() - b.1 + rs);
+    let c = (q.0.max(0.0) / rs, q.1.max(0.0) / rs);
+    let corner = rs * (c.0.powf(n) + c.1.powf(n)).powf(1.0 / n);
+    corner + q.0.max(q.1).min(0.0) - rs
+}
+
+fn sd_ellipse(p: (f32, f32), b: (f32, f32)) -> f32 {
+    if b.0 <= 0.0 || b.1 <= 0.0 {return f32::MAX;}
+    let k0 = length(p.0 / b.0, p.1 / b.1);
+    let k1 = length(p.0 / (b.0*b.0), p.1 / (b.1*b.1));
+    if k1 == 0.0 {return -b.0.min(b.1);}
+    k0*(k0-1.0) / k1
+}
+
+fn sd_arc(p: (f32, f32), b: (f32, f32), thickness: f32, start: f32, sweep: f32, rounded: bool) -> f32 {
+    let r = b.0.min(b.1).max(0.0001);
+    let pc = (p.0 * r / b.0.max(0.0001), p.1 * r / b.1.max(0.0001));
+    let mid = start + sweep / 2.0;
+    let q = ((pc.0 * mid.cos() + pc.1 * mid.sin()).abs(), pc.0 * mid.sin() - pc.1 * mid.cos());
+    let half = (sweep / 2.0).clamp(0.0, PI);
+    let c = (half.sin(), half.cos());
+    let full = half >= PI;
+    let side = (c.1*q.0 - c.0*q.1).signum();
+
+    if thickness <= 0.0 || thickness >= r {
+        let l = length(q.0, q.1) - r;
+        if full {return l;}
+        let t = (q.0*c.0 + q.1*c.1).clamp(0.0, r);
+        return l.max(length(q.0 - c.0*t, q.1 - c.1*t) * side);
+    }
+
+    let rb = thickness / 2.0;
+    let ra = r - rb;
+    let ring = (length(q.0, q.1) - ra).abs() - rb;
+    if full {return ring;}
+    if rounded {
+        if c.1*q.0 > c.0*q.1 {return length(q.0 - c.0*ra, q.1 - c.1*ra) - rb;}
+        return ring;
+    }
+    let t = (q.0*c.0 + q.1*c.1).max(0.0);
+    ring.max(length(q.0 - c.0*t, q.1 - c.1*t) * side)
+}
+
+fn sd_polygon(p: (f32, f32), b: (f32, f32), n: f32, ratio: f32, corner_radius: f32) -> f32 {
+    let r = b.0.min(b.1).max(0.0001);
+    let pc = (p.0 * r / b.0.max(0.0001), p.1 * r / b.1.max(0.0001));
+    let n = n.max(2.0);
+    let an = PI / n;
+
+    let inner = (ratio * an.sin(), ratio * an.cos());
+    let e = (inner.0, inner.1 - 1.0);
+    let h = e.0.abs() / length(e.0, e.1);
+    let cr = corner_radius.clamp(0.0, r*h*0.999);
+    let scale = r * (h - cr/r) / h;
+
+    let q = (pc.0, -pc.1);
+    let a = q.0.atan2(q.1);
+    let bn = (a - (2.0*an)*((a + an) / (2.0*an)).floor()).abs();
+    let l = length(q.0, q.1) / scale;
+    let w = (l * bn.sin(), l * bn.cos() - 1.0);
+    let t = ((w.0*e.0 + w.1*e.1) / (e.0*e.0 + e.1*e.1)).clamp(0.0, 1.0);
+    let d = length(w.0 - e.0*t, w.1 - e.1*t) * (e.0*w.1 - e.1*w.0).signum();
+    d*scale - cr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_sweeps_cover_the_same_slice() {
+        let forward = Shape::Arc(0.0, (100.0, 100.0), 0.0, 270.0, 90.0, false);
+        let backward = Shape::Arc(0.0, (100.0, 100.0), 0.0, 0.0, -90.0, false);
+        assert_eq!(backward.sweep(), Some((-90.0, 90.0)));
+        for point in [(-30.0, -30.0), (30.0, -30.0), (-30.0, 30.0), (-45.0, -5.0)] {
+            assert!((forward.distance(point) - backward.distance(point)).abs() < 1e-3, "at {point:?}");
+        }
+        assert!(backward.contains((0.0, 0.0), (20.0, 20.0)));
+        assert!(!backward.contains((0.0, 0.0), (80.0, 20.0)));
+        let (_, _, w, h) = backward.outline(0.0).bounds();
+        assert!(w > 49.0 && h > 49.0);
+    }
+
+    #[test]
+    fn polygons_and_stars_point_up() {
+        let square = Shape::Polygon(0.0, (20.0, 20.0), 0.0, 4, 0.0);
+        let vertices = square.vertices();
+        assert_eq!(vertices.len(), 8);
+        assert!(vertices[0][0].abs() < 1e-4 && (vertices[0][1] + 10.0).abs() < 1e-4);
+        assert!(square.distance((0.0, -10.0)).abs() < 1e-3);
+        assert!(square.distance((0.0, 0.0)) < 0.0);
+        assert!(square.distance((8.0, -8.0)) > 0.0);
+        //Fewer than three sides is still a triangle
+        assert_eq!(Shape::Polygon(0.0, (20.0, 20.0), 0.0, 1, 0.0).star().map(|s| s.0), Some(3));
+
+        let star = Shape::Star(0.0, (20.0, 20.0), 0.0, 5, 0.4, 0.0);
+        assert!(star.distance((0.0, -9.0)) < 0.0);
+        assert!(star.distance((0.0, -10.0)).abs() < 1e-3);
+        //Between the top two points lies the notch
+        let (sin, cos) = 36.0_f32.to_radians().sin_cos();
+        assert!(star.distance((9.0 * sin, -9.0 * cos)) > 0.0);
+        assert!(star.distance((3.0 * sin, -3.0 * cos)) < 0.0);
+        //Rounded corners pull the tips in
+        assert!(Shape::Star(0.0, (20.0, 20.0), 0.0, 5, 0.4, 2.0).distance((0.0, -9.5)) > 0.0);
+    }
+
+    #[test]
+    fn polygon_bounds_follow_their_rotated_vertices() {
+        let triangle = Shape::Polygon(0.0, (20.0, 20.0), 0.0, 3, 0.0);
+        let (w, h) = triangle.size();
+        assert!((w - 20.0 * 0.75_f32.sqrt()).abs() < 1e-3 && (h - 15.0).abs() < 1e-3);
+        let (x, y, bw, bh) = triangle.outline(0.0).bounds();
+        assert!((bw - w).abs() < 1e-3 && (bh - h).abs() < 1e-3);
+        assert!((x + w / 2.0).abs() < 1e-3 && (y + 10.0).abs() < 1e-3);
+        let turned = Shape::Polygon(0.0, (20.0, 20.0), 180.0, 3, 0.0);
+        assert!((turned.size().1 - 15.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn smoothing_spreads_corners_without_moving_edges() {
+        let rounded = Shape::RoundedRectangle(0.0, (100.0, 60.0), 0.0, 10.0);
+        let smooth = |smoothing| Shape::SmoothRoundedRectangle(0.0, (100.0, 60.0), 0.0, 10.0, smoothing);
+        for point in [(0.0, 0.0), (50.0, 0.0), (0.0, -30.0), (45.0, -25.0), (60.0, 40.0)] {
+            assert!((smooth(0.0).distance(point) - rounded.distance(point)).abs() < 1e-4, "at {point:?}");
+        }
+        //Edge midpoints stay put, the corner fills out on its diagonal and starts further along the edges
+        assert!(smooth(1.0).distance((50.0, 0.0)).abs() < 1e-4);
+        assert!(smooth(1.0).distance((0.0, -30.0)).abs() < 1e-4);
+        let diagonal = (48.0, -28.0);
+        assert!(smooth(1.0).distance(diagonal) < smooth(0.5).distance(diagonal));
+        assert!(smooth(0.5).distance(diagonal) < rounded.distance(diagonal));
+        let edge = (49.5, -22.0);
+        assert!(smooth(1.0).distance(edge) > smooth(0.5).distance(edge));
+        assert!(smooth(0.5).distance(edge) > rounded.distance(edge));
+        assert_eq!(smooth(3.0).distance(edge), smooth(1.0).distance(edge));
+        //The radius is capped at half the shorter side, however smooth
+        assert!(Shape::SmoothRoundedRectangle(0.0, (20.0, 20.0), 0.0, 50.0, 1.0).distance((0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn sweeps_are_capped_at_a_full_turn() {
+        assert_eq!(Shape::Arc(0.0, (10.0, 10.0), 0.0, 30.0, 720.0, false).sweep(), Some((30.0, 360.0)));
+        assert_eq!(Shape::Arc(0.0, (10.0, 10.0), 0.0, 30.0, -720.0, false).sweep(), Some((-330.0, 360.0)));
+        assert_eq!(Shape::Ellipse(0.0, (10.0, 10.0), 0.0).sweep(), None);
+    }
+}
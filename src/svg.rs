@@ -0,0 +1,834 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::Engine;
+
+use crate::{
+    Instruction, Item, Area, Color, Path, PathData, PathCommand, PathError, FillRule, Stroke, LineCap, LineJoin,
+    Gradient, GradientKind, GradientSpread, Image, ShapeType, Text, Font, Align, RgbaImage
+};
+
+mod export;
+
+use crate::transform::{IDENTITY, multiply, apply, determinant};
+
+/// Most elements `use` references can expand to in a document, stopping those nesting
+/// references to references from growing exponentially.
+const MAX_USED: usize = 100_000;
+
+/// Why an SVG document could not be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SvgError {
+    /// The document is not well formed XML.
+    Xml(String),
+    /// The root element is not `<svg>`.
+    NotSvg,
+    /// The `d` attribute of a `<path>` is malformed.
+    Path(PathError),
+    /// An embedded `<image>` could not be decoded.
+    Image(String),
+    /// The `<use>` elements expand to more than 100 000 elements.
+    TooManyUses,
+}
+
+impl std::fmt::Display for SvgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {match self {
+        SvgError::Xml(e) => write!(f, "invalid xml: {e}"),
+        SvgError::NotSvg => write!(f, "the root element is not <svg>"),
+        SvgError::Path(e) => write!(f, "invalid path data: {e}"),
+        SvgError::Image(e) => write!(f, "invalid embedded image: {e}"),
+        SvgError::TooManyUses => write!(f, "<use> elements expand to more than {MAX_USED} elements"),
+    }}
+}
+
+impl std::error::Error for SvgError {}
+
+impl From<PathError> for SvgError {
+    fn from(e: PathError) -> Self {SvgError::Path(e)}
+}
+
+/// A simple SVG document converted into renderable instructions.
+///
+/// Supports `rect`, `circle`, `ellipse`, `line`, `polyline`, `polygon`, `path`, `g` and `use`
+/// with transforms, fill and stroke paints and opacities, linear and radial gradients, embedded
+/// (`data:`) images and single line `text` when a font is given. Shapes become [`Path`]s, so they
+/// stay crisp at any scale. Group opacity is applied to each child separately and focal points
+/// of radial gradients are ignored.
+#[derive(Debug, Clone)]
+pub struct Svg {
+    size: (f32, f32),
+    instructions: Vec<Instruction>,
+}
+
+impl Svg {
+    /// Parses `source`, drawing `<text>` with `font` or skipping it when there is none.
+    pub fn parse(source: &str, font: Option<Font>) -> Result<Self, SvgError> {
+        let root = Node::parse(source)?;
+        if root.name != "svg" {return Err(SvgError::NotSvg);}
+
+        let view_box = root.attribute("viewBox").map(numbers).filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0);
+        //Percentages of the (unknown) viewport fall back to the default size
+        let dimension = |name: &str, default: f32| root.attribute(name)
+            .filter(|v| !v.trim_end().ends_with('%'))
+            .and_then(|v| length(v, 0.0, 16.0))
+            .unwrap_or(default);
+        let (size, transform) = match view_box {
+            Some(v) => ((v[2], v[3]), [1.0, 0.0, 0.0, 1.0, -v[0], -v[1]]),
+            None => ((dimension("width", 300.0), dimension("height", 150.0)), IDENTITY)
+        };
+
+        let mut ids = HashMap::new();
+        root.ids(&mut ids);
+        let mut importer = Importer{ids, font, viewport: size, instructions: vec![], uses: vec![], used: 0};
+        importer.children(&root, transform, &Style::default())?;
+        Ok(Svg{size, instructions: importer.instructions})
+    }
+
+    /// Size of the document in its own units (the `viewBox`, or `width` and `height`).
+    pub fn size(&self) -> (f32, f32) {self.size}
+
+    /// The document fitted (and centered) into the `size` box at `offset`, clipped to it.
+    pub fn instructions(&self, offset: (f32, f32), size: (f32, f32)) -> Vec<Instruction> {
+        let (w, h) = self.size;
+        let scale = if w > 0.0 && h > 0.0 {(size.0 / w).min(size.1 / h)} else {1.0};
+        let shift = (offset.0 + (size.0 - w*scale) / 2.0, offset.1 + (size.1 - h*scale) / 2.0);
+        self.instructions.iter().cloned().map(|mut instruction| {
+            instruction.scale(scale);
+            instruction.0.offset = (instruction.0.offset.0 + shift.0, instruction.0.offset.1 + shift.1);
+            instruction.0.bounds = Some((offset.0, offset.1, size.0, size.1));
+            instruction
+        }).collect()
+    }
+}
+
+struct Node {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Node>,
+    text: String,
+}
+
+impl Node {
+    fn parse(source: &str) -> Result<Node, SvgError> {
+        use xmlparser::{Token, ElementEnd};
+        let xml = |e: xmlparser::Error| SvgError::Xml(e.to_string());
+        let mut stack: Vec<Node> = vec![];
+        let mut root = None;
+        for token in xmlparser::Tokenizer::from(source) {
+            match token.map_err(xml)? {
+                Token::ElementStart{prefix, local, ..} => {
+                    let name = if prefix.is_empty() {local.to_string()} else {format!("{prefix}:{local}")};
+                    stack.push(Node{name, attributes: HashMap::new(), children: vec![], text: String::new()});
+                },
+                Token::Attribute{prefix, local, value, ..} => if let Some(node) = stack.last_mut() {
+                    let name = if prefix.is_empty() {local.to_string()} else {format!("{prefix}:{local}")};
+                    node.attributes.insert(name, unescape(&value));
+                },
+                Token::ElementEnd{end, span} if end != ElementEnd::Open => {
+                    let node = stack.pop().ok_or(SvgError::Xml(format!("unexpected closing tag at {}", span.start())))?;
+                    if let ElementEnd::Close(prefix, local) = end {
+                        let name = if prefix.is_empty() {local.to_string()} else {format!("{prefix}:{local}")};
+                        if name != node.name {
+                            return Err(SvgError::Xml(format!("expected </{}> but found </{name}> at {}", node.name, span.start())));
+                        }
+                    }
+                    match stack.last_mut() {
+                        Some(parent) => {
+                            parent.text.push_str(&node.text);
+                            parent.children.push(node);
+                        },
+                        None => root = Some(node),
+                    }
+                },
+                Token::Text{text} => if let Some(node) = stack.last_mut() {node.text.push_str(&unescape(&text))},
+                Token::Cdata{text, ..} => if let Some(node) = stack.last_mut() {node.text.push_str(&text)},
+                _ => {}
+            }
+        }
+        match stack.last() {
+            Some(node) => Err(SvgError::Xml(format!("unclosed element <{}>", node.name))),
+            None => root.ok_or(SvgError::Xml("missing root element".to_string()))
+        }
+    }
+
+    /// A presentation attribute, with declarations in `style` taking precedence.
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get("style").and_then(|style| style.split(';').rev().find_map(|declaration| {
+            let (key, value) = declaration.split_once(':')?;
+            (key.trim() == name).then_some(value.trim())
+        })).or_else(|| self.attributes.get(name).map(|v| v.trim()))
+    }
+
+    fn href(&self) -> Option<&str> {
+        self.attributes.get("href").or_else(|| self.attributes.get("xlink:href")).map(|v| v.trim())
+    }
+
+    fn ids<'a>(&'a self, ids: &mut HashMap<String, &'a Node>) {
+        if let Some(id) = self.attributes.get("id") {ids.insert(id.clone(), self);}
+        self.children.iter().for_each(|child| child.ids(ids));
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {break};
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").map(|h| u32::from_str_radix(h, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32)
+        };
+        match c {
+            Some(c) => {result.push(c); rest = &rest[end+1..];},
+            None => {result.push('&'); rest = &rest[1..];}
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Paint {
+    None,
+    Color(Color),
+    CurrentColor,
+    /// A reference to a gradient, with the color to use when it does not exist.
+    Url(String, Option<Box<Paint>>),
+}
+
+impl Paint {
+    fn parse(value: &str) -> Option<Paint> {
+        let value = value.trim();
+        if let Some(rest) = value.strip_prefix("url(") {
+            let (url, fallback) = rest.split_once(')')?;
+            let id = url.trim().trim_matches(|c| c == '\'' || c == '"').strip_prefix('#')?.to_string();
+            return Some(Paint::Url(id, Paint::parse(fallback).map(Box::new)));
+        }
+        match value {
+            "none" => Some(Paint::None),
+            "currentColor" => Some(Paint::CurrentColor),
+            _ => color(value).map(Paint::Color)
+        }
+    }
+}
+
+/// Inherited presentation attributes.
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Paint,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Paint,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    cap: LineCap,
+    join: LineJoin,
+    color: Color,
+    font_size: f32,
+    anchor: Align,
+    /// Product of the `opacity` of every ancestor.
+    opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {Style{
+        fill: Paint::Color(Color::BLACK),
+        fill_opacity: 1.0,
+        fill_rule: FillRule::NonZero,
+        stroke: Paint::None,
+        stroke_opacity: 1.0,
+        stroke_width: 1.0,
+        cap: LineCap::Butt,
+        join: LineJoin::Miter,
+        color: Color::BLACK,
+        font_size: 16.0,
+        anchor: Align::Left,
+        opacity: 1.0,
+    }}
+}
+
+impl Style {
+    fn inherit(&self, node: &Node, viewport: (f32, f32)) -> Style {
+        let mut style = self.clone();
+        let opacity = |v: &str| v.strip_suffix('%').map(|p| p.trim().parse::<f32>().ok().map(|p| p / 100.0))
+            .unwrap_or_else(|| v.parse().ok()).map(|o| o.clamp(0.0, 1.0));
+        if let Some(c) = node.attribute("color").and_then(color) {style.color = c;}
+        if let Some(p) = node.attribute("fill").and_then(Paint::parse) {style.fill = p;}
+        if let Some(p) = node.attribute("stroke").and_then(Paint::parse) {style.stroke = p;}
+        if let Some(o) = node.attribute("fill-opacity").and_then(opacity) {style.fill_opacity = o;}
+        if let Some(o) = node.attribute("stroke-opacity").and_then(opacity) {style.stroke_opacity = o;}
+        if let Some(o) = node.attribute("opacity").and_then(opacity) {style.opacity *= o;}
+        if let Some(s) = node.attribute("font-size").and_then(|v| length(v, self.font_size, self.font_size)) {style.font_size = s;}
+        let diagonal = (viewport.0*viewport.0 + viewport.1*viewport.1).sqrt() / std::f32::consts::SQRT_2;
+        if let Some(w) = node.attribute("stroke-width").and_then(|v| length(v, diagonal, style.font_size)) {style.stroke_width = w;}
+        match node.attribute("fill-rule") {
+            Some("evenodd") => style.fill_rule = FillRule::EvenOdd,
+            Some("nonzero") => style.fill_rule = FillRule::NonZero,
+            _ => {}
+        }
+        match node.attribute("stroke-linecap") {
+            Some("butt") => style.cap = LineCap::Butt,
+            Some("round") => style.cap = LineCap::Round,
+            Some("square") => style.cap = LineCap::Square,
+            _ => {}
+        }
+        match node.attribute("stroke-linejoin") {
+            Some("miter") | Some("miter-clip") | Some("arcs") => style.join = LineJoin::Miter,
+            Some("round") => style.join = LineJoin::Round,
+            Some("bevel") => style.join = LineJoin::Bevel,
+            _ => {}
+        }
+        match node.attribute("text-anchor") {
+            Some("start") => style.anchor = Align::Left,
+            Some("middle") => style.anchor = Align::Center,
+            Some("end") => style.anchor = Align::Right,
+            _ => {}
+        }
+        style
+    }
+}
+
+/// Paint of a fill or stroke after resolving references and opacity.
+enum Fill {
+    None,
+    Color(Color),
+    Gradient(Gradient),
+}
+
+struct Importer<'a> {
+    ids: HashMap<String, &'a Node>,
+    font: Option<Font>,
+    viewport: (f32, f32),
+    instructions: Vec<Instruction>,
+    /// Targets of the `use` elements being expanded, to stop reference cycles.
+    uses: Vec<&'a str>,
+    /// Elements expanded through `use` elements so far.
+    used: usize,
+}
+
+impl<'a> Importer<'a> {
+    fn children(&mut self, node: &'a Node, transform: [f32; 6], style: &Style) -> Result<(), SvgError> {
+        node.children.iter().try_for_each(|child| self.node(child, transform, style))
+    }
+
+    fn node(&mut self, node: &'a Node, transform: [f32; 6], parent: &Style) -> Result<(), SvgError> {
+        if node.attribute("display") == Some("none") {return Ok(());}
+        if !self.uses.is_empty() {
+            self.used += 1;
+            if self.used > MAX_USED {return Err(SvgError::TooManyUses);}
+        }
+        let transform = match node.attributes.get("transform") {
+            Some(t) => multiply(transform, parse_transform(t)),
+            None => transform
+        };
+        let style = parent.inherit(node, self.viewport);
+        let visible = !matches!(node.attribute("visibility"), Some("hidden") | Some("collapse"));
+        let (vw, vh) = self.viewport;
+        let len = |name: &str, reference: f32| node.attributes.get(name)
+            .and_then(|v| length(v, reference, style.font_size)).unwrap_or(0.0);
+
+        let data = match node.name.as_str() {
+            "g" | "svg" | "a" | "switch" => return self.children(node, transform, &style),
+            "use" => {
+                let Some(target) = node.href().and_then(|h| h.strip_prefix('#')).and_then(|id| self.ids.get(id).copied()) else {return Ok(())};
+                let id = target.attributes["id"].as_str();
+                if self.uses.contains(&id) || self.uses.len() >= 16 {return Ok(());}
+                self.uses.push(id);
+                let transform = multiply(transform, [1.0, 0.0, 0.0, 1.0, len("x", vw), len("y", vh)]);
+                let result = self.node(target, transform, &style);
+                self.uses.pop();
+                return result;
+            },
+            "image" if visible => return self.image(node, transform, &style),
+            "text" if visible => {
+                self.text(node, transform, &style);
+                return Ok(());
+            },
+            "rect" => {
+                let (x, y, w, h) = (len("x", vw), len("y", vh), len("width", vw), len("height", vh));
+                let rx = node.attributes.get("rx").and_then(|v| length(v, vw, style.font_size));
+                let ry = node.attributes.get("ry").and_then(|v| length(v, vh, style.font_size));
+                let (rx, ry) = (rx.or(ry).unwrap_or(0.0), ry.or(rx).unwrap_or(0.0));
+                (w > 0.0 && h > 0.0).then(|| PathData::rectangle(x, y, w, h, rx.clamp(0.0, w/2.0), ry.clamp(0.0, h/2.0)))
+            },
+            "circle" => {
+                let diagonal = (vw*vw + vh*vh).sqrt() / std::f32::consts::SQRT_2;
+                let r = len("r", diagonal);
+                (r > 0.0).then(|| PathData::ellipse(len("cx", vw), len("cy", vh), r, r))
+            },
+            "ellipse" => {
+                let rx = node.attributes.get("rx").and_then(|v| length(v, vw, style.font_size));
+                let ry = node.attributes.get("ry").and_then(|v| length(v, vh, style.font_size));
+                let (rx, ry) = (rx.or(ry).unwrap_or(0.0), ry.or(rx).unwrap_or(0.0));
+                (rx > 0.0 && ry > 0.0).then(|| PathData::ellipse(len("cx", vw), len("cy", vh), rx, ry))
+            },
+            "line" => Some(PathData(vec![
+                PathCommand::MoveTo((len("x1", vw), len("y1", vh))),
+                PathCommand::LineTo((len("x2", vw), len("y2", vh))),
+            ])),
+            "polyline" | "polygon" => {
+                let points = node.attributes.get("points").map(|p| numbers(p)).unwrap_or_default();
+                let mut commands = points.chunks_exact(2).enumerate().map(|(i, p)|
+                    if i == 0 {PathCommand::MoveTo((p[0], p[1]))} else {PathCommand::LineTo((p[0], p[1]))}
+                ).collect::<Vec<_>>();
+                if node.name == "polygon" && !commands.is_empty() {commands.push(PathCommand::Close);}
+                (!commands.is_empty()).then_some(PathData(commands))
+            },
+            "path" => node.attributes.get("d").map(|d| PathData::parse(d)).transpose()?,
+            _ => None
+        };
+
+        if let Some(data) = data.filter(|_| visible) {
+            self.path(data, transform, &style);
+        }
+        Ok(())
+    }
+
+    fn path(&mut self, data: PathData, transform: [f32; 6], style: &Style) {
+        let bounds = data.bounds();
+        let scale = determinant(transform).abs().sqrt();
+
+        let (color, gradient) = match self.paint(&style.fill, style, style.fill_opacity, bounds, transform) {
+            Fill::None => (Color::TRANSPARENT, None),
+            Fill::Color(color) => (color, None),
+            Fill::Gradient(gradient) => (Color::TRANSPARENT, Some(gradient)),
+        };
+        //Strokes can only be solid, gradients fall back to their first stop
+        let stroke = match self.paint(&style.stroke, style, style.stroke_opacity, bounds, transform) {
+            Fill::None => None,
+            Fill::Color(color) => Some(color),
+            Fill::Gradient(gradient) => gradient.stops.first().map(|s| s.1),
+        }.filter(|_| style.stroke_width > 0.0).map(|color| Stroke{
+            color, width: style.stroke_width*scale, cap: style.cap, join: style.join
+        });
+
+        if color.3 == 0 && gradient.is_none() && stroke.is_none() {return;}
+        self.instructions.push(Instruction(Area::default(), Item::Path(Path{
+            data: data.transform(transform), color, gradient, fill_rule: style.fill_rule, stroke
+        })));
+    }
+
+    /// Resolves a fill or stroke paint, `opacity` being its `fill-opacity` or `stroke-opacity`.
+    fn paint(&self, paint: &Paint, style: &Style, opacity: f32, bounds: (f32, f32, f32, f32), transform: [f32; 6]) -> Fill {
+        let alpha = opacity * style.opacity;
+        let fade = |c: Color| Color(c.0, c.1, c.2, (c.3 as f32 * alpha).round() as u8);
+        match paint {
+            Paint::None => Fill::None,
+            Paint::Color(c) => Fill::Color(fade(*c)),
+            Paint::CurrentColor => Fill::Color(fade(style.color)),
+            Paint::Url(id, fallback) => match self.ids.get(id.as_str()) {
+                Some(node) if node.name == "linearGradient" || node.name == "radialGradient" =>
+                    match self.gradient(node, bounds, transform) {
+                        Some(mut gradient) => {
+                            gradient.stops.iter_mut().for_each(|s| s.1 = fade(s.1));
+                            Fill::Gradient(gradient)
+                        },
+                        None => Fill::None
+                    },
+                _ => fallback.as_ref().map(|f| self.paint(f, style, opacity, bounds, transform)).unwrap_or(Fill::None)
+            }
+        }
+    }
+
+    /// An attribute of a gradient, inherited through its `href` chain.
+    fn gradient_attribute(&self, node: &'a Node, name: &str) -> Option<&'a str> {
+        let mut node = node;
+        for _ in 0..8 {
+            if let Some(value) = node.attribute(name) {return Some(value);}
+            node = self.ids.get(node.href()?.strip_prefix('#')?).copied()?;
+        }
+        None
+    }
+
+    fn gradient(&self, node: &'a Node, bounds: (f32, f32, f32, f32), transform: [f32; 6]) -> Option<Gradient> {
+        let mut stops_node = node;
+        for _ in 0..8 {
+            if stops_node.children.iter().any(|c| c.name == "stop") {break;}
+            stops_node = self.ids.get(stops_node.href()?.strip_prefix('#')?).copied()?;
+        }
+        let mut last = 0.0_f32;
+        let stops = stops_node.children.iter().filter(|c| c.name == "stop").map(|stop| {
+            let offset = stop.attribute("offset").and_then(fraction).unwrap_or(0.0).clamp(last, 1.0);
+            last = offset;
+            let color = stop.attribute("stop-color").and_then(color).unwrap_or(Color::BLACK);
+            let opacity = stop.attribute("stop-opacity").and_then(fraction).unwrap_or(1.0).clamp(0.0, 1.0);
+            (offset, Color(color.0, color.1, color.2, (color.3 as f32 * opacity).round() as u8))
+        }).collect::<Vec<_>>();
+        if stops.is_empty() {return None;}
+
+        let user_space = self.gradient_attribute(node, "gradientUnits") == Some("userSpaceOnUse");
+        let (vw, vh) = self.viewport;
+        let value = |name: &str, default: f32, reference: f32| match self.gradient_attribute(node, name) {
+            Some(v) if user_space => length(v, reference, 16.0).unwrap_or(default * reference),
+            Some(v) => fraction(v).unwrap_or(default),
+            None if user_space => default * reference,
+            None => default,
+        };
+        let diagonal = (vw*vw + vh*vh).sqrt() / std::f32::consts::SQRT_2;
+        let kind = if node.name == "linearGradient" {
+            GradientKind::Linear(
+                (value("x1", 0.0, vw), value("y1", 0.0, vh)),
+                (value("x2", 1.0, vw), value("y2", 0.0, vh)),
+            )
+        } else {
+            GradientKind::Radial((value("cx", 0.5, vw), value("cy", 0.5, vh)), value("r", 0.5, diagonal))
+        };
+
+        let spread = match self.gradient_attribute(node, "spreadMethod") {
+            Some("reflect") => GradientSpread::Reflect,
+            Some("repeat") => GradientSpread::Repeat,
+            _ => GradientSpread::Pad,
+        };
+
+        let units = if user_space {IDENTITY} else {
+            let (x, y, w, h) = bounds;
+            if w <= 0.0 || h <= 0.0 {return None;}
+            [w, 0.0, 0.0, h, x, y]
+        };
+        let local = self.gradient_attribute(node, "gradientTransform").map(parse_transform).unwrap_or(IDENTITY);
+        Some(Gradient{kind, stops, spread, transform: multiply(multiply(transform, units), local)})
+    }
+
+    fn image(&mut self, node: &Node, transform: [f32; 6], style: &Style) -> Result<(), SvgError> {
+        let Some(data) = node.href().and_then(|h| h.strip_prefix("data:")) else {return Ok(())};
+        let Some((header, payload)) = data.split_once(',') else {return Ok(())};
+        let bytes = match header.ends_with(";base64") {
+            true => base64::engine::general_purpose::STANDARD
+                .decode(payload.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+                .map_err(|e| SvgError::Image(e.to_string()))?,
+            false => payload.as_bytes().to_vec()
+        };
+        let mut image = image::load_from_memory(&bytes).map_err(|e| SvgError::Image(e.to_string()))?.to_rgba8();
+        if style.opacity < 1.0 {
+            image.pixels_mut().for_each(|p| p.0[3] = (p.0[3] as f32 * style.opacity).round() as u8);
+        }
+
+        let (vw, vh) = self.viewport;
+        let len = |name: &str, reference: f32| node.attributes.get(name).and_then(|v| length(v, reference, style.font_size));
+        let (iw, ih) = (image.width() as f32, image.height() as f32);
+        let (x, y) = (len("x", vw).unwrap_or(0.0), len("y", vh).unwrap_or(0.0));
+        let (w, h) = match (len("width", vw), len("height", vh)) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, w * ih / iw),
+            (None, Some(h)) => (h * iw / ih, h),
+            (None, None) => (iw, ih),
+        };
+        //Fit the image inside its box unless it may stretch
+        let (w, h, x, y) = match node.attributes.get("preserveAspectRatio").map(|v| v.trim()) {
+            Some("none") => (w, h, x, y),
+            _ => {
+                let s = (w / iw).min(h / ih);
+                (iw*s, ih*s, x + (w - iw*s) / 2.0, y + (h - ih*s) / 2.0)
+            }
+        };
+        if w <= 0.0 || h <= 0.0 {return Ok(());}
+
+        let [a, b, c, d, _, _] = transform;
+        let center = apply(transform, (x + w / 2.0, y + h / 2.0));
+        let size = (w * (a*a + b*b).sqrt(), h * (c*c + d*d).sqrt());
+        let shape = ShapeType::Rectangle(0.0, size, b.atan2(a).to_degrees());
+        let (bw, bh) = shape.size();
+        self.instructions.push(Instruction(
+            Area::new((center.0 - bw / 2.0, center.1 - bh / 2.0), None),
+            Item::Image(Image::new(shape, Arc::new(RgbaImage::from(image)), None))
+        ));
+        Ok(())
+    }
+
+    fn text(&mut self, node: &Node, transform: [f32; 6], style: &Style) {
+        let Some(font) = self.font.clone() else {return};
+        let content = node.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let Fill::Color(color) = self.paint(&style.fill, style, style.fill_opacity, (0.0, 0.0, 1.0, 1.0), transform) else {return};
+        if content.is_empty() {return;}
+
+        let (vw, vh) = self.viewport;
+        let first = |name: &str, reference: f32| node.attributes.get(name)
+            .and_then(|v| v.split(|c: char| c.is_whitespace() || c == ',').find(|s| !s.is_empty()).map(str::to_string))
+            .and_then(|v| length(&v, reference, style.font_size)).unwrap_or(0.0);
+        let baseline = apply(transform, (first("x", vw), first("y", vh)));
+        let size = style.font_size * determinant(transform).abs().sqrt();
+        let Some(metrics) = font.horizontal_line_metrics(size) else {return};
+        let mut text = Text::new(&content, font, size, color, Align::Left);
+        text.angle = transform[1].atan2(transform[0]).to_degrees();
+        //From the baseline to the top left corner of the block, which the text rotates around
+        let dx = match style.anchor {
+            Align::Left => 0.0,
+            Align::Center => -text.size().0 / 2.0,
+            Align::Right => -text.size().0,
+        };
+        let dy = -(metrics.new_line_size + metrics.descent);
+        let (sin, cos) = text.angle.to_radians().sin_cos();
+        let offset = (baseline.0 + dx*cos - dy*sin, baseline.1 + dx*sin + dy*cos);
+        self.instructions.push(Instruction(Area::new(offset, None), Item::Text(text)));
+    }
+}
+
+fn parse_transform(value: &str) -> [f32; 6] {
+    value.split(')').filter_map(|part| {
+        let (name, args) = part.split_once('(')?;
+        let a = numbers(args);
+        let arg = |i: usize, default: f32| a.get(i).copied().unwrap_or(default);
+        Some(match name.trim().trim_start_matches(',').trim() {
+            "matrix" if a.len() == 6 => [a[0], a[1], a[2], a[3], a[4], a[5]],
+            "translate" => [1.0, 0.0, 0.0, 1.0, arg(0, 0.0), arg(1, 0.0)],
+            "scale" => [arg(0, 1.0), 0.0, 0.0, arg(1, arg(0, 1.0)), 0.0, 0.0],
+            "rotate" => {
+                let (sin, cos) = arg(0, 0.0).to_radians().sin_cos();
+                let (cx, cy) = (arg(1, 0.0), arg(2, 0.0));
+                multiply(multiply([1.0, 0.0, 0.0, 1.0, cx, cy], [cos, sin, -sin, cos, 0.0, 0.0]), [1.0, 0.0, 0.0, 1.0, -cx, -cy])
+            },
+            "skewX" => [1.0, 0.0, arg(0, 0.0).to_radians().tan(), 1.0, 0.0, 0.0],
+            "skewY" => [1.0, arg(0, 0.0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => return None
+        })
+    }).fold(IDENTITY, multiply)
+}
+
+/// Numbers separated by whitespace and/or commas.
+fn numbers(value: &str) -> Vec<f32> {
+    value.split(|c: char| c.is_whitespace() || c == ',').filter(|s| !s.is_empty()).map_while(|s| s.parse().ok()).collect()
+}
+
+/// A length with an optional unit, percentages relative to `reference`.
+fn length(value: &str, reference: f32, font_size: f32) -> Option<f32> {
+    let value = value.trim();
+    //An `e` followed by a digit is an exponent, not the start of `em` or `ex`
+    let bytes = value.as_bytes();
+    let exponent = |i: usize| matches!(bytes[i], b'e' | b'E') && match bytes.get(i+1) {
+        Some(b'+' | b'-') => bytes.get(i+2).is_some_and(u8::is_ascii_digit),
+        next => next.is_some_and(u8::is_ascii_digit)
+    };
+    let split = (0..bytes.len()).find(|i| (bytes[*i].is_ascii_alphabetic() && !exponent(*i)) || bytes[*i] == b'%').unwrap_or(bytes.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.trim().parse::<f32>().ok()?;
+    Some(number * match unit {
+        "" | "px" => 1.0,
+        "%" => reference / 100.0,
+        "em" => font_size,
+        "ex" => font_size / 2.0,
+        "pt" => 4.0 / 3.0,
+        "pc" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        _ => return None
+    })
+}
+
+/// A number or a percentage as a fraction.
+fn fraction(value: &str) -> Option<f32> {
+    match value.trim().strip_suffix('%') {
+        Some(p) => p.trim().parse::<f32>().ok().map(|p| p / 100.0),
+        None => value.trim().parse().ok()
+    }
+}
+
+fn color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digit = |i: usize| u8::from_str_radix(hex.get(i..i+1)?, 16).ok();
+        let pair = |i: usize| u8::from_str_radix(hex.get(i..i+2)?, 16).ok();
+        return match hex.len() {
+            3 => Some(Color(digit(0)?*17, digit(1)?*17, digit(2)?*17, 255)),
+            4 => Some(Color(digit(0)?*17, digit(1)?*17, digit(2)?*17, digit(3)?*17)),
+            6 => Some(Color(pair(0)?, pair(2)?, pair(4)?, 255)),
+            8 => Some(Color(pair(0)?, pair(2)?, pair(4)?, pair(6)?)),
+            _ => None
+        };
+    }
+    if let Some(args) = value.strip_prefix("rgba(").or_else(|| value.strip_prefix("rgb(")) {
+        let args = args.strip_suffix(')')?.split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        let channel = |s: &str| match s.strip_suffix('%') {
+            Some(p) => p.parse::<f32>().ok().map(|p| p * 2.55),
+            None => s.parse::<f32>().ok()
+        }.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        let alpha = args.get(3).map(|a| fraction(a).map(|a| (a.clamp(0.0, 1.0) * 255.0).round() as u8)).unwrap_or(Some(255))?;
+        return Some(Color(channel(args.first()?)?, channel(args.get(1)?)?, channel(args.get(2)?)?, alpha));
+    }
+    Some(match value.to_ascii_lowercase().as_str() {
+        "transparent" => Color::TRANSPARENT,
+        "black" => Color(0, 0, 0, 255),
+        "white" => Color(255, 255, 255, 255),
+        "red" => Color(255, 0, 0, 255),
+        "lime" => Color(0, 255, 0, 255),
+        "green" => Color(0, 128, 0, 255),
+        "blue" => Color(0, 0, 255, 255),
+        "yellow" => Color(255, 255, 0, 255),
+        "cyan" | "aqua" => Color(0, 255, 255, 255),
+        "magenta" | "fuchsia" => Color(255, 0, 255, 255),
+        "gray" | "grey" => Color(128, 128, 128, 255),
+        "silver" => Color(192, 192, 192, 255),
+        "lightgray" | "lightgrey" => Color(211, 211, 211, 255),
+        "darkgray" | "darkgrey" => Color(169, 169, 169, 255),
+        "maroon" => Color(128, 0, 0, 255),
+        "olive" => Color(128, 128, 0, 255),
+        "purple" => Color(128, 0, 128, 255),
+        "teal" => Color(0, 128, 128, 255),
+        "navy" => Color(0, 0, 128, 255),
+        "orange" => Color(255, 165, 0, 255),
+        "pink" => Color(255, 192, 203, 255),
+        "brown" => Color(165, 42, 42, 255),
+        "gold" => Color(255, 215, 0, 255),
+        "indigo" => Color(75, 0, 130, 255),
+        "violet" => Color(238, 130, 238, 255),
+        "crimson" => Color(220, 20, 60, 255),
+        "coral" => Color(255, 127, 80, 255),
+        "salmon" => Color(250, 128, 114, 255),
+        "tomato" => Color(255, 99, 71, 255),
+        "orangered" => Color(255, 69, 0, 255),
+        "skyblue" => Color(135, 206, 235, 255),
+        "steelblue" => Color(70, 130, 180, 255),
+        "royalblue" => Color(65, 105, 225, 255),
+        "dodgerblue" => Color(30, 144, 255, 255),
+        "darkblue" => Color(0, 0, 139, 255),
+        "darkgreen" => Color(0, 100, 0, 255),
+        "darkred" => Color(139, 0, 0, 255),
+        "forestgreen" => Color(34, 139, 34, 255),
+        "limegreen" => Color(50, 205, 50, 255),
+        "whitesmoke" => Color(245, 245, 245, 255),
+        "gainsboro" => Color(220, 220, 220, 255),
+        "dimgray" | "dimgrey" => Color(105, 105, 105, 255),
+        _ => return None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(source: &str) -> Vec<Path> {
+        Svg::parse(source, None).unwrap().instructions.into_iter().map(|i| match i.1 {
+            Item::Path(path) => path,
+            item => panic!("expected a path, found {item:?}")
+        }).collect()
+    }
+
+    #[test]
+    fn lengths_resolve_units_and_percentages() {
+        assert_eq!(length("12", 200.0, 16.0), Some(12.0));
+        assert_eq!(length(" 12px ", 200.0, 16.0), Some(12.0));
+        assert_eq!(length("50%", 200.0, 16.0), Some(100.0));
+        assert_eq!(length("2em", 200.0, 10.0), Some(20.0));
+        assert_eq!(length("1in", 0.0, 16.0), Some(96.0));
+        assert_eq!(length("3pt", 0.0, 16.0), Some(4.0));
+        assert_eq!(length("-1.5e1", 0.0, 16.0), Some(-15.0));
+        assert_eq!(length("12furlongs", 0.0, 16.0), None);
+        assert_eq!(length("px", 0.0, 16.0), None);
+        assert_eq!(fraction("25%"), Some(0.25));
+        assert_eq!(fraction("0.5"), Some(0.5));
+    }
+
+    #[test]
+    fn colors_parse_every_notation() {
+        assert_eq!(color("#f00"), Some(Color(255, 0, 0, 255)));
+        assert_eq!(color("#f008"), Some(Color(255, 0, 0, 136)));
+        assert_eq!(color("#102030"), Some(Color(16, 32, 48, 255)));
+        assert_eq!(color("#10203040"), Some(Color(16, 32, 48, 64)));
+        assert_eq!(color("rgb(255, 128, 0)"), Some(Color(255, 128, 0, 255)));
+        assert_eq!(color("rgb(100%,0%,50%)"), Some(Color(255, 0, 128, 255)));
+        assert_eq!(color("rgba(0 0 0 / 50%)"), Some(Color(0, 0, 0, 128)));
+        assert_eq!(color("rgba(0, 0, 0, 0.25)"), Some(Color(0, 0, 0, 64)));
+        assert_eq!(color(" SteelBlue "), Some(Color(70, 130, 180, 255)));
+        assert_eq!(color("#12345"), None);
+        assert_eq!(color("#ggg"), None);
+        assert_eq!(color("rgb(1, 2)"), None);
+        assert_eq!(color("blurple"), None);
+    }
+
+    #[test]
+    fn transforms_compose_left_to_right() {
+        assert_eq!(parse_transform("translate(10, 20)"), [1.0, 0.0, 0.0, 1.0, 10.0, 20.0]);
+        assert_eq!(parse_transform("scale(2)"), [2.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        assert_eq!(parse_transform("translate(10) scale(2, 3)"), [2.0, 0.0, 0.0, 3.0, 10.0, 0.0]);
+        assert_eq!(parse_transform("matrix(1 2 3 4 5 6)"), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        //A rotation around a center keeps that center in place
+        let rotate = parse_transform("rotate(90, 10, 10)");
+        let (x, y) = apply(rotate, (10.0, 10.0));
+        assert!((x - 10.0).abs() < 1e-4 && (y - 10.0).abs() < 1e-4);
+        let (x, y) = apply(rotate, (20.0, 10.0));
+        assert!((x - 10.0).abs() < 1e-4 && (y - 20.0).abs() < 1e-4);
+        //Unknown or malformed functions are skipped
+        assert_eq!(parse_transform("wobble(3) translate(1 2) matrix(1 2)"), [1.0, 0.0, 0.0, 1.0, 1.0, 2.0]);
+        assert_eq!(parse_transform(""), IDENTITY);
+    }
+
+    #[test]
+    fn entities_are_unescaped() {
+        assert_eq!(unescape("a &amp; b &lt;c&gt; &quot;d&quot; &apos;e&apos;"), "a & b <c> \"d\" 'e'");
+        assert_eq!(unescape("&#65;&#x42;&#x1F600;"), "AB\u{1F600}");
+        //Unknown and unterminated entities are kept as written
+        assert_eq!(unescape("&nbsp; &#xZZ; &#1114112; AT&T"), "&nbsp; &#xZZ; &#1114112; AT&T");
+        assert_eq!(unescape("&amp;amp;"), "&amp;");
+        let svg = r##"<svg><linearGradient id="a&amp;b"><stop stop-color="red"/></linearGradient>
+            <rect width="10" height="10" fill="url(#a&amp;b)"/></svg>"##;
+        assert!(paths(svg)[0].gradient.is_some());
+    }
+
+    #[test]
+    fn gradient_units_follow_the_bounding_box_or_user_space() {
+        let svg = r##"<svg viewBox="0 0 100 100">
+            <linearGradient id="box" x2="0" y2="100%"><stop stop-color="red"/><stop offset="1" stop-color="blue"/></linearGradient>
+            <linearGradient id="user" href="#box" gradientUnits="userSpaceOnUse" x1="10" x2="50%" y2="0"/>
+            <rect x="20" y="40" width="10" height="20" fill="url(#box)"/>
+            <rect x="20" y="40" width="10" height="20" fill="url(#user)"/>
+        </svg>"##;
+        let paths = paths(svg);
+        let gradient = paths[0].gradient.as_ref().unwrap();
+        assert!(matches!(gradient.kind, GradientKind::Linear((0.0, 0.0), (0.0, 1.0))));
+        assert_eq!(gradient.transform, [10.0, 0.0, 0.0, 20.0, 20.0, 40.0]);
+        //Stops and attributes not set are inherited through href
+        let gradient = paths[1].gradient.as_ref().unwrap();
+        assert!(matches!(gradient.kind, GradientKind::Linear((10.0, 0.0), (50.0, 0.0))));
+        assert_eq!(gradient.transform, IDENTITY);
+        assert_eq!(gradient.stops, vec![(0.0, Color(255, 0, 0, 255)), (1.0, Color(0, 0, 255, 255))]);
+    }
+
+    #[test]
+    fn use_cycles_are_expanded_once() {
+        let svg = r##"<svg>
+            <g id="g"><rect width="1" height="1"/><use href="#g"/><use href="#g"/></g>
+            <use id="a" href="#b"/><use id="b" x="5" href="#a"/>
+        </svg>"##;
+        //The group itself and its copy inside the first level of uses
+        assert_eq!(paths(svg).len(), 3);
+        let svg = r##"<svg><rect id="r" width="1" height="1"/><use href="#r" x="2"/><use href="#r" x="4"/></svg>"##;
+        let paths = paths(svg);
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[2].data.bounds(), (4.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn use_fan_out_is_limited() {
+        //Ten uses of ten uses of ... nine levels deep, a billion rectangles
+        let mut svg = r#"<svg><rect id="l0" width="1" height="1"/>"#.to_string();
+        for level in 1..10 {
+            svg += &format!(r#"<g id="l{level}">{}</g>"#, format!(r##"<use href="#l{}"/>"##, level - 1).repeat(10));
+        }
+        svg += "</svg>";
+        assert_eq!(Svg::parse(&svg, None).unwrap_err(), SvgError::TooManyUses);
+        //Wide but within the limit
+        let svg = format!(r##"<svg><rect id="r" width="1" height="1"/>{}</svg>"##, r##"<use href="#r"/>"##.repeat(1000));
+        assert_eq!(paths(&svg).len(), 1001);
+    }
+
+    #[test]
+    fn malformed_documents_are_errors() {
+        assert_eq!(Svg::parse("<html/>", None).unwrap_err(), SvgError::NotSvg);
+        assert!(matches!(Svg::parse("<svg><g></svg>", None), Err(SvgError::Xml(_))));
+        assert!(matches!(Svg::parse("<svg><g>", None), Err(SvgError::Xml(_))));
+        assert_eq!(
+            Svg::parse(r#"<svg><path d="M0 0 L1"/></svg>"#, None).unwrap_err(),
+            SvgError::Path(PathError::ExpectedNumber(7))
+        );
+    }
+}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use base64::Engine;
+
+use crate::{
+    Instruction, Item, Area, Clip, Mask, Layer, BlendMode, BlurEdge, ClipShape, Color, Shape, Shadow, Image, Text, Path, Font, ShapeType, StrokeAlign, FillRule, LineCap,
+    LineJoin, Gradient, GradientKind, GradientSpread, RgbaImage, ColorMatrix
+};
+
+use crate::path::arc_point;
+
+use super::Svg;
+
+impl Svg {
+    /// Writes `instructions` as a standalone SVG document of `size`, painted in order.
+    ///
+    /// Shapes keep their rotation, strokes and borders, images are embedded as PNGs cropped and
+    /// filtered the way the renderer does, text becomes positioned glyph runs in its embedded
+    /// font and `Area.bounds` become clip paths around the group `Area.transform` becomes,
+    /// without its perspective. Pushed clips and masks become clip paths and alpha masks around
+    /// the groups of the instructions up to their pop, pushed layers groups with their opacity,
+    /// blend mode, transform, blur and color matrix. Blend modes become `mix-blend-mode`, but for the Porter-Duff operators
+    /// that draw over. Inner shadows, corner smoothing and the corner radius of polygons are left
+    /// out, and the stroke of an image does not hollow it out. SVG has no backdrop filter, so
+    /// the backdrop blur of shapes is left out too.
+    pub fn export(instructions: &[Instruction], size: (f32, f32)) -> String {
+        let mut writer = Writer{size, ..Writer::default()};
+        instructions.iter().for_each(|instruction| writer.instruction(instruction));
+        writer.body.push_str(&"</g>".repeat(writer.pushed.iter().sum()));
+
+        let (w, h) = (n(size.0), n(size.1));
+        let style = match writer.fonts.is_empty() {
+            true => String::new(),
+            false => format!("<style>{}</style>\n", writer.fonts)
+        };
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n<defs>\n{style}{}</defs>\n{}</svg>\n",
+            writer.defs, writer.body
+        )
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    size: (f32, f32),
+    defs: String,
+    fonts: String,
+    body: String,
+    ids: usize,
+    clips: HashMap<[u32; 9], usize>,
+    blurs: HashMap<(u32, BlurEdge), usize>,
+    matrices: HashMap<[u32; 20], usize>,
+    families: HashMap<usize, usize>,
+    /// Groups opened by every clip, mask and layer that has not been popped yet.
+    pushed: Vec<usize>,
+}
+
+impl Writer {
+    fn id(&mut self) -> usize {
+        self.ids += 1;
+        self.ids
+    }
+
+    fn instruction(&mut self, Instruction(area, item): &Instruction) {
+        match item {
+            Item::PushClip(clip) => return self.push(area, clip),
+            Item::PushMask(mask) => return self.mask(area, mask),
+            Item::PushLayer(layer) => return self.layer(area, layer),
+            Item::PopClip | Item::PopMask | Item::PopLayer => return self.pop(),
+            _ => {}
+        }
+        let blend = blend_style(area.blend);
+        if !blend.is_empty() {
+            self.body.push_str(&format!("<g{blend}>"));
+        }
+        let clip = area.bounds.map(|bounds| self.clip(bounds, area.clip.unwrap_or_default()));
+        if let Some(id) = clip {
+            self.body.push_str(&format!("<g clip-path=\"url(#c{id})\">"));
+        }
+        if area.transform.is_some() {
+            let [a, b, c, d, e, f] = area.matrix().map(n);
+            self.body.push_str(&format!("<g transform=\"matrix({a} {b} {c} {d} {e} {f})\">"));
+        }
+        match item {
+            Item::Shape(shape) => self.shape(area.offset, shape),
+            Item::Image(image) => self.image(area.offset, image),
+            Item::Text(text) => self.text(area.offset, text),
+            Item::Shadow(shadow) => self.shadow(area.offset, shadow),
+            Item::Path(path) => self.path(area.offset, path),
+            Item::PushClip(_) | Item::PopClip | Item::PushMask(_) | Item::PopMask | Item::PushLayer(_) | Item::PopLayer => unreachable!(),
+        }
+        if area.transform.is_some() {
+            self.body.push_str("</g>");
+        }
+        if clip.is_some() {
+            self.body.push_str("</g>");
+        }
+        if !blend.is_empty() {
+            self.body.push_str("</g>");
+        }
+        self.body.push('\n');
+    }
+
+    /// A clip path for `Area.bounds` and its shape, shared by every instruction with the same clip.
+    fn clip(&mut self, (x, y, w, h): (f32, f32, f32, f32), shape: ClipShape) -> usize {
+        let [r0, r1, r2, r3] = shape.radii;
+        let key = [x, y, w, h, r0, r1, r2, r3, shape.angle].map(f32::to_bits);
+        if let Some(id) = self.clips.get(&key) {return *id;}
+        let id = self.id();
+        let outline = match shape == ClipShape::default() {
+            true => format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>", n(x), n(y), n(w.max(0.0)), n(h.max(0.0))),
+            false => format!("<path d=\"{}\"/>", shape.outline((x, y, w.max(0.0), h.max(0.0))))
+        };
+        self.defs.push_str(&format!("<clipPath id=\"c{id}\">{outline}</clipPath>\n"));
+        self.clips.insert(key, id);
+        id
+    }
+
+    /// Opens a group clipped to `clip`, in screen space and inside the clip of `area`.
+    fn push(&mut self, area: &Area, clip: &Clip) {
+        let bounds = area.bounds.map(|bounds| format!(" clip-path=\"url(#c{})\"", self.clip(bounds, area.clip.unwrap_or_default())));
+        let (data, rule) = clip.outline();
+        let rule = match rule {
+            FillRule::NonZero => "",
+            FillRule::EvenOdd => " clip-rule=\"evenodd\"",
+        };
+        let (x, y) = area.offset;
+        let data = data.transform(crate::transform::multiply(area.matrix(), [1.0, 0.0, 0.0, 1.0, x, y]));
+        let id = self.id();
+        self.defs.push_str(&format!("<clipPath id=\"p{id}\"{}><path d=\"{data}\"{rule}/></clipPath>\n", bounds.unwrap_or_default()));
+        self.body.push_str(&format!("<g clip-path=\"url(#p{id})\">\n"));
+        self.pushed.push(1);
+    }
+
+    /// Opens a group masked by the alpha of `mask`, written like the instructions of the
+    /// document into a mask covering it.
+    fn mask(&mut self, area: &Area, mask: &Mask) {
+        let body = std::mem::take(&mut self.body);
+        let pushed = std::mem::take(&mut self.pushed);
+        match mask {
+            Mask::Image(image) => self.instruction(&Instruction(*area, Item::Image(image.clone()))),
+            Mask::Items(items) => {
+                let clip = area.bounds.map(|bounds| self.clip(bounds, area.clip.unwrap_or_default()));
+                if let Some(id) = clip {
+                    self.body.push_str(&format!("<g clip-path=\"url(#c{id})\">"));
+                }
+                self.body.push_str(&format!("<g transform=\"translate({} {})\">\n", n(area.offset.0), n(area.offset.1)));
+                items.iter().for_each(|instruction| self.instruction(instruction));
+                self.body.push_str(&"</g>".repeat(self.pushed.iter().sum::<usize>() + 1 + clip.is_some() as usize));
+                self.body.push('\n');
+            }
+        }
+        let content = std::mem::replace(&mut self.body, body);
+        self.pushed = pushed;
+        self.pushed.push(1);
+        let id = self.id();
+        self.defs.push_str(&format!(
+            "<mask id=\"m{id}\" maskUnits=\"userSpaceOnUse\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" mask-type=\"alpha\">\n{content}</mask>\n",
+            n(self.size.0), n(self.size.1)
+        ));
+        self.body.push_str(&format!("<g mask=\"url(#m{id})\">\n"));
+    }
+
+    /// Opens a group with the opacity, blend mode, color matrix and blur of `layer`, inside the
+    /// clip of `area` and transformed by it.
+    fn layer(&mut self, area: &Area, layer: &Layer) {
+        let clip = area.bounds.map(|bounds| format!(" clip-path=\"url(#c{})\"", self.clip(bounds, area.clip.unwrap_or_default())));
+        let blend = blend_style(layer.blend);
+        self.body.push_str(&format!("<g{} opacity=\"{}\"{blend}>", clip.unwrap_or_default(), n(layer.opacity.clamp(0.0, 1.0))));
+        if area.transform.is_some() {
+            let [a, b, c, d, e, f] = area.matrix().map(n);
+            self.body.push_str(&format!("<g transform=\"matrix({a} {b} {c} {d} {e} {f})\">"));
+        }
+        //The matrix filters what the blur gives, so its group is outside
+        let matrix = layer.filter.map(|m| self.color_matrix(&m));
+        if let Some(id) = matrix {
+            self.body.push_str(&format!("<g filter=\"url(#f{id})\">"));
+        }
+        let blur = layer.blur.filter(|b| b.radius > 0.0).map(|b| self.blur(b.radius, b.edge));
+        if let Some(id) = blur {
+            self.body.push_str(&format!("<g filter=\"url(#f{id})\">"));
+        }
+        self.body.push('\n');
+        self.pushed.push(1 + area.transform.is_some() as usize + matrix.is_some() as usize + blur.is_some() as usize);
+    }
+
+    /// Closes the groups of the clip, mask or layer pushed last, if any.
+    fn pop(&mut self) {
+        if let Some(groups) = self.pushed.pop() {
+            self.body.push_str(&"</g>".repeat(groups));
+            self.body.push('\n');
+        }
+    }
+
+    /// A gaussian blur covering the whole document, `blur` being twice the deviation. SVG
+    /// cannot mirror at the edges, mirrored blurs duplicate the edge pixels instead.
+    fn blur(&mut self, blur: f32, edge: BlurEdge) -> usize {
+        let key = (blur.to_bits(), edge);
+        if let Some(id) = self.blurs.get(&key) {return *id;}
+        let id = self.id();
+        let edge = match edge {
+            BlurEdge::Transparent => "",
+            BlurEdge::Clamp | BlurEdge::Mirror => " edgeMode=\"duplicate\"",
+        };
+        self.defs.push_str(&format!(
+            "<filter id=\"f{id}\" filterUnits=\"userSpaceOnUse\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\"><feGaussianBlur stdDeviation=\"{}\"{edge}/></filter>\n",
+            n(self.size.0), n(self.size.1), n(blur / 2.0)
+        ));
+        self.blurs.insert(key, id);
+        id
+    }
+
+    /// A color matrix filter covering the whole document, in sRGB like the renderer applies it.
+    fn color_matrix(&mut self, matrix: &ColorMatrix) -> usize {
+        let key = matrix.0.map(f32::to_bits);
+        if let Some(id) = self.matrices.get(&key) {return *id;}
+        let id = self.id();
+        let values = matrix.0.iter().map(|v| n(*v).to_string()).collect::<Vec<_>>().join(" ");
+        self.defs.push_str(&format!(
+            "<filter id=\"f{id}\" filterUnits=\"userSpaceOnUse\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" color-interpolation-filters=\"sRGB\"><feColorMatrix type=\"matrix\" values=\"{values}\"/></filter>\n",
+            n(self.size.0), n(self.size.1)
+        ));
+        self.matrices.insert(key, id);
+        id
+    }
+
+    /// The family name `font` is embedded under, left to the viewer when its file is not known.
+    fn font(&mut self, font: &Font) -> usize {
+        if let Some(id) = self.families.get(&font.file_hash()) {return *id;}
+        let id = self.id();
+        if let Some(bytes) = font.bytes() {
+            let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+            self.fonts.push_str(&format!("@font-face{{font-family:font{id};src:url(data:font/ttf;base64,{data})}}"));
+        }
+        self.families.insert(font.file_hash(), id);
+        id
+    }
+
+    fn gradient(&mut self, gradient: &Gradient) -> usize {
+        let id = self.id();
+        let (tag, geometry) = match gradient.kind {
+            GradientKind::Linear((x1, y1), (x2, y2)) =>
+                ("linearGradient", format!(" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"", n(x1), n(y1), n(x2), n(y2))),
+            GradientKind::Radial((cx, cy), r) =>
+                ("radialGradient", format!(" cx=\"{}\" cy=\"{}\" r=\"{}\"", n(cx), n(cy), n(r))),
+        };
+        let spread = match gradient.spread {
+            GradientSpread::Pad => "",
+            GradientSpread::Reflect => " spreadMethod=\"reflect\"",
+            GradientSpread::Repeat => " spreadMethod=\"repeat\"",
+        };
+        let stops = gradient.stops.iter().map(|(offset, color)| {
+            let opacity = match color.3 < 255 {
+                true => format!(" stop-opacity=\"{}\"", n(color.3 as f32 / 255.0)),
+                false => String::new()
+            };
+            format!("<stop offset=\"{}\" stop-color=\"{}\"{opacity}/>", n(*offset), hex(*color))
+        }).collect::<String>();
+        let [a, b, c, d, e, f] = gradient.transform.map(n);
+        self.defs.push_str(&format!(
+            "<{tag} id=\"g{id}\" gradientUnits=\"userSpaceOnUse\"{geometry}{spread} gradientTransform=\"matrix({a} {b} {c} {d} {e} {f})\">{stops}</{tag}>\n"
+        ));
+        id
+    }
+
+    fn shape(&mut self, offset: (f32, f32), shape: &Shape) {
+        let (center, rotate) = placement(&shape.shape, offset);
+        let [w, h] = shape.shape.wh();
+        let stroke = shape.shape.stroke();
+        let element = match shape.shape {
+            //Ring segments are drawn as a thick arc along their middle
+            ShapeType::Arc(_, _, _, _, _, rounded) if stroke > 0.0 && stroke < w.min(h) / 2.0 => {
+                let (start, sweep) = shape.shape.sweep().unwrap();
+                let (rx, ry) = (w / 2.0 - stroke / 2.0, h / 2.0 - stroke / 2.0);
+                let (x, y) = arc_point(center, (rx, ry), start);
+                let cap = if rounded {" stroke-linecap=\"round\""} else {""};
+                format!(
+                    "<path d=\"M{} {}{}\"{rotate} fill=\"none\"{}{cap}/>",
+                    n(x), n(y), arc(center, rx, ry, start, sweep), line("stroke", shape.color, stroke)
+                )
+            },
+            _ if stroke > 0.0 => format!(
+                "{}{rotate} fill=\"none\"{}/>", outline(&shape.shape, center, -stroke / 2.0), line("stroke", shape.color, stroke)
+            ),
+            _ => format!("{}{rotate}{}/>", outline(&shape.shape, center, 0.0), paint("fill", shape.color)),
+        };
+        self.body.push_str(&element);
+
+        if let Some(border) = shape.border.filter(|b| b.width > 0.0) {
+            let grow = match border.align {
+                StrokeAlign::Inside => -border.width / 2.0,
+                StrokeAlign::Center => 0.0,
+                StrokeAlign::Outside => border.width / 2.0,
+            };
+            self.body.push_str(&format!(
+                "{}{rotate} fill=\"none\"{}/>", outline(&shape.shape, center, grow), line("stroke", border.color, border.width)
+            ));
+        }
+    }
+
+    fn shadow(&mut self, offset: (f32, f32), shadow: &Shadow) {
+        let (center, rotate) = placement(&shadow.shape, (offset.0 + shadow.offset.0, offset.1 + shadow.offset.1));
+        let element = format!("{}{rotate}{}/>", outline(&shadow.shape, center, shadow.spread), paint("fill", shadow.color));
+        match shadow.blur > 0.0 {
+            true => {
+                let id = self.blur(shadow.blur, BlurEdge::Transparent);
+                self.body.push_str(&format!("<g filter=\"url(#f{id})\">{element}</g>"));
+            },
+            false => self.body.push_str(&element)
+        }
+    }
+
+    fn image(&mut self, offset: (f32, f32), image: &Image) {
+        let [w, h] = image.shape.wh();
+        if w <= 0.0 || h <= 0.0 || image.image.width() == 0 || image.image.height() == 0 {return;}
+        let Some(data) = png(&image.image, (w, h), image.color, image.filter) else {return};
+        let (center, rotate) = placement(&image.shape, offset);
+        let clip = match image.shape {
+            ShapeType::Rectangle(..) => String::new(),
+            _ => {
+                let id = self.id();
+                self.defs.push_str(&format!("<clipPath id=\"s{id}\">{}/></clipPath>\n", outline(&image.shape, center, 0.0)));
+                format!(" clip-path=\"url(#s{id})\"")
+            }
+        };
+        self.body.push_str(&format!(
+            "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"{rotate}{clip} href=\"data:image/png;base64,{data}\"/>",
+            n(center.0 - w / 2.0), n(center.1 - h / 2.0), n(w), n(h)
+        ));
+    }
+
+    /// Each line is split into runs of glyphs sharing a font, size, color and baseline, every
+    /// glyph placed at its laid out pen position.
+    fn text(&mut self, offset: (f32, f32), text: &Text) {
+        let rotation = text.rotation(offset);
+        if let Some([a, b, c, d, e, f]) = rotation.map(|m| m.map(n)) {
+            self.body.push_str(&format!("<g transform=\"matrix({a} {b} {c} {d} {e} {f})\">"));
+        }
+        for line in text.lines() {
+            let mut runs: Vec<Run> = vec![];
+            for (character, size) in line.sized(text) {
+                let grapheme = &character.0;
+                if grapheme.chars().all(char::is_whitespace) {continue;}
+                let (x, y) = character.origin(size);
+                let (pen, baseline) = (offset.0 + x, offset.1 + y);
+
+                let family = self.font(&character.2);
+                let color = character.3.unwrap_or(Color::BLACK);
+                //Graphemes of several characters get a run of their own, as each position
+                //applies to a single character
+                let single = grapheme.chars().count() == 1;
+                match runs.last_mut() {
+                    Some(run) if single && run.single && run.family == family && run.size == size
+                        && run.color == color && run.baseline == baseline => {
+                        run.xs.push(pen);
+                        run.content.push_str(grapheme);
+                    },
+                    _ => runs.push(Run{family, size, color, baseline, single, xs: vec![pen], content: grapheme.clone()})
+                }
+            }
+            for run in runs {
+                let xs = run.xs.iter().map(|x| n(*x).to_string()).collect::<Vec<_>>().join(" ");
+                self.body.push_str(&format!(
+                    "<text x=\"{xs}\" y=\"{}\" font-family=\"font{}\" font-size=\"{}\"{}>{}</text>",
+                    n(run.baseline), run.family, n(run.size), paint("fill", run.color), escape(&run.content)
+                ));
+            }
+        }
+        if rotation.is_some() {
+            self.body.push_str("</g>");
+        }
+    }
+
+    fn path(&mut self, offset: (f32, f32), path: &Path) {
+        let fill = match &path.gradient {
+            Some(gradient) => format!(" fill=\"url(#g{})\"", self.gradient(gradient)),
+            None if path.color.3 == 0 => " fill=\"none\"".to_string(),
+            None => paint("fill", path.color),
+        };
+        let rule = match path.fill_rule {
+            FillRule::NonZero => "",
+            FillRule::EvenOdd => " fill-rule=\"evenodd\"",
+        };
+        let stroke = path.stroke.filter(|s| s.width > 0.0).map(|s| {
+            let cap = match s.cap {
+                LineCap::Butt => "",
+                LineCap::Round => " stroke-linecap=\"round\"",
+                LineCap::Square => " stroke-linecap=\"square\"",
+            };
+            let join = match s.join {
+                LineJoin::Miter => "",
+                LineJoin::Round => " stroke-linejoin=\"round\"",
+                LineJoin::Bevel => " stroke-linejoin=\"bevel\"",
+            };
+            format!("{}{cap}{join}", line("stroke", s.color, s.width))
+        }).unwrap_or_default();
+        self.body.push_str(&format!(
+            "<path transform=\"translate({} {})\" d=\"{}\"{fill}{rule}{stroke}/>",
+            n(offset.0), n(offset.1), path.data
+        ));
+    }
+}
+
+struct Run {
+    family: usize,
+    size: f32,
+    color: Color,
+    baseline: f32,
+    single: bool,
+    xs: Vec<f32>,
+    content: String,
+}
+
+/// Center of `shape` placed at `offset`, and the attribute rotating it around that center.
+fn placement(shape: &ShapeType, offset: (f32, f32)) -> ((f32, f32), String) {
+    let center = shape.center(offset);
+    let angle = shape.angle();
+    let rotate = match angle == 0.0 {
+        true => String::new(),
+        false => format!(" transform=\"rotate({} {} {})\"", n(angle), n(center.0), n(center.1))
+    };
+    (center, rotate)
+}
+
+/// The unrotated outline of `shape` around `center`, grown by `grow` on every side, as an
+/// unterminated element.
+fn outline(shape: &ShapeType, center: (f32, f32), grow: f32) -> String {
+    let [w, h] = shape.wh();
+    let (gw, gh) = ((w + 2.0 * grow).max(0.0), (h + 2.0 * grow).max(0.0));
+    let rect = |radius: f32| {
+        let radius = match radius > 0.0 {
+            true => format!(" rx=\"{}\"", n(radius.min(gw.min(gh) / 2.0))),
+            false => String::new()
+        };
+        format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{radius}",
+            n(center.0 - gw / 2.0), n(center.1 - gh / 2.0), n(gw), n(gh)
+        )
+    };
+    match *shape {
+        ShapeType::Ellipse(..) =>
+            format!("<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"", n(center.0), n(center.1), n(gw / 2.0), n(gh / 2.0)),
+        ShapeType::Rectangle(..) => rect(0.0),
+        ShapeType::RoundedRectangle(_, _, _, radius) | ShapeType::SmoothRoundedRectangle(_, _, _, radius, _) => rect(radius + grow),
+        ShapeType::Arc(..) => {
+            let (start, sweep) = shape.sweep().unwrap();
+            let (rx, ry) = (gw / 2.0, gh / 2.0);
+            let thickness = shape.stroke();
+            let d = if thickness > 0.0 && thickness < w.min(h) / 2.0 {
+                let (ix, iy) = ((rx - thickness - 2.0 * grow).max(0.0), (ry - thickness - 2.0 * grow).max(0.0));
+                let outer = arc_point(center, (rx, ry), start);
+                let inner = arc_point(center, (ix, iy), start + sweep);
+                format!(
+                    "M{} {}{} L{} {}{} Z", n(outer.0), n(outer.1), arc(center, rx, ry, start, sweep),
+                    n(inner.0), n(inner.1), arc(center, ix, iy, start + sweep, -sweep)
+                )
+            } else if sweep >= 360.0 {
+                let (x, y) = arc_point(center, (rx, ry), start);
+                format!("M{} {}{} Z", n(x), n(y), arc(center, rx, ry, start, sweep))
+            } else {
+                let (x, y) = arc_point(center, (rx, ry), start);
+                format!("M{} {} L{} {}{} Z", n(center.0), n(center.1), n(x), n(y), arc(center, rx, ry, start, sweep))
+            };
+            format!("<path d=\"{d}\"")
+        },
+        ShapeType::Polygon(..) | ShapeType::Star(..) => {
+            let (sx, sy) = (if w > 0.0 {gw / w} else {0.0}, if h > 0.0 {gh / h} else {0.0});
+            let points = shape.vertices().into_iter()
+                .map(|[x, y]| format!("{},{}", n(center.0 + x * sx), n(center.1 + y * sy)))
+                .collect::<Vec<_>>().join(" ");
+            format!("<polygon points=\"{points}\"")
+        }
+    }
+}
+
+/// Elliptical arc commands from the point at `start` along `sweep` degrees, split in half when
+/// longer than a half turn so neither part needs the large arc flag.
+fn arc(center: (f32, f32), rx: f32, ry: f32, start: f32, sweep: f32) -> String {
+    let sweep = sweep.clamp(-360.0, 360.0);
+    let flag = if sweep >= 0.0 {1} else {0};
+    let segments: &[f32] = if sweep.abs() > 180.0 {&[0.5, 1.0]} else {&[1.0]};
+    segments.iter().map(|f| {
+        let (x, y) = arc_point(center, (rx, ry), start + sweep * f);
+        format!(" A{} {} 0 0 {flag} {} {}", n(rx), n(ry), n(x), n(y))
+    }).collect()
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+/// `name` and `name-opacity` attributes for `color`.
+fn paint(name: &str, color: Color) -> String {
+    let mut attributes = format!(" {name}=\"{}\"", hex(color));
+    if color.3 < 255 {
+        attributes.push_str(&format!(" {name}-opacity=\"{}\"", n(color.3 as f32 / 255.0)));
+    }
+    attributes
+}
+
+fn line(name: &str, color: Color, width: f32) -> String {
+    format!("{} {name}-width=\"{}\"", paint(name, color), n(width))
+}
+
+/// The part of `image` the renderer would show in `size`, encoded as base64 PNG.
+fn png(image: &RgbaImage, size: (f32, f32), tint: Option<Color>, filter: Option<ColorMatrix>) -> Option<String> {
+    let mut bytes = vec![];
+    Image::visible(image, size, tint, filter).write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png).ok()?;
+    Some(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+fn escape(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        c => c.to_string(),
+    }).collect()
+}
+
+/// Rounds to a thousandth of a pixel to keep the document short.
+fn n(value: f32) -> f32 {
+    (value * 1000.0).round() / 1000.0 + 0.0
+}
+
+/// The style attribute blending a group by `mode`, empty for the Porter-Duff operators CSS
+/// has no blend mode for.
+fn blend_style(mode: BlendMode) -> &'static str {match mode {
+    BlendMode::Multiply => " style=\"mix-blend-mode:multiply\"",
+    BlendMode::Screen => " style=\"mix-blend-mode:screen\"",
+    BlendMode::Add => " style=\"mix-blend-mode:plus-lighter\"",
+    BlendMode::Overlay => " style=\"mix-blend-mode:overlay\"",
+    BlendMode::Darken => " style=\"mix-blend-mode:darken\"",
+    BlendMode::Lighten => " style=\"mix-blend-mode:lighten\"",
+    BlendMode::Difference => " style=\"mix-blend-mode:difference\"",
+    _ => "",
+}}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PathData, Transform};
+    use super::*;
+
+    fn shape(offset: (f32, f32), shape: ShapeType) -> Instruction {
+        Instruction(Area::new(offset, None), Item::Shape(Shape::new(shape, Color(200, 20, 20, 255))))
+    }
+
+    #[test]
+    fn exported_geometry_imports_back_in_place() {
+        let mut turned = shape((60.0, 0.0), ShapeType::Rectangle(0.0, (30.0, 10.0), 0.0));
+        turned.0.transform = Some(Transform::around((15.0, 5.0)).rotate(90.0));
+        let instructions = [
+            shape((0.0, 0.0), ShapeType::RoundedRectangle(0.0, (40.0, 20.0), 0.0, 6.0)),
+            shape((0.0, 30.0), ShapeType::Ellipse(4.0, (40.0, 20.0), 0.0)),
+            shape((50.0, 30.0), ShapeType::Arc(6.0, (40.0, 40.0), 0.0, 270.0, 180.0, false)),
+            shape((0.0, 60.0), ShapeType::Rectangle(0.0, (40.0, 10.0), 45.0)),
+            turned,
+            Instruction(Area::new((100.0, 0.0), None), Item::Path(Path{
+                data: PathData::parse("M0 0 H30 V30 H0 Z M10 10 H20 V20 H10 Z").unwrap(),
+                color: Color::BLACK,
+                gradient: None,
+                fill_rule: FillRule::EvenOdd,
+                stroke: None,
+            })),
+        ];
+        let size = (140.0, 100.0);
+        let svg = Svg::parse(&Svg::export(&instructions, size), None).unwrap();
+        assert_eq!(svg.size(), size);
+        let imported = svg.instructions((0.0, 0.0), size);
+        for point in [
+            (20.0, 10.0), (1.0, 1.0), (20.0, 32.0), (20.0, 40.0), (2.0, 40.0), (85.0, 45.0), (55.0, 45.0), (70.0, 33.0), (70.0, 67.0),
+            (20.0, 65.0), (2.0, 62.0), (75.0, 15.0), (75.0, 25.0), (85.0, 5.0), (105.0, 5.0), (115.0, 15.0), (135.0, 5.0),
+        ] {
+            assert_eq!(
+                Instruction::hit(&imported, point).is_some(), Instruction::hit(&instructions, point).is_some(),
+                "at {point:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unpopped_groups_are_closed() {
+        let instructions = [
+            Instruction(Area::default(), Item::PushClip(Clip::Shape(ShapeType::Ellipse(0.0, (10.0, 10.0), 0.0)))),
+            Instruction(Area::default(), Item::PushLayer(Layer{blend: BlendMode::Multiply, ..Layer::new(0.5)})),
+            Instruction(Area::default(), Item::PushLayer(Layer::new(0.5))),
+            Instruction(Area::default(), Item::PopLayer),
+            shape((0.0, 0.0), ShapeType::Rectangle(0.0, (10.0, 10.0), 0.0)),
+        ];
+        let document = Svg::export(&instructions, (10.0, 10.0));
+        assert!(document.contains("mix-blend-mode:multiply"));
+        assert_eq!(document.matches("<g").count(), document.matches("</g>").count());
+        assert!(Svg::parse(&document, None).is_ok());
+    }
+
+    #[test]
+    fn text_and_numbers_are_written_safely() {
+        assert_eq!(escape("a<b>&\"c\"'"), "a&lt;b&gt;&amp;&quot;c&quot;'");
+        assert_eq!(super::super::unescape(&escape("<&\">")), "<&\">");
+        assert_eq!(n(1.23456), 1.235);
+        assert_eq!(n(-0.0001).to_string(), "0");
+        assert_eq!(hex(Color(1, 171, 255, 0)), "#01abff");
+        assert_eq!(paint("fill", Color(0, 0, 0, 255)), " fill=\"#000000\"");
+        assert_eq!(paint("fill", Color(0, 0, 0, 51)), " fill=\"#000000\" fill-opacity=\"0.2\"");
+    }
+}
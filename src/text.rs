@@ -1,6 +1,6 @@
-use super::Color;
+use super::{Color, Transform};
 use std::ops::Deref;
-use std::sync::{Mutex, Arc};
+use std::sync::{Mutex, Arc, Weak};
 use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hasher, Hash};
 use lazy_static::lazy_static;
@@ -13,6 +13,15 @@ lazy_static! {
         .expect("failed to load emoji font");
 }
 
+type LoadedFont = (Weak<fontdue::Font>, Arc<[u8]>);
+
+lazy_static! {
+    /// Every font alive loaded with [`Font::from_bytes`] and its file, by [`Font::id`], so
+    /// recordings and exported documents can embed it and deserialized spans can find it again
+    /// without keeping it alive. Dead ones are dropped whenever another is loaded.
+    static ref FONTS: Mutex<HashMap<u64, LoadedFont>> = Mutex::default();
+}
+
 
 fn is_emoji_grapheme(g: &str) -> bool {
     g.chars().any(|c| {
@@ -29,14 +38,44 @@ fn is_emoji_grapheme(g: &str) -> bool {
 pub struct Font(pub Arc<fontdue::Font>);
 
 impl Font {
+    /// Parses a font file, sharing the font already loaded from the same file while it is alive.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
-        Ok(Font(Arc::new(fontdue::Font::from_bytes(
+        let font = Font(Arc::new(fontdue::Font::from_bytes(
             bytes,
             fontdue::FontSettings {
                 scale: 160.0,
                 ..Default::default()
             },
-        )?)))
+        )?));
+        let mut fonts = FONTS.lock().unwrap();
+        fonts.retain(|_, (font, _)| font.strong_count() > 0);
+        match fonts.get(&font.id()) {
+            Some((loaded, file)) => Ok(loaded.upgrade().filter(|_| **file == *bytes).map_or(font, Font)),
+            None => {
+                fonts.insert(font.id(), (Arc::downgrade(&font.0), bytes.into()));
+                Ok(font)
+            }
+        }
+    }
+
+    /// The file this font was loaded from with [`Font::from_bytes`], `None` for one made from a
+    /// [`fontdue::Font`] directly.
+    pub fn bytes(&self) -> Option<Arc<[u8]>> {
+        let fonts = FONTS.lock().unwrap();
+        fonts.get(&self.id()).filter(|(font, _)| std::ptr::eq(font.as_ptr(), Arc::as_ptr(&self.0))).map(|(_, file)| file.clone())
+    }
+
+    /// Identifier of the font file, its [`file_hash`](fontdue::Font::file_hash), stable across
+    /// runs on platforms of the same pointer width.
+    pub fn id(&self) -> u64 {
+        self.file_hash() as u64
+    }
+
+    /// A font loaded with [`Font::from_bytes`] with the given [`Font::id`], if it is still alive.
+    #[cfg(feature = "serde")]
+    pub(crate) fn find(id: u64) -> Option<Font> {
+        let fonts = FONTS.lock().unwrap();
+        fonts.get(&id).and_then(|(font, _)| Some(Font(font.upgrade()?)))
     }
 }
 
@@ -61,6 +100,7 @@ impl Hash for Font {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Align {
     Left,
     Center,
@@ -68,6 +108,7 @@ pub enum Align {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub text: String,
     pub font_size: f32,
@@ -113,12 +154,16 @@ impl Span {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Text {
     pub spans: Vec<Span>,
     pub width: Option<f32>,
     pub align: Align,
     pub cursor: Option<usize>,
     pub max_lines: Option<u32>,
+    /// Clockwise rotation in degrees of the whole block around its origin, the offset of its area.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub angle: f32,
 }
 
 impl Hash for Text {
@@ -155,9 +200,15 @@ impl Text {
             align,
             cursor: None,
             max_lines: None,
+            angle: 0.0,
         }
     }
 
+    /// The matrix rotating the block placed at `offset` around it, when it has an angle.
+    pub(crate) fn rotation(&self, offset: (f32, f32)) -> Option<[f32; 6]> {
+        (self.angle != 0.0).then(|| Transform::IDENTITY.rotate(self.angle).screen(offset))
+    }
+
     pub fn scale(&mut self, scale: f32) {
         self.width = self.width.map(|w| scale*w);
         self.spans.iter_mut().for_each(|span| {
@@ -275,7 +326,7 @@ impl Text {
             lines.push(line.take());
         };
 
-        for s in &self.spans {
+        for (index, s) in self.spans.iter().enumerate() {
             let lm = s.font.horizontal_line_metrics(s.font_size).unwrap();
             let lh = s.line_height.unwrap_or(lm.new_line_size);
 
@@ -375,6 +426,7 @@ impl Text {
                             lh,
                             aw,
                         ));
+                        line.3.push(index);
 
                         line.0 += aw + s.kerning;
                         line.1 = line.1.max(lh);
@@ -407,6 +459,7 @@ impl Text {
 
                 if let Some(last) = lines.last_mut() {
                     last.2.truncate(last.2.len().saturating_sub(3));
+                    last.3.truncate(last.2.len());
 
                     let s = &self.spans[0];
                     let lm = s.font.horizontal_line_metrics(s.font_size).unwrap();
@@ -430,6 +483,7 @@ impl Text {
                             lh,
                             aw,
                         ));
+                        last.3.push(0);
 
                         x += aw;
                         last.0 += aw;
@@ -443,6 +497,9 @@ impl Text {
     }
 }
 
+/// A laid out grapheme: `Character(grapheme, (x, y, w, h), font, color, line_height, advance)`.
+///
+/// The rectangle is the glyph's bounding box relative to the top left of the text.
 #[derive(Debug, Clone)]
 pub struct Character(
     pub String,
@@ -453,14 +510,66 @@ pub struct Character(
     pub f32,
 );
 
+impl Character {
+    /// Pen position on the baseline the glyph was laid out from at `font_size`, relative to the
+    /// text.
+    #[cfg(any(feature = "svg", feature = "pdf"))]
+    pub(crate) fn origin(&self, font_size: f32) -> (f32, f32) {
+        let mut chars = self.0.chars().filter(|c| *c != '\u{fe0f}' && *c != '\u{200d}');
+        let Some(first) = chars.next() else {return (self.1.0, self.1.1 + self.1.3)};
+        let metrics = self.2.metrics(first, font_size);
+        let ymin = chars.fold(metrics.bounds.ymin, |m, c| m.min(self.2.metrics(c, font_size).bounds.ymin));
+        (self.1.0 - metrics.bounds.xmin, self.1.1 + self.1.3 + ymin)
+    }
+}
+
+/// A laid out line: `Line(width, height, characters, spans)`, with the index of the span every
+/// character was laid out from.
 #[derive(Debug, Clone, Default)]
-pub(crate) struct Line(pub f32, pub f32, pub Vec<Character>);
+pub(crate) struct Line(pub f32, pub f32, pub Vec<Character>, pub Vec<usize>);
 
 impl Line {
+    /// Every character with the font size of its span in `text`.
+    #[cfg(any(feature = "svg", feature = "pdf"))]
+    pub(crate) fn sized<'a>(&'a self, text: &'a Text) -> impl Iterator<Item = (&'a Character, f32)> {
+        self.2.iter().zip(&self.3).map(|(character, span)| (character, text.spans[*span].font_size))
+    }
+
     fn take(&mut self) -> Self {
-        let l = Line(self.0, self.1, self.2.drain(..).collect());
+        let l = Line(self.0, self.1, self.2.drain(..).collect(), self.3.drain(..).collect());
         self.0 = 0.0;
         self.1 = 0.0;
         l
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fonts_are_known_by_their_file_while_alive() {
+        //Trailing bytes make a file of its own, which no other test or the emoji fallback keeps alive
+        let mut bytes = include_bytes!("../emoji.ttf").to_vec();
+        bytes.extend(b"registry");
+        let font = Font::from_bytes(&bytes).unwrap();
+        let (id, weak) = (font.id(), Arc::downgrade(&font.0));
+        //Loading the same file again shares the font
+        assert!(Arc::ptr_eq(&font.0, &Font::from_bytes(&bytes).unwrap().0));
+        assert_ne!(id, Font::from_bytes(include_bytes!("../emoji.ttf")).unwrap().id());
+        assert_eq!(font.bytes().as_deref(), Some(bytes.as_slice()));
+        //Made directly, the file is not known
+        let parsed = fontdue::Font::from_bytes(bytes.as_slice(), fontdue::FontSettings::default()).unwrap();
+        assert_eq!(Font(Arc::new(parsed)).bytes(), None);
+        #[cfg(feature = "serde")]
+        assert!(Font::find(id).is_some_and(|f| Arc::ptr_eq(&f.0, &font.0)));
+        drop(font);
+        assert!(weak.upgrade().is_none());
+        #[cfg(feature = "serde")]
+        assert!(Font::find(id).is_none());
+        //Loading any font frees the files of dropped ones
+        bytes.extend(b" again");
+        let _other = Font::from_bytes(&bytes).unwrap();
+        assert!(!FONTS.lock().unwrap().contains_key(&id));
+    }
+}
@@ -0,0 +1,243 @@
+/// A 2D projective transform applied to everything drawn in an [`Area`](crate::Area), around a pivot.
+///
+/// `matrix` is the affine part `[a, b, c, d, e, f]` (as in SVG) and `origin` the pivot it is
+/// applied around, relative to the offset of the area. `perspective` is the bottom row `(g, h)`
+/// of the full 3x3 matrix: a point `(x, y)` relative to the pivot ends up divided by
+/// `w = g*x + h*y + 1`, so it is `(0, 0)` for affine transforms. The builder methods each apply one
+/// more step after the ones before, so `Transform::around((50.0, 50.0)).scale(2.0, 2.0).rotate(45.0)`
+/// scales and then rotates about the point 50 pixels right and down from the offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub matrix: [f32; 6],
+    pub origin: (f32, f32),
+    pub perspective: (f32, f32),
+}
+
+impl Default for Transform {
+    fn default() -> Self {Transform::IDENTITY}
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform{matrix: IDENTITY, origin: (0.0, 0.0), perspective: (0.0, 0.0)};
+
+    /// The identity transform pivoting around `origin`.
+    pub fn around(origin: (f32, f32)) -> Self {
+        Transform{origin, ..Transform::IDENTITY}
+    }
+
+    pub fn translate(self, x: f32, y: f32) -> Self {
+        self.then([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    /// Rotates clockwise on screen by `degrees`, like the angle of a shape.
+    pub fn rotate(self, degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        self.then([cos, sin, -sin, cos, 0.0, 0.0])
+    }
+
+    pub fn scale(self, x: f32, y: f32) -> Self {
+        self.then([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    /// Shears along x by `x_degrees` and along y by `y_degrees`, as SVG's `skewX` and `skewY`.
+    pub fn skew(self, x_degrees: f32, y_degrees: f32) -> Self {
+        self.then([1.0, y_degrees.to_radians().tan(), x_degrees.to_radians().tan(), 1.0, 0.0, 0.0])
+    }
+
+    /// Turns around the vertical axis through the pivot by `degrees`, seen from `distance`
+    /// pixels in front of the screen. Positive angles move the right edge away, like a card
+    /// being flipped from left to right.
+    pub fn rotate_y(self, degrees: f32, distance: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        self.then_projective([cos, 0.0, 0.0, 0.0, 1.0, 0.0, sin / distance, 0.0, 1.0])
+    }
+
+    /// Turns around the horizontal axis through the pivot by `degrees`, seen from `distance`
+    /// pixels in front of the screen. Positive angles move the top edge away.
+    pub fn rotate_x(self, degrees: f32, distance: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        self.then_projective([1.0, 0.0, 0.0, 0.0, cos, 0.0, 0.0, -sin / distance, 1.0])
+    }
+
+    /// Applies `matrix` after this transform.
+    pub fn then(self, matrix: [f32; 6]) -> Self {
+        self.then_projective(projective(matrix))
+    }
+
+    /// Applies the row major 3x3 `matrix` after this transform.
+    pub fn then_projective(self, matrix: [f32; 9]) -> Self {
+        Transform::from_projective(multiply3(matrix, self.projective()), self.origin)
+    }
+
+    /// The matrix relative to the pivot as a row major 3x3 matrix.
+    pub fn projective(&self) -> [f32; 9] {
+        let [a, b, c, d, e, f] = self.matrix;
+        let (g, h) = self.perspective;
+        [a, c, e, b, d, f, g, h, 1.0]
+    }
+
+    /// Whether the transform has a perspective, which the exporters leave out.
+    pub fn is_affine(&self) -> bool {
+        self.perspective == (0.0, 0.0)
+    }
+
+    /// The affine matrix taking pixels of an area at `offset` to the screen, pivot included.
+    /// The perspective is left out.
+    pub fn screen(&self, offset: (f32, f32)) -> [f32; 6] {
+        let (px, py) = (offset.0 + self.origin.0, offset.1 + self.origin.1);
+        multiply([1.0, 0.0, 0.0, 1.0, px, py], multiply(self.matrix, [1.0, 0.0, 0.0, 1.0, -px, -py]))
+    }
+
+    /// The row major 3x3 matrix taking pixels of an area at `offset` to the screen, pivot and
+    /// perspective included.
+    pub fn projection(&self, offset: (f32, f32)) -> [f32; 9] {
+        let (px, py) = (offset.0 + self.origin.0, offset.1 + self.origin.1);
+        let to = projective([1.0, 0.0, 0.0, 1.0, px, py]);
+        let from = projective([1.0, 0.0, 0.0, 1.0, -px, -py]);
+        multiply3(to, multiply3(self.projective(), from))
+    }
+
+    /// The transform pivoting around `origin` with the row major 3x3 `matrix`, scaled so its
+    /// last entry is one.
+    pub(crate) fn from_projective(m: [f32; 9], origin: (f32, f32)) -> Self {
+        let s = if m[8].abs() < f32::EPSILON {1.0} else {1.0 / m[8]};
+        let m = m.map(|v| v * s);
+        Transform{matrix: [m[0], m[3], m[1], m[4], m[2], m[5]], origin, perspective: (m[6], m[7])}
+    }
+
+    /// How much the transform stretches lengths at most, for picking tessellation tolerances.
+    #[cfg(feature = "renderer")]
+    pub(crate) fn max_scale(&self) -> f32 {
+        let [a, b, c, d, _, _] = self.matrix;
+        (a*a + b*b).max(c*c + d*d).sqrt()
+    }
+}
+
+pub(crate) const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `m` applied after `n`.
+pub(crate) fn multiply(m: [f32; 6], n: [f32; 6]) -> [f32; 6] {[
+    m[0]*n[0] + m[2]*n[1],
+    m[1]*n[0] + m[3]*n[1],
+    m[0]*n[2] + m[2]*n[3],
+    m[1]*n[2] + m[3]*n[3],
+    m[0]*n[4] + m[2]*n[5] + m[4],
+    m[1]*n[4] + m[3]*n[5] + m[5],
+]}
+
+pub(crate) fn apply(m: [f32; 6], (x, y): (f32, f32)) -> (f32, f32) {
+    (m[0]*x + m[2]*y + m[4], m[1]*x + m[3]*y + m[5])
+}
+
+pub(crate) fn determinant(m: [f32; 6]) -> f32 {m[0]*m[3] - m[1]*m[2]}
+
+/// The row major 3x3 form of the affine `m`.
+pub(crate) fn projective([a, b, c, d, e, f]: [f32; 6]) -> [f32; 9] {
+    [a, c, e, b, d, f, 0.0, 0.0, 1.0]
+}
+
+/// The row major 3x3 `m` applied after `n`.
+pub(crate) fn multiply3(m: [f32; 9], n: [f32; 9]) -> [f32; 9] {
+    std::array::from_fn(|i| {
+        let (row, column) = (i / 3, i % 3);
+        (0..3).map(|k| m[row*3 + k] * n[k*3 + column]).sum()
+    })
+}
+
+/// Maps `(x, y)` through the row major 3x3 `m`, before the divide by the returned `w`.
+pub(crate) fn project(m: [f32; 9], (x, y): (f32, f32)) -> (f32, f32, f32) {
+    (m[0]*x + m[1]*y + m[2], m[3]*x + m[4]*y + m[5], m[6]*x + m[7]*y + m[8])
+}
+
+/// The inverse of the row major 3x3 `m`, or `None` when it collapses the plane.
+pub(crate) fn invert3(m: [f32; 9]) -> Option<[f32; 9]> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0*3 + c0]*m[r1*3 + c1] - m[r0*3 + c1]*m[r1*3 + c0];
+    let adjugate = [
+        cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2),
+        -cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2),
+        cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1),
+    ];
+    let det = m[0]*adjugate[0] + m[1]*adjugate[3] + m[2]*adjugate[6];
+    if det.abs() < f32::EPSILON {return None;}
+    Some(adjugate.map(|v| v / det))
+}
+
+/// The inverse of `m`, or `None` when it collapses the plane.
+pub(crate) fn invert(m: [f32; 6]) -> Option<[f32; 6]> {
+    let det = determinant(m);
+    if det.abs() < f32::EPSILON {return None;}
+    let [a, b, c, d, e, f] = m;
+    Some([d / det, -b / det, -c / det, a / det, (c*f - d*e) / det, (b*e - a*f) / det])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    /// `point` through the row major 3x3 `m`, divided by `w`.
+    fn map(m: [f32; 9], point: (f32, f32)) -> (f32, f32) {
+        let (x, y, w) = project(m, point);
+        (x / w, y / w)
+    }
+
+    #[test]
+    fn steps_apply_in_order_around_the_pivot() {
+        let transform = Transform::around((50.0, 50.0)).scale(2.0, 2.0).rotate(90.0);
+        let screen = transform.screen((10.0, 0.0));
+        //The pivot stays in place, a point right of it is scaled then turned clockwise to below it
+        assert!(close(apply(screen, (60.0, 50.0)), (60.0, 50.0)));
+        assert!(close(apply(screen, (70.0, 50.0)), (60.0, 70.0)));
+        let translated = Transform::IDENTITY.rotate(90.0).translate(5.0, 0.0);
+        assert!(close(apply(translated.screen((0.0, 0.0)), (1.0, 0.0)), (5.0, 1.0)));
+        assert!(translated.is_affine());
+        assert_eq!(Transform::default(), Transform::IDENTITY);
+    }
+
+    #[test]
+    fn affine_and_projective_forms_agree() {
+        let transform = Transform::around((20.0, 30.0)).skew(10.0, 0.0).rotate(33.0).scale(1.5, 0.5).translate(4.0, -2.0);
+        for point in [(0.0, 0.0), (25.0, 35.0), (-40.0, 12.5)] {
+            assert!(close(apply(transform.screen((3.0, 4.0)), point), map(transform.projection((3.0, 4.0)), point)));
+        }
+        assert_eq!(Transform::from_projective(transform.projective().map(|v| v * 4.0), transform.origin), transform);
+    }
+
+    #[test]
+    fn perspective_shrinks_the_side_turned_away() {
+        let transform = Transform::around((50.0, 50.0)).rotate_y(30.0, 500.0);
+        assert!(!transform.is_affine());
+        let m = transform.projection((0.0, 0.0));
+        assert!(close(map(m, (50.0, 50.0)), (50.0, 50.0)));
+        let (right, left) = (map(m, (100.0, 50.0)).0 - 50.0, 50.0 - map(m, (0.0, 50.0)).0);
+        assert!(right < left && right > 0.0);
+        let m = Transform::around((50.0, 50.0)).rotate_x(30.0, 500.0).projection((0.0, 0.0));
+        let (top, bottom) = (50.0 - map(m, (50.0, 0.0)).1, map(m, (50.0, 100.0)).1 - 50.0);
+        assert!(top < bottom && top > 0.0);
+    }
+
+    #[test]
+    fn inverses_undo_the_transform() {
+        let transform = Transform::around((10.0, 10.0)).rotate(20.0).scale(2.0, 3.0).rotate_y(40.0, 300.0).rotate_x(-15.0, 300.0);
+        let m = transform.projection((5.0, 7.0));
+        let inverse = invert3(m).unwrap();
+        for point in [(0.0, 0.0), (10.0, -20.0), (33.0, 44.0)] {
+            assert!(close(map(inverse, map(m, point)), point));
+        }
+        let product = multiply3(m, inverse);
+        let identity = projective(IDENTITY);
+        assert!(product.iter().zip(identity).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        let affine = [2.0, 1.0, -1.0, 3.0, 5.0, 6.0];
+        let inverse = invert(affine).unwrap();
+        assert!(close(apply(inverse, apply(affine, (7.0, -8.0))), (7.0, -8.0)));
+
+        assert_eq!(invert([1.0, 2.0, 2.0, 4.0, 0.0, 0.0]), None);
+        assert_eq!(invert3(Transform::IDENTITY.scale(0.0, 1.0).projective()), None);
+        assert_eq!(invert3([1.0, 2.0, 3.0, 2.0, 4.0, 6.0, 0.0, 0.0, 1.0]), None);
+    }
+}